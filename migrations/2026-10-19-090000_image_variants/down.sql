@@ -0,0 +1,11 @@
+CREATE TABLE "tests_without_variants" (
+                         "id"	INTEGER NOT NULL UNIQUE,
+                         "description"	TEXT NOT NULL,
+                         "answers"	TEXT NOT NULL,
+                         "right_answer_id"	INTEGER NOT NULL,
+                         "image" BLOB,
+                         PRIMARY KEY("id" AUTOINCREMENT)
+);
+INSERT INTO "tests_without_variants" SELECT "id", "description", "answers", "right_answer_id", "image" FROM "tests";
+DROP TABLE "tests";
+ALTER TABLE "tests_without_variants" RENAME TO "tests";
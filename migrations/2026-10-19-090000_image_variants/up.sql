@@ -0,0 +1,2 @@
+ALTER TABLE "tests" ADD COLUMN "image_thumbnail" BLOB;
+ALTER TABLE "tests" ADD COLUMN "image_retina" BLOB;
@@ -0,0 +1,20 @@
+CREATE TABLE "tests_with_images" (
+                         "id"	INTEGER NOT NULL UNIQUE,
+                         "description"	TEXT NOT NULL,
+                         "answers"	TEXT NOT NULL,
+                         "right_answer_id"	INTEGER NOT NULL,
+                         "image" BLOB,
+                         "image_thumbnail" BLOB,
+                         "image_retina" BLOB,
+                         PRIMARY KEY("id" AUTOINCREMENT)
+);
+INSERT INTO "tests_with_images"
+    SELECT "id", "description", "answers", "right_answer_id",
+           (SELECT "data" FROM "images" WHERE "hash" = "image_hash"),
+           (SELECT "data" FROM "images" WHERE "hash" = "thumbnail_hash"),
+           (SELECT "data" FROM "images" WHERE "hash" = "retina_hash")
+    FROM "tests";
+DROP TABLE "tests";
+ALTER TABLE "tests_with_images" RENAME TO "tests";
+DROP TABLE IF EXISTS "legacy_test_images";
+DROP TABLE "images";
@@ -0,0 +1,26 @@
+CREATE TABLE "images" (
+                         "hash"	TEXT NOT NULL UNIQUE,
+                         "content_type"	TEXT NOT NULL,
+                         "data"	BLOB NOT NULL,
+                         PRIMARY KEY("hash")
+);
+
+-- The images are keyed by their SHA-256 which SQL can't compute, so the inline BLOBs are moved to the `images` table
+-- by `db::images::adopt_legacy_images` on the next start and this table is dropped after that
+CREATE TABLE "legacy_test_images" AS
+    SELECT "id" AS "test_id", "image", "image_thumbnail", "image_retina" FROM "tests" WHERE "image" IS NOT NULL;
+
+CREATE TABLE "tests_with_image_hashes" (
+                         "id"	INTEGER NOT NULL UNIQUE,
+                         "description"	TEXT NOT NULL,
+                         "answers"	TEXT NOT NULL,
+                         "right_answer_id"	INTEGER NOT NULL,
+                         "image_hash"	TEXT REFERENCES "images"("hash"),
+                         "thumbnail_hash"	TEXT REFERENCES "images"("hash"),
+                         "retina_hash"	TEXT REFERENCES "images"("hash"),
+                         PRIMARY KEY("id" AUTOINCREMENT)
+);
+INSERT INTO "tests_with_image_hashes" ("id", "description", "answers", "right_answer_id")
+    SELECT "id", "description", "answers", "right_answer_id" FROM "tests";
+DROP TABLE "tests";
+ALTER TABLE "tests_with_image_hashes" RENAME TO "tests";
@@ -0,0 +1,15 @@
+DROP INDEX "tests_key";
+CREATE TABLE "tests_without_keys" (
+                         "id"	INTEGER NOT NULL UNIQUE,
+                         "description"	TEXT NOT NULL,
+                         "answers"	TEXT NOT NULL,
+                         "right_answer_id"	INTEGER NOT NULL,
+                         "image_hash"	TEXT REFERENCES "images"("hash"),
+                         "thumbnail_hash"	TEXT REFERENCES "images"("hash"),
+                         "retina_hash"	TEXT REFERENCES "images"("hash"),
+                         PRIMARY KEY("id" AUTOINCREMENT)
+);
+INSERT INTO "tests_without_keys"
+    SELECT "id", "description", "answers", "right_answer_id", "image_hash", "thumbnail_hash", "retina_hash" FROM "tests";
+DROP TABLE "tests";
+ALTER TABLE "tests_without_keys" RENAME TO "tests";
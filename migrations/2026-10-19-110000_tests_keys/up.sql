@@ -0,0 +1,3 @@
+ALTER TABLE "tests" ADD COLUMN "key" TEXT;
+ALTER TABLE "tests" ADD COLUMN "retired" BOOLEAN NOT NULL DEFAULT 0;
+CREATE UNIQUE INDEX "tests_key" ON "tests" ("key");
@@ -0,0 +1,19 @@
+DROP INDEX "tests_key";
+CREATE TABLE "tests_without_categories" (
+                         "id"	INTEGER NOT NULL UNIQUE,
+                         "description"	TEXT NOT NULL,
+                         "answers"	TEXT NOT NULL,
+                         "right_answer_id"	INTEGER NOT NULL,
+                         "image_hash"	TEXT REFERENCES "images"("hash"),
+                         "thumbnail_hash"	TEXT REFERENCES "images"("hash"),
+                         "retina_hash"	TEXT REFERENCES "images"("hash"),
+                         "key"	TEXT,
+                         "retired"	BOOLEAN NOT NULL DEFAULT 0,
+                         PRIMARY KEY("id" AUTOINCREMENT)
+);
+INSERT INTO "tests_without_categories"
+    SELECT "id", "description", "answers", "right_answer_id", "image_hash", "thumbnail_hash", "retina_hash", "key", "retired"
+    FROM "tests";
+DROP TABLE "tests";
+ALTER TABLE "tests_without_categories" RENAME TO "tests";
+CREATE UNIQUE INDEX "tests_key" ON "tests" ("key");
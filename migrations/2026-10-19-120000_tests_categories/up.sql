@@ -0,0 +1,2 @@
+ALTER TABLE "tests" ADD COLUMN "category" TEXT;
+ALTER TABLE "tests" ADD COLUMN "explanation" TEXT;
@@ -0,0 +1 @@
+DROP TABLE "lti_users";
@@ -0,0 +1,25 @@
+DROP TABLE "user_answers";
+-- SQLite can't drop a column, so the users are rebuilt. Dropping them would delete the LTI users referencing them,
+-- so those are kept aside meanwhile and their table is made again like the `lti_users` migration does
+CREATE TABLE "lti_users_kept" AS SELECT "id", "issuer", "subject", "user_id" FROM "lti_users";
+DROP TABLE "lti_users";
+CREATE TABLE "users_without_privacy" (
+                         "id"	INTEGER NOT NULL UNIQUE,
+                         "name"	TEXT NOT NULL,
+                         "second_name"	TEXT NOT NULL,
+                         "password"	TEXT NOT NULL,
+                         "scores"	INTEGER NOT NULL  DEFAULT  '0',
+                         PRIMARY KEY("id" AUTOINCREMENT)
+);
+INSERT INTO "users_without_privacy" SELECT "id", "name", "second_name", "password", "scores" FROM "users";
+DROP TABLE "users";
+ALTER TABLE "users_without_privacy" RENAME TO "users";
+CREATE TABLE "lti_users" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "issuer" TEXT NOT NULL,
+    "subject" TEXT NOT NULL,
+    "user_id" INTEGER NOT NULL REFERENCES "users"("id") ON DELETE CASCADE,
+    UNIQUE ("issuer", "subject")
+);
+INSERT INTO "lti_users" SELECT "id", "issuer", "subject", "user_id" FROM "lti_users_kept";
+DROP TABLE "lti_users_kept";
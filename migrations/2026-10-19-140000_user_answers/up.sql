@@ -0,0 +1,16 @@
+-- Every checked answer of a user, the leaderboard windows and the learning statistics are computed from it
+CREATE TABLE "user_answers" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "user_id" INTEGER NOT NULL REFERENCES "users"("id") ON DELETE CASCADE,
+    "test_id" INTEGER NOT NULL REFERENCES "tests"("id") ON DELETE CASCADE,
+    "answer_id" INTEGER NOT NULL,
+    "correct" BOOLEAN NOT NULL,
+    "scores" INTEGER NOT NULL,
+    -- Unix time in seconds
+    "answered_at" BIGINT NOT NULL
+);
+CREATE INDEX "user_answers_user" ON "user_answers" ("user_id", "answered_at");
+CREATE INDEX "user_answers_answered_at" ON "user_answers" ("answered_at");
+
+ALTER TABLE "users" ADD COLUMN "display_name" TEXT;
+ALTER TABLE "users" ADD COLUMN "leaderboard_hidden" BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,12 @@
+-- SM-2 schedule of every test a user has answered
+CREATE TABLE "review_states" (
+    "user_id" INTEGER NOT NULL REFERENCES "users"("id") ON DELETE CASCADE,
+    "test_id" INTEGER NOT NULL REFERENCES "tests"("id") ON DELETE CASCADE,
+    "repetitions" INTEGER NOT NULL,
+    "ease_factor" DOUBLE NOT NULL,
+    "interval_days" INTEGER NOT NULL,
+    -- Unix time in seconds
+    "due_at" BIGINT NOT NULL,
+    PRIMARY KEY ("user_id", "test_id")
+);
+CREATE INDEX "review_states_due_at" ON "review_states" ("user_id", "due_at");
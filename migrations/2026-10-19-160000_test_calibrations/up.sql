@@ -0,0 +1,10 @@
+-- IRT parameters of the tests fitted from the recorded answers
+CREATE TABLE "test_calibrations" (
+    "test_id" INTEGER PRIMARY KEY NOT NULL REFERENCES "tests"("id") ON DELETE CASCADE,
+    "difficulty" DOUBLE NOT NULL,
+    "discrimination" DOUBLE NOT NULL,
+    -- Answers the parameters were fitted from
+    "answers" INTEGER NOT NULL,
+    -- Unix time in seconds
+    "calibrated_at" BIGINT NOT NULL
+);
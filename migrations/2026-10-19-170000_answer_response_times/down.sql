@@ -0,0 +1,15 @@
+CREATE TABLE "user_answers_without_response_times" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "user_id" INTEGER NOT NULL REFERENCES "users"("id") ON DELETE CASCADE,
+    "test_id" INTEGER NOT NULL REFERENCES "tests"("id") ON DELETE CASCADE,
+    "answer_id" INTEGER NOT NULL,
+    "correct" BOOLEAN NOT NULL,
+    "scores" INTEGER NOT NULL,
+    "answered_at" BIGINT NOT NULL
+);
+INSERT INTO "user_answers_without_response_times"
+SELECT "id", "user_id", "test_id", "answer_id", "correct", "scores", "answered_at" FROM "user_answers";
+DROP TABLE "user_answers";
+ALTER TABLE "user_answers_without_response_times" RENAME TO "user_answers";
+CREATE INDEX "user_answers_user" ON "user_answers" ("user_id", "answered_at");
+CREATE INDEX "user_answers_answered_at" ON "user_answers" ("answered_at");
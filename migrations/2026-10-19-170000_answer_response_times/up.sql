@@ -0,0 +1,2 @@
+-- Milliseconds the user took to answer, as reported by the client
+ALTER TABLE "user_answers" ADD COLUMN "response_ms" INTEGER;
@@ -0,0 +1,12 @@
+-- The marathon of a user walking through the bank in the order of the tests ids
+CREATE TABLE "marathons" (
+    "user_id" INTEGER PRIMARY KEY NOT NULL REFERENCES "users"("id") ON DELETE CASCADE,
+    -- Only the tests of the category are walked through, all of them when it's null
+    "category" TEXT,
+    -- The last answered test, the next one is the first served test after it
+    "last_test_id" INTEGER NOT NULL DEFAULT 0,
+    "answers" INTEGER NOT NULL DEFAULT 0,
+    "correct" INTEGER NOT NULL DEFAULT 0,
+    -- Unix time in seconds
+    "started_at" BIGINT NOT NULL
+);
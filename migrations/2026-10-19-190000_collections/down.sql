@@ -0,0 +1,3 @@
+DROP TABLE "collection_tests";
+DROP TABLE "collections";
+DROP TABLE "bookmarks";
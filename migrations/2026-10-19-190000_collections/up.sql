@@ -0,0 +1,25 @@
+-- Tests a user saved
+CREATE TABLE "bookmarks" (
+    "user_id" INTEGER NOT NULL REFERENCES "users"("id") ON DELETE CASCADE,
+    "test_id" INTEGER NOT NULL REFERENCES "tests"("id") ON DELETE CASCADE,
+    -- Unix time in seconds
+    "created_at" BIGINT NOT NULL,
+    PRIMARY KEY ("user_id", "test_id")
+);
+-- Named personal collections the bookmarks are organized in
+CREATE TABLE "collections" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "user_id" INTEGER NOT NULL REFERENCES "users"("id") ON DELETE CASCADE,
+    "name" TEXT NOT NULL,
+    -- Unix time in seconds
+    "created_at" BIGINT NOT NULL,
+    UNIQUE ("user_id", "name")
+);
+-- A removed bookmark leaves all the collections of the user
+CREATE TABLE "collection_tests" (
+    "collection_id" INTEGER NOT NULL REFERENCES "collections"("id") ON DELETE CASCADE,
+    "user_id" INTEGER NOT NULL,
+    "test_id" INTEGER NOT NULL,
+    PRIMARY KEY ("collection_id", "test_id"),
+    FOREIGN KEY ("user_id", "test_id") REFERENCES "bookmarks"("user_id", "test_id") ON DELETE CASCADE
+);
@@ -0,0 +1,4 @@
+DROP TABLE "daily_answers";
+DROP TABLE "daily_attempts";
+DROP TABLE "daily_sets";
+DROP TABLE "user_time_zones";
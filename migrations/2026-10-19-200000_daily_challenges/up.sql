@@ -0,0 +1,38 @@
+-- IANA time zone the daily challenges of a user follow, UTC when the user has no row
+CREATE TABLE "user_time_zones" (
+    "user_id" INTEGER NOT NULL PRIMARY KEY REFERENCES "users"("id") ON DELETE CASCADE,
+    "time_zone" TEXT NOT NULL
+);
+-- The one scored attempt of a user at the challenge of a day
+CREATE TABLE "daily_attempts" (
+    "user_id" INTEGER NOT NULL REFERENCES "users"("id") ON DELETE CASCADE,
+    -- The calendar day in the time zone of the user, YYYY-MM-DD
+    "day" TEXT NOT NULL,
+    "questions" INTEGER NOT NULL,
+    "answers" INTEGER NOT NULL DEFAULT 0,
+    "correct" INTEGER NOT NULL DEFAULT 0,
+    -- Unix time in seconds the last question was answered at, null until then
+    "finished_at" BIGINT,
+    -- Unix times in seconds the day starts and ends at in the time zone of the user, the end is excluded
+    "starts_at" BIGINT NOT NULL,
+    "ends_at" BIGINT NOT NULL,
+    PRIMARY KEY ("user_id", "day")
+);
+CREATE INDEX "daily_attempts_day" ON "daily_attempts" ("day");
+CREATE INDEX "daily_attempts_user_starts_at" ON "daily_attempts" ("user_id", "starts_at");
+-- The tests of the challenge of a day, pinned when the day is first requested
+CREATE TABLE "daily_sets" (
+    "day" TEXT NOT NULL,
+    "position" INTEGER NOT NULL,
+    "test_id" INTEGER NOT NULL REFERENCES "tests"("id") ON DELETE CASCADE,
+    PRIMARY KEY ("day", "position")
+);
+CREATE TABLE "daily_answers" (
+    "user_id" INTEGER NOT NULL,
+    "day" TEXT NOT NULL,
+    "test_id" INTEGER NOT NULL REFERENCES "tests"("id") ON DELETE CASCADE,
+    "answer_id" INTEGER NOT NULL,
+    "correct" BOOLEAN NOT NULL,
+    PRIMARY KEY ("user_id", "day", "test_id"),
+    FOREIGN KEY ("user_id", "day") REFERENCES "daily_attempts"("user_id", "day") ON DELETE CASCADE
+);
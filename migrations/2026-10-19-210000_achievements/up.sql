@@ -0,0 +1,22 @@
+-- Finished exams and daily challenges, the achievements are evaluated from them
+CREATE TABLE "exams" (
+    "id" INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    "user_id" INTEGER NOT NULL REFERENCES "users"("id") ON DELETE CASCADE,
+    -- 'lti' for an LTI exam session or 'daily' for a daily challenge
+    "source" TEXT NOT NULL,
+    "questions" INTEGER NOT NULL,
+    "correct" INTEGER NOT NULL,
+    -- Unix time in seconds
+    "finished_at" BIGINT NOT NULL
+);
+CREATE INDEX "exams_user" ON "exams" ("user_id");
+-- The badges earned by the users by the ids of their rules
+CREATE TABLE "user_achievements" (
+    "user_id" INTEGER NOT NULL REFERENCES "users"("id") ON DELETE CASCADE,
+    "achievement" TEXT NOT NULL,
+    -- Unix time in seconds
+    "earned_at" BIGINT NOT NULL,
+    -- The user has been notified about the badge
+    "seen" BOOLEAN NOT NULL DEFAULT 0,
+    PRIMARY KEY ("user_id", "achievement")
+);
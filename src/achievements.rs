@@ -0,0 +1,104 @@
+//! Badges the learners earn, every badge is a rule checked whenever an answer or an exam of the user is recorded
+
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Condition {
+    /// Right answers in the score history
+    CorrectAnswers(i64),
+    /// Days in a row the daily challenge was finished, the longest run counts
+    DailyStreak(i64),
+    /// The last answer to every served test of the category is right
+    CategoryMastered(&'static str),
+    /// An exam or a daily challenge with every answer right
+    PerfectExam,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Rule {
+    /// Stored with the earned badges, so it's never changed once released
+    pub id: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    pub condition: Condition,
+}
+
+pub const RULES: &[Rule] = &[
+    Rule {
+        id: "first_perfect_exam",
+        title: "First perfect exam",
+        description: "Answer every question of an exam or a daily challenge right",
+        condition: Condition::PerfectExam,
+    },
+    Rule {
+        id: "correct_100",
+        title: "100 correct answers",
+        description: "Answer 100 questions right",
+        condition: Condition::CorrectAnswers(100),
+    },
+    Rule {
+        id: "correct_1000",
+        title: "1000 correct answers",
+        description: "Answer 1000 questions right",
+        condition: Condition::CorrectAnswers(1000),
+    },
+    Rule {
+        id: "streak_7",
+        title: "7-day streak",
+        description: "Finish the daily challenge 7 days in a row",
+        condition: Condition::DailyStreak(7),
+    },
+    Rule {
+        id: "streak_30",
+        title: "30-day streak",
+        description: "Finish the daily challenge 30 days in a row",
+        condition: Condition::DailyStreak(30),
+    },
+    Rule {
+        id: "signs_mastered",
+        title: "Signs master",
+        description: "Have the last answer to every signs question right",
+        condition: Condition::CategoryMastered("Signs"),
+    },
+];
+
+/// `None` for a rule that isn't released anymore
+pub fn rule(id: &str) -> Option<&'static Rule> {
+    RULES.iter().find(|rule| rule.id == id)
+}
+
+/// How far the user is from the badge of a rule
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct RuleProgress {
+    pub current: i64,
+    pub target: i64,
+}
+
+impl RuleProgress {
+    /// A category without tests can't be mastered
+    pub fn is_reached(&self) -> bool {
+        self.target > 0 && self.current >= self.target
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_have_unique_ids_and_reachable_targets() {
+        let mut ids = RULES.iter().map(|rule| rule.id).collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.dedup();
+
+        assert_eq!(ids.len(), RULES.len());
+        assert_eq!(
+            rule("streak_7").map(|rule| rule.condition),
+            Some(Condition::DailyStreak(7))
+        );
+        assert_eq!(rule("retired_badge"), None);
+        assert!(RuleProgress { current: 7, target: 7 }.is_reached());
+        assert!(!RuleProgress { current: 6, target: 7 }.is_reached());
+        assert!(!RuleProgress { current: 0, target: 0 }.is_reached());
+    }
+}
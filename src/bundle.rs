@@ -0,0 +1,122 @@
+use anyhow::Context;
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+/// The file with the tests in the root of a bundle
+pub const BUNDLE_TESTS_FILE: &str = "tests.json";
+
+pub fn is_zip(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+}
+
+/// Files written either to a directory or to a zip archive when the path ends with `.zip`
+pub enum BundleWriter {
+    Dir(PathBuf),
+    Zip(ZipWriter<File>),
+}
+
+impl BundleWriter {
+    pub fn create(path: &Path) -> anyhow::Result<Self> {
+        if is_zip(path) {
+            Self::create_zip(path)
+        } else {
+            fs::create_dir_all(path).with_context(|| format!("failed to create {} directory", path.display()))?;
+            Ok(BundleWriter::Dir(path.to_owned()))
+        }
+    }
+
+    /// A zip archive whatever the extension is, for the formats which are zip archives under another name
+    pub fn create_zip(path: &Path) -> anyhow::Result<Self> {
+        let file = File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        Ok(BundleWriter::Zip(ZipWriter::new(file)))
+    }
+
+    /// `name` is a relative path with `/` separators
+    pub fn add_file(&mut self, name: &str, data: &[u8]) -> anyhow::Result<()> {
+        match self {
+            BundleWriter::Dir(dir) => {
+                let path = dir.join(name);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&path, data).with_context(|| format!("failed to write {}", path.display()))
+            }
+            BundleWriter::Zip(zip) => {
+                zip.start_file(
+                    name,
+                    FileOptions::default().compression_method(CompressionMethod::Deflated),
+                )?;
+                zip.write_all(data)?;
+                Ok(())
+            }
+        }
+    }
+
+    pub fn finish(self) -> anyhow::Result<()> {
+        if let BundleWriter::Zip(mut zip) = self {
+            zip.finish()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A directory removed with everything inside when it is dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(prefix: &str) -> anyhow::Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let path = env::temp_dir().join(format!("{}-{}-{}", prefix, process::id(), nanos));
+        fs::create_dir_all(&path).with_context(|| format!("failed to create {} directory", path.display()))?;
+
+        Ok(TempDir(path))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Extracts the zip archive into `dir`, entries pointing outside of it are rejected
+pub fn extract_zip(path: &Path, dir: &Path) -> anyhow::Result<()> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut archive = ZipArchive::new(file)?;
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let name = entry
+            .enclosed_name()
+            .ok_or_else(|| anyhow!("{} entry points outside of the archive", entry.name()))?
+            .to_owned();
+        let target = dir.join(name);
+
+        if entry.is_dir() {
+            fs::create_dir_all(&target)?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut entry, &mut File::create(&target)?)?;
+    }
+
+    Ok(())
+}
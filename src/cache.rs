@@ -0,0 +1,168 @@
+use log::debug;
+use openssl::base64;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use crate::db::{self, DbPool};
+
+/// A test ready to be served, tests with the same image share it
+pub struct CachedTest {
+    pub id: i32,
+    pub description: String,
+    pub answers: Vec<String>,
+    pub right_answer_id: i32,
+    pub category: Option<String>,
+    pub explanation: Option<String>,
+    pub image: Option<Arc<CachedImage>>,
+    pub thumbnail: Option<Arc<CachedImage>>,
+    pub retina: Option<Arc<CachedImage>>,
+}
+
+pub struct CachedImage {
+    pub data: Vec<u8>,
+    pub content_type: String,
+    /// The image hash in the images store
+    pub etag: String,
+}
+
+impl CachedImage {
+    /// Only old clients ask for it, so it isn't kept next to the data
+    pub fn base64(&self) -> String {
+        base64::encode_block(&self.data)
+    }
+}
+
+/// A snapshot of the whole tests bank
+pub struct CachedBank {
+    // Sorted by id
+    tests: Vec<Arc<CachedTest>>,
+}
+
+impl CachedBank {
+    pub fn random_test(&self) -> Option<Arc<CachedTest>> {
+        if self.tests.is_empty() {
+            return None;
+        }
+
+        Some(self.tests[rand::random::<usize>() % self.tests.len()].clone())
+    }
+
+    /// Up to `count` different tests in random order
+    pub fn random_tests(&self, count: usize) -> Vec<Arc<CachedTest>> {
+        let mut rng = rand::thread_rng();
+        rand::seq::index::sample(&mut rng, self.tests.len(), count.min(self.tests.len()))
+            .into_iter()
+            .map(|index| self.tests[index].clone())
+            .collect()
+    }
+
+    pub fn test(&self, id: i32) -> Option<Arc<CachedTest>> {
+        self.tests
+            .binary_search_by_key(&id, |test| test.id)
+            .ok()
+            .map(|index| self.tests[index].clone())
+    }
+
+    pub fn tests(&self) -> &[Arc<CachedTest>] {
+        &self.tests
+    }
+}
+
+#[derive(Serialize)]
+pub struct CacheStats {
+    pub tests: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// In-process copy of the tests bank so the hot path doesn't have to go to the DB.
+/// It is loaded lazily and has to be refreshed or invalidated after any change of the `tests` or `images` tables.
+/// Only the import writes them, the calibration and the statistics of the answers live in other tables
+#[derive(Default)]
+pub struct QuestionCache {
+    // `None` means the cache has been invalidated and has to be loaded from the DB again
+    bank: RwLock<Option<Arc<CachedBank>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl QuestionCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reloads the whole tests bank from the DB
+    pub fn refresh(&self, pool: &DbPool) -> anyhow::Result<()> {
+        self.load_bank(pool).map(|_| ())
+    }
+
+    /// The next request loads the bank again
+    pub fn invalidate(&self) {
+        *self.bank.write().unwrap() = None;
+    }
+
+    /// Returns the cached bank if it is loaded, `None` means it has to be loaded with `load_bank`
+    pub fn bank(&self) -> Option<Arc<CachedBank>> {
+        let bank = self.bank.read().unwrap().clone();
+
+        match bank {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+
+        bank
+    }
+
+    pub fn load_bank(&self, pool: &DbPool) -> anyhow::Result<Arc<CachedBank>> {
+        let images = db::get_images(pool)?
+            .into_iter()
+            .map(|image| {
+                let cached_image = CachedImage {
+                    data: image.data,
+                    content_type: image.content_type,
+                    etag: image.hash.clone(),
+                };
+                (image.hash, Arc::new(cached_image))
+            })
+            .collect::<HashMap<_, _>>();
+        let image = |hash: Option<String>| hash.and_then(|hash| images.get(&hash).cloned());
+
+        let tests = db::get_tests(pool)?
+            .into_iter()
+            .map(|test| {
+                Ok(Arc::new(CachedTest {
+                    id: test.id,
+                    description: test.description,
+                    answers: serde_json::from_str::<Vec<String>>(&test.answers)?,
+                    right_answer_id: test.right_answer_id,
+                    category: test.category,
+                    explanation: test.explanation,
+                    image: image(test.image_hash),
+                    thumbnail: image(test.thumbnail_hash),
+                    retina: image(test.retina_hash),
+                }))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        debug!("The tests cache was loaded with {} tests", tests.len());
+
+        let bank = Arc::new(CachedBank { tests });
+        *self.bank.write().unwrap() = Some(bank.clone());
+
+        Ok(bank)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            tests: self.bank.read().unwrap().as_ref().map_or(0, |bank| bank.tests.len()),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use chrono::NaiveDate;
+use diesel::{
+    dsl::count_star, insert_into, insert_or_ignore_into, BoolExpressionMethods, Connection, ExpressionMethods,
+    QueryDsl, RunQueryDsl, SqliteConnection,
+};
+use serde::Serialize;
+use std::{collections::HashMap, ops::Deref};
+
+use super::{
+    daily,
+    schema::{exams, tests, user_achievements, user_answers},
+    DbPool,
+};
+use crate::achievements::{rule, Condition, RuleProgress, RULES};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExamSource {
+    /// An LTI session in the exam mode
+    Lti,
+    Daily,
+}
+
+impl ExamSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            ExamSource::Lti => "lti",
+            ExamSource::Daily => "daily",
+        }
+    }
+}
+
+/// A badge with the progress of the user towards it
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Achievement {
+    pub id: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    /// Unix time in seconds, `None` until the badge is earned
+    pub earned_at: Option<i64>,
+    pub progress: RuleProgress,
+}
+
+/// A badge the user hasn't been notified about yet
+#[derive(Serialize, Debug, PartialEq)]
+pub struct EarnedAchievement {
+    pub id: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    /// Unix time in seconds
+    pub earned_at: i64,
+}
+
+fn rule_progress(db: &SqliteConnection, user_id: i32, condition: Condition) -> diesel::QueryResult<RuleProgress> {
+    let (current, target) = match condition {
+        Condition::CorrectAnswers(target) => {
+            let correct = user_answers::table
+                .filter(user_answers::user_id.eq(user_id).and(user_answers::correct.eq(true)))
+                .select(count_star())
+                .first::<i64>(db)?;
+            (correct, target)
+        }
+        Condition::DailyStreak(target) => {
+            let days = daily::finished_days(db, user_id)?;
+            // The current run doesn't matter, so any day does as today
+            let today = days.last().copied().unwrap_or(NaiveDate::MIN);
+            (daily::streak(&days, today).longest as i64, target)
+        }
+        Condition::CategoryMastered(category) => {
+            let served = tests::table
+                .filter(tests::category.eq(category).and(tests::retired.eq(false)))
+                .select(count_star())
+                .first::<i64>(db)?;
+            // The answers come in the order they were given, so the last one of every test wins
+            let last_answers = user_answers::table
+                .inner_join(tests::table)
+                .filter(
+                    user_answers::user_id
+                        .eq(user_id)
+                        .and(tests::category.eq(category))
+                        .and(tests::retired.eq(false)),
+                )
+                .select((user_answers::test_id, user_answers::correct))
+                .order((user_answers::answered_at, user_answers::id))
+                .load::<(i32, bool)>(db)?
+                .into_iter()
+                .collect::<HashMap<_, _>>();
+            (last_answers.values().filter(|correct| **correct).count() as i64, served)
+        }
+        Condition::PerfectExam => {
+            let perfect = exams::table
+                .filter(
+                    exams::user_id
+                        .eq(user_id)
+                        .and(exams::questions.gt(0))
+                        .and(exams::correct.eq(exams::questions)),
+                )
+                .select(count_star())
+                .first::<i64>(db)?;
+            (perfect.min(1), 1)
+        }
+    };
+
+    Ok(RuleProgress { current, target })
+}
+
+/// Awards the badges of the rules the user has just met, it's called in the transactions recording answers and exams
+pub(super) fn award(db: &SqliteConnection, user_id: i32, now: i64) -> diesel::QueryResult<()> {
+    let earned = user_achievements::table
+        .filter(user_achievements::user_id.eq(user_id))
+        .select(user_achievements::achievement)
+        .load::<String>(db)?;
+
+    for rule in RULES.iter().filter(|rule| !earned.iter().any(|id| id == rule.id)) {
+        if rule_progress(db, user_id, rule.condition)?.is_reached() {
+            insert_or_ignore_into(user_achievements::table)
+                .values((
+                    user_achievements::user_id.eq(user_id),
+                    user_achievements::achievement.eq(rule.id),
+                    user_achievements::earned_at.eq(now),
+                ))
+                .execute(db)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `record_exam` in a transaction of the caller
+pub(super) fn save_exam(
+    db: &SqliteConnection,
+    user_id: i32,
+    source: ExamSource,
+    questions: i32,
+    correct: i32,
+    now: i64,
+) -> diesel::QueryResult<()> {
+    insert_into(exams::table)
+        .values((
+            exams::user_id.eq(user_id),
+            exams::source.eq(source.as_str()),
+            exams::questions.eq(questions),
+            exams::correct.eq(correct),
+            exams::finished_at.eq(now),
+        ))
+        .execute(db)?;
+
+    award(db, user_id, now)
+}
+
+/// Saves the result of a finished exam and awards the badges it brings
+pub fn record_exam(
+    pool: &DbPool,
+    user_id: i32,
+    source: ExamSource,
+    questions: usize,
+    correct: usize,
+    now: i64,
+) -> anyhow::Result<()> {
+    let db = pool.get().unwrap();
+
+    db.transaction(|| save_exam(db.deref(), user_id, source, questions as i32, correct as i32, now))
+        .map_err(|err: diesel::result::Error| anyhow!("Failed to record an exam of {} user - {}", user_id, err))
+}
+
+/// Every badge, earned or not, in the order of the rules
+pub fn achievements(pool: &DbPool, user_id: i32) -> anyhow::Result<Vec<Achievement>> {
+    let db = pool.get().unwrap();
+
+    let load = || -> diesel::QueryResult<Vec<Achievement>> {
+        let earned = user_achievements::table
+            .filter(user_achievements::user_id.eq(user_id))
+            .select((user_achievements::achievement, user_achievements::earned_at))
+            .load::<(String, i64)>(db.deref())?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+
+        RULES
+            .iter()
+            .map(|rule| {
+                Ok(Achievement {
+                    id: rule.id,
+                    title: rule.title,
+                    description: rule.description,
+                    earned_at: earned.get(rule.id).copied(),
+                    progress: rule_progress(db.deref(), user_id, rule.condition)?,
+                })
+            })
+            .collect()
+    };
+
+    load().map_err(|err| anyhow!("Failed to load the achievements of {} user - {}", user_id, err))
+}
+
+/// The badges the user hasn't been notified about, the earliest first
+pub fn unseen_achievements(pool: &DbPool, user_id: i32) -> anyhow::Result<Vec<EarnedAchievement>> {
+    let db = pool.get().unwrap();
+
+    user_achievements::table
+        .filter(
+            user_achievements::user_id
+                .eq(user_id)
+                .and(user_achievements::seen.eq(false)),
+        )
+        .select((user_achievements::achievement, user_achievements::earned_at))
+        .order((user_achievements::earned_at, user_achievements::achievement))
+        .load::<(String, i64)>(db.deref())
+        .map(|earned| {
+            earned
+                .into_iter()
+                // The badges of retired rules stay in the DB but aren't shown
+                .filter_map(|(id, earned_at)| {
+                    rule(&id).map(|rule| EarnedAchievement {
+                        id: rule.id,
+                        title: rule.title,
+                        description: rule.description,
+                        earned_at,
+                    })
+                })
+                .collect()
+        })
+        .map_err(|err| anyhow!("Failed to load the new achievements of {} user - {}", user_id, err))
+}
+
+/// Marks the badges the user was notified about as seen, the ones earned since then are notified later.
+/// Returns how many were new
+pub fn mark_achievements_seen(pool: &DbPool, user_id: i32, achievement_ids: &[String]) -> anyhow::Result<usize> {
+    let db = pool.get().unwrap();
+
+    diesel::update(
+        user_achievements::table.filter(
+            user_achievements::user_id
+                .eq(user_id)
+                .and(user_achievements::achievement.eq_any(achievement_ids))
+                .and(user_achievements::seen.eq(false)),
+        ),
+    )
+    .set(user_achievements::seen.eq(true))
+    .execute(db.deref())
+    .map_err(|err| anyhow!("Failed to mark the achievements of {} user as seen - {}", user_id, err))
+}
+
+#[cfg(test)]
+mod _tests {
+    use super::*;
+    use crate::db::{get_tests, model, record_answer, testing::*};
+
+    #[test]
+    fn achievements_are_awarded_once_and_notified_until_seen() {
+        let (pool, path) = temp_db();
+        let forms = vec![
+            test_form(Some("s1"), "Stop sign", 1),
+            test_form(Some("s2"), "Yield sign", 1),
+            test_form(Some("q1"), "Speed limit", 1),
+        ]
+        .into_iter()
+        .map(|form| model::TestForm {
+            category: form
+                .key
+                .as_deref()
+                .filter(|key| key.starts_with('s'))
+                .map(|_| "Signs".to_owned()),
+            ..form
+        })
+        .collect();
+        import(forms, &pool, false).unwrap();
+        let ids = get_tests(&pool).unwrap().iter().map(|test| test.id).collect::<Vec<_>>();
+        let user_id = new_user(&pool, "ann").id;
+        let answer = |test_id: i32, answer_id: i32, answered_at: i64| model::NewUserAnswer {
+            answered_at,
+            ..model::NewUserAnswer::new(user_id, test_id, answer_id, answer_id == 1, 0)
+        };
+
+        record_answer(&answer(ids[0], 1, 10), &pool).unwrap();
+        record_answer(&answer(ids[2], 1, 20), &pool).unwrap();
+        let half_mastered = achievements(&pool, user_id).unwrap();
+        record_answer(&answer(ids[1], 1, 30), &pool).unwrap();
+        record_exam(&pool, user_id, ExamSource::Lti, 4, 3, 40).unwrap();
+        let before_perfect_exam = unseen_achievements(&pool, user_id).unwrap();
+        record_exam(&pool, user_id, ExamSource::Lti, 4, 4, 50).unwrap();
+        record_answer(&answer(ids[1], 2, 60), &pool).unwrap();
+        let unseen = unseen_achievements(&pool, user_id).unwrap();
+        let listed = achievements(&pool, user_id).unwrap();
+        let shown = |earned: &[EarnedAchievement]| earned.iter().map(|earned| earned.id.to_owned()).collect::<Vec<_>>();
+        // The perfect exam is earned after the notifications were shown, so it isn't seen yet
+        let seen = mark_achievements_seen(&pool, user_id, &shown(&before_perfect_exam)).unwrap();
+        let after_seen = unseen_achievements(&pool, user_id).unwrap();
+        let seen_later = mark_achievements_seen(&pool, user_id, &shown(&after_seen)).unwrap();
+        record_exam(&pool, user_id, ExamSource::Daily, 2, 2, 70).unwrap();
+        let after_all_seen = unseen_achievements(&pool, user_id).unwrap();
+
+        remove_temp_db(pool, path);
+        let signs = |achievements: &[Achievement]| {
+            achievements
+                .iter()
+                .find(|achievement| achievement.id == "signs_mastered")
+                .map(|achievement| {
+                    (
+                        achievement.earned_at,
+                        achievement.progress.current,
+                        achievement.progress.target,
+                    )
+                })
+        };
+        assert_eq!(signs(&half_mastered), Some((None, 1, 2)));
+        assert_eq!(
+            before_perfect_exam
+                .iter()
+                .map(|earned| (earned.id, earned.earned_at))
+                .collect::<Vec<_>>(),
+            vec![("signs_mastered", 30)]
+        );
+        assert_eq!(
+            unseen
+                .iter()
+                .map(|earned| (earned.id, earned.earned_at))
+                .collect::<Vec<_>>(),
+            vec![("signs_mastered", 30), ("first_perfect_exam", 50)]
+        );
+        // A badge stays earned when the progress towards it drops
+        assert_eq!(signs(&listed), Some((Some(30), 1, 2)));
+        assert_eq!(listed.len(), crate::achievements::RULES.len());
+        assert_eq!(seen, 1);
+        assert_eq!(
+            after_seen.iter().map(|earned| earned.id).collect::<Vec<_>>(),
+            vec!["first_perfect_exam"]
+        );
+        assert_eq!(seen_later, 1);
+        assert!(after_all_seen.is_empty());
+    }
+}
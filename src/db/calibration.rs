@@ -0,0 +1,289 @@
+use actix_web::{
+    rt::{self, time::delay_for},
+    web::block,
+};
+use chrono::Utc;
+use diesel::{delete, insert_into, BoolExpressionMethods, Connection, ExpressionMethods, QueryDsl, RunQueryDsl};
+use log::{error, info};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    ops::Deref,
+};
+
+use super::{
+    model::TestCalibration,
+    schema::{test_calibrations, tests, user_answers},
+    DbPool,
+};
+use crate::irt::{self, AbilityEstimate, IrtConfig, IrtModel, ItemParams, Response};
+
+#[derive(Default, Debug, PartialEq)]
+pub struct CalibrationSummary {
+    pub tests: usize,
+    pub answers: usize,
+    pub learners: usize,
+}
+
+impl fmt::Display for CalibrationSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} tests calibrated from {} answers of {} users",
+            self.tests, self.answers, self.learners
+        )
+    }
+}
+
+/// Fits the parameters of the served tests with at least `min_answers` answers and replaces the previous ones.
+/// Only the first answer of a user to a test is used, the later ones are skewed by having seen the test
+pub fn calibrate_tests(
+    pool: &DbPool,
+    model: IrtModel,
+    min_answers: usize,
+    now: i64,
+) -> anyhow::Result<CalibrationSummary> {
+    let db = pool.get().unwrap();
+
+    let answers = user_answers::table
+        .inner_join(tests::table)
+        .filter(tests::retired.eq(false))
+        .select((user_answers::user_id, user_answers::test_id, user_answers::correct))
+        .order((user_answers::answered_at, user_answers::id))
+        .load::<(i32, i32, bool)>(db.deref())
+        .map_err(|err| anyhow!("Failed to load the answers - {}", err))?;
+    let mut first_answers = HashMap::new();
+    for (user_id, test_id, correct) in answers {
+        first_answers.entry((user_id, test_id)).or_insert(correct);
+    }
+
+    let mut answers_count = BTreeMap::<i32, usize>::new();
+    for (_, test_id) in first_answers.keys() {
+        *answers_count.entry(*test_id).or_default() += 1;
+    }
+    let test_ids = answers_count
+        .into_iter()
+        .filter(|(_, answers)| *answers >= min_answers)
+        .map(|(test_id, _)| test_id)
+        .collect::<Vec<_>>();
+    let items = test_ids
+        .iter()
+        .enumerate()
+        .map(|(item, test_id)| (*test_id, item))
+        .collect::<HashMap<_, _>>();
+    let mut learners = HashMap::new();
+    let responses = first_answers
+        .iter()
+        .filter_map(|((user_id, test_id), correct)| {
+            let item = *items.get(test_id)?;
+            let learners_count = learners.len();
+            let learner = *learners.entry(*user_id).or_insert(learners_count);
+            Some(Response {
+                learner,
+                item,
+                correct: *correct,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let params = irt::calibrate(&responses, learners.len(), test_ids.len(), model);
+    let mut answers_count = vec![0; test_ids.len()];
+    for response in responses.iter() {
+        answers_count[response.item] += 1;
+    }
+    let calibrations = test_ids
+        .iter()
+        .zip(params.iter())
+        .zip(answers_count.iter())
+        .map(|((test_id, params), answers)| TestCalibration {
+            test_id: *test_id,
+            difficulty: params.difficulty,
+            discrimination: params.discrimination,
+            answers: *answers,
+            calibrated_at: now,
+        })
+        .collect::<Vec<_>>();
+
+    db.transaction(|| {
+        delete(test_calibrations::table).execute(db.deref())?;
+        insert_into(test_calibrations::table)
+            .values(&calibrations)
+            .execute(db.deref())
+    })
+    .map_err(|err: diesel::result::Error| anyhow!("Failed to save the calibrations - {}", err))?;
+
+    Ok(CalibrationSummary {
+        tests: calibrations.len(),
+        answers: responses.len(),
+        learners: learners.len(),
+    })
+}
+
+/// Calibrates the tests every `calibration_interval` in the background, the first time right away
+pub fn spawn_calibration(pool: DbPool, config: IrtConfig) {
+    if config.calibration_interval.as_secs() == 0 {
+        return;
+    }
+
+    rt::spawn(async move {
+        loop {
+            let pool = pool.clone();
+            let config_clone = config.clone();
+            let result = block(move || {
+                calibrate_tests(
+                    &pool,
+                    config_clone.model,
+                    config_clone.min_answers,
+                    Utc::now().timestamp(),
+                )
+            })
+            .await;
+            match result {
+                Ok(summary) => info!("{}", summary),
+                Err(err) => error!("{}:{} Calibrating the tests failed - {:?}", file!(), line!(), err),
+            }
+            delay_for(config.calibration_interval).await;
+        }
+    });
+}
+
+#[derive(Serialize, Debug)]
+pub struct AdaptivePick {
+    /// `None` once the ability is measured precisely enough or every test is answered
+    #[serde(skip)]
+    pub test_id: Option<i32>,
+    pub ability: f64,
+    pub standard_error: f64,
+    /// The probability the ability is enough to pass an exam of the served tests,
+    /// `None` when there are no tests
+    pub pass_probability: Option<f64>,
+    /// Tests the estimate is made from
+    pub answered: usize,
+    pub finished: bool,
+}
+
+/// Estimates the ability of the user from their last answer to every test and picks the unanswered test
+/// most informative at it, until the standard error drops to `target_se`
+pub fn adaptive_test(pool: &DbPool, user_id: i32, target_se: f64) -> anyhow::Result<AdaptivePick> {
+    let db = pool.get().unwrap();
+
+    let calibrations = test_calibrations::table
+        .load::<TestCalibration>(db.deref())
+        .map_err(|err| anyhow!("Failed to load the calibrations - {}", err))?
+        .into_iter()
+        .map(|calibration| {
+            let params = ItemParams {
+                difficulty: calibration.difficulty,
+                discrimination: calibration.discrimination,
+            };
+            (calibration.test_id, params)
+        })
+        .collect::<HashMap<_, _>>();
+    // The tests that aren't calibrated yet are taken for the average ones
+    let bank = tests::table
+        .filter(tests::retired.eq(false))
+        .select(tests::id)
+        .order(tests::id)
+        .load::<i32>(db.deref())
+        .map_err(|err| anyhow!("Failed to load tests - {}", err))?
+        .into_iter()
+        .map(|test_id| (test_id, calibrations.get(&test_id).cloned().unwrap_or_default()))
+        .collect::<BTreeMap<_, _>>();
+
+    let answers = user_answers::table
+        .inner_join(tests::table)
+        .filter(user_answers::user_id.eq(user_id).and(tests::retired.eq(false)))
+        .select((user_answers::test_id, user_answers::correct))
+        .order((user_answers::answered_at, user_answers::id))
+        .load::<(i32, bool)>(db.deref())
+        .map_err(|err| anyhow!("Failed to load answers of {} user - {}", user_id, err))?;
+    // The later answers of a test overwrite the earlier ones
+    let last_answers = answers.into_iter().collect::<HashMap<_, _>>();
+    let estimate = AbilityEstimate::new(last_answers.iter().map(|(test_id, correct)| (&bank[test_id], *correct)));
+    let (ability, standard_error) = (estimate.ability(), estimate.standard_error());
+    let pass_probability = irt::pass_ability(&bank.values().cloned().collect::<Vec<_>>())
+        .map(|pass_ability| estimate.probability_above(pass_ability));
+
+    let test_id = match standard_error <= target_se {
+        true => None,
+        false => bank
+            .iter()
+            .filter(|(test_id, _)| !last_answers.contains_key(test_id))
+            .max_by(|(_, a), (_, b)| a.information(ability).partial_cmp(&b.information(ability)).unwrap())
+            .map(|(test_id, _)| *test_id),
+    };
+
+    Ok(AdaptivePick {
+        test_id,
+        ability,
+        standard_error,
+        pass_probability,
+        answered: last_answers.len(),
+        finished: test_id.is_none(),
+    })
+}
+
+#[cfg(test)]
+mod _tests {
+    use super::*;
+    use crate::db::{get_tests, model, record_answer, schema, testing::*};
+
+    #[test]
+    fn calibration_orders_the_tests_by_difficulty_and_drives_the_adaptive_picks() {
+        use crate::irt::IrtModel;
+
+        let (pool, path) = temp_db();
+        let forms = vec![
+            test_form(Some("easy"), "Easy", 1),
+            test_form(Some("medium"), "Medium", 1),
+            test_form(Some("hard"), "Hard", 1),
+        ];
+        import(forms, &pool, false).unwrap();
+        let ids = get_tests(&pool).unwrap().iter().map(|test| test.id).collect::<Vec<_>>();
+        for learner in 0..25 {
+            let user_id = new_user(&pool, &format!("learner{}", learner)).id;
+            let right = [learner % 10 != 0, learner % 2 == 0, learner % 5 == 0];
+            for (test_id, correct) in ids.iter().zip(right.iter()) {
+                record_answer(&model::NewUserAnswer::new(user_id, *test_id, 1, *correct, 0), &pool).unwrap();
+            }
+            // Only the first answer of a user counts
+            record_answer(&model::NewUserAnswer::new(user_id, ids[2], 1, true, 0), &pool).unwrap();
+        }
+
+        let too_few_answers = calibrate_tests(&pool, IrtModel::OnePl, 26, 0).unwrap();
+        let summary = calibrate_tests(&pool, IrtModel::OnePl, 20, 0).unwrap();
+        let calibrations = schema::test_calibrations::table
+            .order(schema::test_calibrations::test_id)
+            .load::<model::TestCalibration>(pool.get().unwrap().deref())
+            .unwrap();
+        let user_id = new_user(&pool, "newcomer").id;
+        let first_pick = adaptive_test(&pool, user_id, 0.3).unwrap();
+        record_answer(&model::NewUserAnswer::new(user_id, ids[1], 1, true, 0), &pool).unwrap();
+        let after_right_answer = adaptive_test(&pool, user_id, 0.3).unwrap();
+        let precise_enough = adaptive_test(&pool, user_id, 1.0).unwrap();
+
+        remove_temp_db(pool, path);
+        assert_eq!(too_few_answers.tests, 0);
+        assert_eq!(
+            summary,
+            CalibrationSummary {
+                tests: 3,
+                answers: 75,
+                learners: 25,
+            }
+        );
+        assert!(calibrations[0].difficulty < calibrations[1].difficulty);
+        assert!(calibrations[1].difficulty < calibrations[2].difficulty);
+        assert!(calibrations.iter().all(|calibration| calibration.discrimination == 1.0));
+        assert_eq!(first_pick.test_id, Some(ids[1]));
+        assert_eq!(first_pick.answered, 0);
+        assert!(!first_pick.finished);
+        assert!(after_right_answer.ability > first_pick.ability);
+        assert!(after_right_answer.standard_error < first_pick.standard_error);
+        assert!(after_right_answer.pass_probability > first_pick.pass_probability);
+        assert_eq!(after_right_answer.test_id, Some(ids[2]));
+        assert!(precise_enough.finished);
+        assert_eq!(precise_enough.test_id, None);
+    }
+}
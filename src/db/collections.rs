@@ -0,0 +1,350 @@
+use diesel::{
+    delete, insert_into, insert_or_ignore_into, result::DatabaseErrorKind, BoolExpressionMethods, Connection,
+    ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl, SqliteConnection,
+};
+use serde::Serialize;
+use std::{collections::HashMap, ops::Deref};
+
+use super::{
+    schema::{bookmarks, collection_tests, collections, tests},
+    DbPool,
+};
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Bookmark {
+    pub test_id: i32,
+    pub description: String,
+    pub category: Option<String>,
+    /// Unix time in seconds
+    pub created_at: i64,
+    /// Ids of the collections of the user the test is in
+    pub collections: Vec<i32>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Collection {
+    pub id: i32,
+    pub name: String,
+    pub tests: usize,
+    /// Unix time in seconds
+    pub created_at: i64,
+}
+
+fn bookmark_test(db: &SqliteConnection, user_id: i32, test_id: i32, now: i64) -> diesel::QueryResult<bool> {
+    let exists = tests::table
+        .find(test_id)
+        .select(tests::id)
+        .first::<i32>(db)
+        .optional()?
+        .is_some();
+    if exists {
+        insert_or_ignore_into(bookmarks::table)
+            .values((
+                bookmarks::user_id.eq(user_id),
+                bookmarks::test_id.eq(test_id),
+                bookmarks::created_at.eq(now),
+            ))
+            .execute(db)?;
+    }
+
+    Ok(exists)
+}
+
+/// `false` when there is no such test, bookmarking a test twice changes nothing
+pub fn bookmark(pool: &DbPool, user_id: i32, test_id: i32, now: i64) -> anyhow::Result<bool> {
+    let db = pool.get().unwrap();
+
+    bookmark_test(db.deref(), user_id, test_id, now)
+        .map_err(|err| anyhow!("Failed to bookmark {} test for {} user - {}", test_id, user_id, err))
+}
+
+/// Removes the test from the collections of the user too, `false` when it isn't bookmarked
+pub fn remove_bookmark(pool: &DbPool, user_id: i32, test_id: i32) -> anyhow::Result<bool> {
+    let db = pool.get().unwrap();
+
+    delete(bookmarks::table.find((user_id, test_id)))
+        .execute(db.deref())
+        .map(|deleted| deleted > 0)
+        .map_err(|err| anyhow!("Failed to remove {} bookmark of {} user - {}", test_id, user_id, err))
+}
+
+/// The bookmarks of the user, the most recent first
+pub fn bookmarks(pool: &DbPool, user_id: i32) -> anyhow::Result<Vec<Bookmark>> {
+    let db = pool.get().unwrap();
+
+    let mut collections = HashMap::<i32, Vec<i32>>::new();
+    collection_tests::table
+        .filter(collection_tests::user_id.eq(user_id))
+        .select((collection_tests::test_id, collection_tests::collection_id))
+        .order(collection_tests::collection_id)
+        .load::<(i32, i32)>(db.deref())
+        .map_err(|err| anyhow!("Failed to load the collections of {} user - {}", user_id, err))?
+        .into_iter()
+        .for_each(|(test_id, collection_id)| collections.entry(test_id).or_default().push(collection_id));
+
+    bookmarks::table
+        .inner_join(tests::table)
+        .filter(bookmarks::user_id.eq(user_id))
+        .select((tests::id, tests::description, tests::category, bookmarks::created_at))
+        .order((bookmarks::created_at.desc(), tests::id))
+        .load::<(i32, String, Option<String>, i64)>(db.deref())
+        .map(|bookmarks| {
+            bookmarks
+                .into_iter()
+                .map(|(test_id, description, category, created_at)| Bookmark {
+                    test_id,
+                    description,
+                    category,
+                    created_at,
+                    collections: collections.remove(&test_id).unwrap_or_default(),
+                })
+                .collect()
+        })
+        .map_err(|err| anyhow!("Failed to load the bookmarks of {} user - {}", user_id, err))
+}
+
+/// `None` when the user already has a collection with the name
+pub fn create_collection(pool: &DbPool, user_id: i32, name: &str, now: i64) -> anyhow::Result<Option<Collection>> {
+    let db = pool.get().unwrap();
+
+    let inserted = insert_into(collections::table)
+        .values((
+            collections::user_id.eq(user_id),
+            collections::name.eq(name),
+            collections::created_at.eq(now),
+        ))
+        .execute(db.deref());
+    match inserted {
+        Ok(_) => {}
+        Err(diesel::result::Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => return Ok(None),
+        Err(err) => return Err(anyhow!("Failed to create a collection of {} user - {}", user_id, err)),
+    }
+
+    collections::table
+        .filter(collections::user_id.eq(user_id).and(collections::name.eq(name)))
+        .select((collections::id, collections::created_at))
+        .first::<(i32, i64)>(db.deref())
+        .map(|(id, created_at)| {
+            Some(Collection {
+                id,
+                name: name.to_owned(),
+                tests: 0,
+                created_at,
+            })
+        })
+        .map_err(|err| anyhow!("Failed to load the created collection of {} user - {}", user_id, err))
+}
+
+/// The collections of the user in the order of their names
+pub fn collections(pool: &DbPool, user_id: i32) -> anyhow::Result<Vec<Collection>> {
+    let db = pool.get().unwrap();
+
+    let mut sizes = HashMap::<i32, usize>::new();
+    collection_tests::table
+        .filter(collection_tests::user_id.eq(user_id))
+        .select(collection_tests::collection_id)
+        .load::<i32>(db.deref())
+        .map_err(|err| anyhow!("Failed to load the collections of {} user - {}", user_id, err))?
+        .into_iter()
+        .for_each(|collection_id| *sizes.entry(collection_id).or_default() += 1);
+
+    collections::table
+        .filter(collections::user_id.eq(user_id))
+        .select((collections::id, collections::name, collections::created_at))
+        .order((collections::name, collections::id))
+        .load::<(i32, String, i64)>(db.deref())
+        .map(|collections| {
+            collections
+                .into_iter()
+                .map(|(id, name, created_at)| Collection {
+                    id,
+                    name,
+                    tests: sizes.get(&id).cloned().unwrap_or_default(),
+                    created_at,
+                })
+                .collect()
+        })
+        .map_err(|err| anyhow!("Failed to load the collections of {} user - {}", user_id, err))
+}
+
+fn owns_collection(db: &SqliteConnection, user_id: i32, collection_id: i32) -> diesel::QueryResult<bool> {
+    collections::table
+        .filter(collections::id.eq(collection_id).and(collections::user_id.eq(user_id)))
+        .select(collections::id)
+        .first::<i32>(db)
+        .optional()
+        .map(|collection| collection.is_some())
+}
+
+/// The bookmarks of the collection are kept, `false` when the user has no such collection
+pub fn delete_collection(pool: &DbPool, user_id: i32, collection_id: i32) -> anyhow::Result<bool> {
+    let db = pool.get().unwrap();
+
+    delete(collections::table.filter(collections::id.eq(collection_id).and(collections::user_id.eq(user_id))))
+        .execute(db.deref())
+        .map(|deleted| deleted > 0)
+        .map_err(|err| {
+            anyhow!(
+                "Failed to delete {} collection of {} user - {}",
+                collection_id,
+                user_id,
+                err
+            )
+        })
+}
+
+/// Bookmarks the test too, `false` when the user has no such collection or there is no such test
+pub fn add_to_collection(
+    pool: &DbPool,
+    user_id: i32,
+    collection_id: i32,
+    test_id: i32,
+    now: i64,
+) -> anyhow::Result<bool> {
+    let db = pool.get().unwrap();
+
+    db.transaction(|| {
+        if !owns_collection(db.deref(), user_id, collection_id)? || !bookmark_test(db.deref(), user_id, test_id, now)? {
+            return Ok(false);
+        }
+        insert_or_ignore_into(collection_tests::table)
+            .values((
+                collection_tests::collection_id.eq(collection_id),
+                collection_tests::user_id.eq(user_id),
+                collection_tests::test_id.eq(test_id),
+            ))
+            .execute(db.deref())?;
+
+        Ok(true)
+    })
+    .map_err(|err: diesel::result::Error| {
+        anyhow!(
+            "Failed to add {} test to {} collection of {} user - {}",
+            test_id,
+            collection_id,
+            user_id,
+            err
+        )
+    })
+}
+
+/// The test stays bookmarked, `false` when it isn't in the collection of the user
+pub fn remove_from_collection(pool: &DbPool, user_id: i32, collection_id: i32, test_id: i32) -> anyhow::Result<bool> {
+    let db = pool.get().unwrap();
+
+    delete(
+        collection_tests::table.filter(
+            collection_tests::collection_id
+                .eq(collection_id)
+                .and(collection_tests::user_id.eq(user_id))
+                .and(collection_tests::test_id.eq(test_id)),
+        ),
+    )
+    .execute(db.deref())
+    .map(|deleted| deleted > 0)
+    .map_err(|err| {
+        anyhow!(
+            "Failed to remove {} test from {} collection of {} user - {}",
+            test_id,
+            collection_id,
+            user_id,
+            err
+        )
+    })
+}
+
+/// The served tests of the collection, `None` when the user has no such collection
+pub fn collection_tests(pool: &DbPool, user_id: i32, collection_id: i32) -> anyhow::Result<Option<Vec<i32>>> {
+    let db = pool.get().unwrap();
+
+    let load = || -> diesel::QueryResult<Option<Vec<i32>>> {
+        if !owns_collection(db.deref(), user_id, collection_id)? {
+            return Ok(None);
+        }
+        collection_tests::table
+            .inner_join(tests::table)
+            .filter(
+                collection_tests::collection_id
+                    .eq(collection_id)
+                    .and(tests::retired.eq(false)),
+            )
+            .select(tests::id)
+            .order(tests::id)
+            .load::<i32>(db.deref())
+            .map(Some)
+    };
+
+    load().map_err(|err| {
+        anyhow!(
+            "Failed to load {} collection of {} user - {}",
+            collection_id,
+            user_id,
+            err
+        )
+    })
+}
+
+#[cfg(test)]
+mod _tests {
+    use super::*;
+    use crate::db::{get_tests, testing::*};
+
+    #[test]
+    fn collections_hold_bookmarks_of_their_user_only() {
+        let (pool, path) = temp_db();
+        let forms = vec![test_form(Some("q1"), "First", 1), test_form(Some("q2"), "Second", 1)];
+        import(forms, &pool, false).unwrap();
+        let ids = get_tests(&pool).unwrap().iter().map(|test| test.id).collect::<Vec<_>>();
+        let mut users = Vec::new();
+        for name in &["ann", "bob"] {
+            users.push(new_user(&pool, name).id);
+        }
+        let (ann, bob) = (users[0], users[1]);
+
+        assert!(bookmark(&pool, ann, ids[0], 10).unwrap());
+        assert!(bookmark(&pool, ann, ids[0], 20).unwrap());
+        let unknown_test = bookmark(&pool, ann, ids[1] + 100, 10).unwrap();
+        let signs = create_collection(&pool, ann, "Signs", 30).unwrap().unwrap();
+        let taken_name = create_collection(&pool, ann, "Signs", 30).unwrap();
+        let bobs = create_collection(&pool, bob, "Signs", 30).unwrap().unwrap();
+        assert!(add_to_collection(&pool, ann, signs.id, ids[1], 40).unwrap());
+        assert!(add_to_collection(&pool, ann, signs.id, ids[0], 40).unwrap());
+        let foreign_collection = add_to_collection(&pool, bob, signs.id, ids[0], 40).unwrap();
+        let foreign_tests = collection_tests(&pool, bob, signs.id).unwrap();
+        let bookmarked = bookmarks(&pool, ann).unwrap();
+        let listed = collections(&pool, ann).unwrap();
+        assert!(remove_bookmark(&pool, ann, ids[1]).unwrap());
+        let after_removed_bookmark = collection_tests(&pool, ann, signs.id).unwrap();
+        assert!(remove_from_collection(&pool, ann, signs.id, ids[0]).unwrap());
+        let emptied = collection_tests(&pool, ann, signs.id).unwrap();
+        let bookmarks_after = bookmarks(&pool, ann).unwrap().len();
+        assert!(delete_collection(&pool, ann, signs.id).unwrap());
+        let deleted_twice = delete_collection(&pool, ann, signs.id).unwrap();
+
+        remove_temp_db(pool, path);
+        assert!(!unknown_test);
+        assert_eq!(taken_name, None);
+        assert_ne!(bobs.id, signs.id);
+        assert!(!foreign_collection);
+        assert_eq!(foreign_tests, None);
+        assert_eq!(
+            bookmarked.iter().map(|bookmark| bookmark.test_id).collect::<Vec<_>>(),
+            vec![ids[1], ids[0]]
+        );
+        assert_eq!(bookmarked[1].created_at, 10);
+        assert_eq!(bookmarked[1].collections, vec![signs.id]);
+        assert_eq!(
+            listed,
+            vec![Collection {
+                id: signs.id,
+                name: "Signs".to_owned(),
+                tests: 2,
+                created_at: 30,
+            }]
+        );
+        assert_eq!(after_removed_bookmark, Some(vec![ids[0]]));
+        assert_eq!(emptied, Some(vec![]));
+        assert_eq!(bookmarks_after, 1);
+        assert!(!deleted_twice);
+    }
+}
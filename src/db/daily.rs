@@ -0,0 +1,604 @@
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use diesel::{
+    insert_into, insert_or_ignore_into, BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension,
+    QueryDsl, RunQueryDsl, SqliteConnection,
+};
+use openssl::sha;
+use serde::Serialize;
+use std::ops::Deref;
+
+use super::{
+    achievements::{self, ExamSource},
+    model::{NewUserAnswer, User},
+    schema::{daily_answers, daily_attempts, daily_sets, tests, user_time_zones, users},
+    DbPool,
+};
+
+/// Questions of a daily challenge, fewer when the bank is smaller
+pub const DAILY_QUESTIONS: usize = 10;
+
+#[derive(Queryable, Serialize, Debug, PartialEq)]
+pub struct DailyAnswer {
+    pub test_id: i32,
+    pub answer_id: i32,
+    pub correct: bool,
+}
+
+/// Days in a row the user finished the daily challenge
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct Streak {
+    /// It's kept until the end of the day after the last finished challenge
+    pub current: usize,
+    pub longest: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DailyChallenge {
+    pub day: String,
+    /// The tests of the day in the order they are served
+    #[serde(skip)]
+    pub tests: Vec<i32>,
+    pub answers: Vec<DailyAnswer>,
+    pub correct: i32,
+    pub finished: bool,
+    pub streak: Streak,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DailyAnswerOutcome {
+    Recorded {
+        finished: bool,
+    },
+    /// The user has already answered the test that day
+    Answered,
+    /// The day overlaps another day the user has started in another time zone
+    Overlapping {
+        day: String,
+    },
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct DailyLeaderboardEntry {
+    pub rank: usize,
+    /// The display name of the user or their full name
+    pub name: String,
+    pub correct: i32,
+    pub answers: i32,
+    pub questions: i32,
+    /// Unix time in seconds, `None` until the user answers every question
+    pub finished_at: Option<i64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DailyLeaderboard {
+    pub day: String,
+    pub page: usize,
+    pub per_page: usize,
+    /// Users on the whole leaderboard, not only on the page
+    pub total: usize,
+    pub entries: Vec<DailyLeaderboardEntry>,
+}
+
+/// An unknown or missing time zone is taken as UTC
+fn parse_time_zone(time_zone: Option<&str>) -> Tz {
+    time_zone
+        .and_then(|time_zone| time_zone.parse::<Tz>().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+/// The calendar day of the user, an unknown or missing time zone is taken as UTC
+pub fn local_day(time_zone: Option<&str>, now: DateTime<Utc>) -> NaiveDate {
+    now.with_timezone(&parse_time_zone(time_zone)).date_naive()
+}
+
+/// Unix times in seconds the day starts and ends at in the time zone, the end is excluded
+fn day_bounds(time_zone: Option<&str>, day: NaiveDate) -> (i64, i64) {
+    let time_zone = parse_time_zone(time_zone);
+    let start_of = |day: NaiveDate| {
+        let midnight = day.and_hms_opt(0, 0, 0).unwrap_or_default();
+        // Some time zones skip the midnight when the clocks go forward, the day starts an hour later then
+        time_zone
+            .from_local_datetime(&midnight)
+            .earliest()
+            .or_else(|| {
+                time_zone
+                    .from_local_datetime(&(midnight + Duration::hours(1)))
+                    .earliest()
+            })
+            .map_or_else(|| midnight.and_utc().timestamp(), |start| start.timestamp())
+    };
+
+    (start_of(day), start_of(day + Duration::days(1)))
+}
+
+/// Orders the served tests by the hash of the day and their ids, so the set only depends on the date and the bank
+fn pick_daily_tests(db: &SqliteConnection, day: &str) -> diesel::QueryResult<Vec<i32>> {
+    let mut tests = tests::table
+        .filter(tests::retired.eq(false))
+        .select(tests::id)
+        .load::<i32>(db)?
+        .into_iter()
+        .map(|test_id| (sha::sha256(format!("{}:{}", day, test_id).as_bytes()), test_id))
+        .collect::<Vec<_>>();
+    tests.sort_unstable();
+
+    Ok(tests
+        .into_iter()
+        .take(DAILY_QUESTIONS)
+        .map(|(_, test_id)| test_id)
+        .collect())
+}
+
+/// The set of the day is pinned when it's first requested, so the tests imported later don't change it.
+/// The pinned tests retired since then aren't served
+fn daily_tests(db: &SqliteConnection, day: &str) -> diesel::QueryResult<Vec<i32>> {
+    db.transaction(|| {
+        let pinned = daily_sets::table
+            .filter(daily_sets::day.eq(day))
+            .count()
+            .get_result::<i64>(db)?;
+        if pinned == 0 {
+            for (position, test_id) in pick_daily_tests(db, day)?.into_iter().enumerate() {
+                insert_or_ignore_into(daily_sets::table)
+                    .values((
+                        daily_sets::day.eq(day),
+                        daily_sets::position.eq(position as i32),
+                        daily_sets::test_id.eq(test_id),
+                    ))
+                    .execute(db)?;
+            }
+        }
+
+        daily_sets::table
+            .inner_join(tests::table)
+            .filter(daily_sets::day.eq(day).and(tests::retired.eq(false)))
+            .select(daily_sets::test_id)
+            .order(daily_sets::position)
+            .load::<i32>(db)
+    })
+}
+
+/// The days the user finished the daily challenge, the earliest first
+pub(super) fn finished_days(db: &SqliteConnection, user_id: i32) -> diesel::QueryResult<Vec<NaiveDate>> {
+    daily_attempts::table
+        .filter(
+            daily_attempts::user_id
+                .eq(user_id)
+                .and(daily_attempts::finished_at.is_not_null()),
+        )
+        .select(daily_attempts::day)
+        .order(daily_attempts::day)
+        .load::<String>(db)
+        .map(|days| days.iter().filter_map(|day| day.parse::<NaiveDate>().ok()).collect())
+}
+
+/// `current` counts the run ending today or yesterday, `days` are sorted
+pub(super) fn streak(days: &[NaiveDate], today: NaiveDate) -> Streak {
+    let mut streak = Streak::default();
+    let mut run = 0;
+    for (index, day) in days.iter().enumerate() {
+        run = match index.checked_sub(1).map(|previous| days[previous]) {
+            Some(previous) if *day - previous == Duration::days(1) => run + 1,
+            _ => 1,
+        };
+        streak.longest = streak.longest.max(run);
+    }
+    if days
+        .last()
+        .is_some_and(|last| *last == today || Some(*last) == today.pred_opt())
+    {
+        streak.current = run;
+    }
+
+    streak
+}
+
+/// The challenge of the user on their current day, `tests` is empty when the bank is empty
+pub fn daily_challenge(pool: &DbPool, user: &User, now: DateTime<Utc>) -> anyhow::Result<DailyChallenge> {
+    let db = pool.get().unwrap();
+
+    let load = || -> diesel::QueryResult<DailyChallenge> {
+        let today = local_day(load_time_zone(db.deref(), user.id)?.as_deref(), now);
+        let day = today.to_string();
+        let tests = daily_tests(db.deref(), &day)?;
+        let answers = daily_answers::table
+            .filter(daily_answers::user_id.eq(user.id).and(daily_answers::day.eq(&day)))
+            .select((daily_answers::test_id, daily_answers::answer_id, daily_answers::correct))
+            .load::<DailyAnswer>(db.deref())?;
+        let finished_days = finished_days(db.deref(), user.id)?;
+
+        Ok(DailyChallenge {
+            correct: answers.iter().filter(|answer| answer.correct).count() as i32,
+            finished: finished_days.last() == Some(&today),
+            streak: streak(&finished_days, today),
+            day,
+            tests,
+            answers,
+        })
+    };
+
+    load().map_err(|err| anyhow!("Failed to load the daily challenge of {} user - {}", user.id, err))
+}
+
+/// Records the answer to the challenge of the day like `record_answer` does to the score history,
+/// the finished challenge is recorded as an exam. `questions` is the size of the set now, it shrinks when tests retire.
+/// A new day can't overlap another day of the user, so changing the time zone doesn't give extra days to the streak
+pub fn record_daily_answer(
+    pool: &DbPool,
+    day: NaiveDate,
+    time_zone: Option<&str>,
+    questions: usize,
+    answer: &NewUserAnswer,
+) -> anyhow::Result<DailyAnswerOutcome> {
+    let db = pool.get().unwrap();
+    let (starts_at, ends_at) = day_bounds(time_zone, day);
+    let day = &day.to_string();
+    let attempt = daily_attempts::table.find((answer.user_id, day));
+
+    db.transaction(|| {
+        let started = attempt
+            .select(daily_attempts::day)
+            .first::<String>(db.deref())
+            .optional()?
+            .is_some();
+        if !started {
+            let overlapping = daily_attempts::table
+                .filter(
+                    daily_attempts::user_id
+                        .eq(answer.user_id)
+                        .and(daily_attempts::starts_at.lt(ends_at))
+                        .and(daily_attempts::ends_at.gt(starts_at)),
+                )
+                .select(daily_attempts::day)
+                .first::<String>(db.deref())
+                .optional()?;
+            if let Some(day) = overlapping {
+                return Ok(DailyAnswerOutcome::Overlapping { day });
+            }
+
+            insert_or_ignore_into(daily_attempts::table)
+                .values((
+                    daily_attempts::user_id.eq(answer.user_id),
+                    daily_attempts::day.eq(day),
+                    daily_attempts::questions.eq(questions as i32),
+                    daily_attempts::starts_at.eq(starts_at),
+                    daily_attempts::ends_at.eq(ends_at),
+                ))
+                .execute(db.deref())?;
+        }
+        let answered = daily_answers::table
+            .find((answer.user_id, day, answer.test_id))
+            .select(daily_answers::test_id)
+            .first::<i32>(db.deref())
+            .optional()?
+            .is_some();
+        if answered {
+            return Ok(DailyAnswerOutcome::Answered);
+        }
+
+        insert_into(daily_answers::table)
+            .values((
+                daily_answers::user_id.eq(answer.user_id),
+                daily_answers::day.eq(day),
+                daily_answers::test_id.eq(answer.test_id),
+                daily_answers::answer_id.eq(answer.answer_id),
+                daily_answers::correct.eq(answer.correct),
+            ))
+            .execute(db.deref())?;
+        diesel::update(attempt)
+            .set((
+                daily_attempts::questions.eq(questions as i32),
+                daily_attempts::answers.eq(daily_attempts::answers + 1),
+                daily_attempts::correct.eq(daily_attempts::correct + answer.correct as i32),
+            ))
+            .execute(db.deref())?;
+        let finished = diesel::update(
+            attempt.filter(
+                daily_attempts::answers
+                    .ge(daily_attempts::questions)
+                    .and(daily_attempts::finished_at.is_null()),
+            ),
+        )
+        .set(daily_attempts::finished_at.eq(answer.answered_at))
+        .execute(db.deref())?
+            > 0;
+        super::save_answer(db.deref(), answer)?;
+        if finished {
+            let (questions, correct) = attempt
+                .select((daily_attempts::questions, daily_attempts::correct))
+                .first::<(i32, i32)>(db.deref())?;
+            achievements::save_exam(
+                db.deref(),
+                answer.user_id,
+                ExamSource::Daily,
+                questions,
+                correct,
+                answer.answered_at,
+            )?;
+        }
+
+        Ok(DailyAnswerOutcome::Recorded { finished })
+    })
+    .map_err(|err: diesel::result::Error| {
+        anyhow!(
+            "Failed to record a daily answer of {} user on {} - {}",
+            answer.user_id,
+            day,
+            err
+        )
+    })
+}
+
+/// The served tests of the challenge of the day
+pub fn daily_set(pool: &DbPool, day: &str) -> anyhow::Result<Vec<i32>> {
+    let db = pool.get().unwrap();
+
+    daily_tests(db.deref(), day).map_err(|err| anyhow!("Failed to load the daily challenge of {} - {}", day, err))
+}
+
+/// Ranks the attempts of the day by the right answers and then by who finished first,
+/// users with the same result share the rank and hidden users aren't ranked
+pub fn daily_leaderboard(pool: &DbPool, day: &str, page: usize, per_page: usize) -> anyhow::Result<DailyLeaderboard> {
+    let db = pool.get().unwrap();
+
+    let mut rows = daily_attempts::table
+        .inner_join(users::table)
+        .filter(daily_attempts::day.eq(day).and(users::leaderboard_hidden.eq(false)))
+        .select((
+            users::name,
+            users::second_name,
+            users::display_name,
+            daily_attempts::correct,
+            daily_attempts::answers,
+            daily_attempts::questions,
+            daily_attempts::finished_at,
+        ))
+        .order(users::id)
+        .load::<(String, String, Option<String>, i32, i32, i32, Option<i64>)>(db.deref())
+        .map_err(|err| anyhow!("Failed to load the daily leaderboard of {} - {}", day, err))?
+        .into_iter()
+        .map(
+            |(name, second_name, display_name, correct, answers, questions, finished_at)| DailyLeaderboardEntry {
+                rank: 0,
+                name: display_name.unwrap_or_else(|| format!("{} {}", name, second_name)),
+                correct,
+                answers,
+                questions,
+                finished_at,
+            },
+        )
+        .collect::<Vec<_>>();
+
+    // The unfinished attempts go after the finished ones with as many right answers
+    let key = |entry: &DailyLeaderboardEntry| (-entry.correct, entry.finished_at.unwrap_or(i64::MAX));
+    rows.sort_by_key(key);
+    let keys = rows.iter().map(key).collect::<Vec<_>>();
+    let total = rows.len();
+    // The keys are sorted, so the better results are a prefix of them
+    let entries = rows
+        .into_iter()
+        .skip(page.saturating_sub(1).saturating_mul(per_page))
+        .take(per_page)
+        .map(|entry| DailyLeaderboardEntry {
+            rank: 1 + keys.partition_point(|other| *other < key(&entry)),
+            ..entry
+        })
+        .collect();
+
+    Ok(DailyLeaderboard {
+        day: day.to_owned(),
+        page,
+        per_page,
+        total,
+        entries,
+    })
+}
+
+fn load_time_zone(db: &SqliteConnection, user_id: i32) -> diesel::QueryResult<Option<String>> {
+    user_time_zones::table
+        .find(user_id)
+        .select(user_time_zones::time_zone)
+        .first::<String>(db)
+        .optional()
+}
+
+/// IANA time zone the daily challenges of the user follow, `None` for UTC
+pub fn time_zone(pool: &DbPool, user_id: i32) -> anyhow::Result<Option<String>> {
+    let db = pool.get().unwrap();
+
+    load_time_zone(db.deref(), user_id)
+        .map_err(|err| anyhow!("Failed to load the time zone of {} user - {}", user_id, err))
+}
+
+/// `None` brings the user back to UTC
+pub fn set_time_zone(pool: &DbPool, user_id: i32, time_zone: Option<&str>) -> anyhow::Result<()> {
+    let db = pool.get().unwrap();
+    let row = user_time_zones::table.find(user_id);
+
+    match time_zone {
+        Some(time_zone) => diesel::replace_into(user_time_zones::table)
+            .values((
+                user_time_zones::user_id.eq(user_id),
+                user_time_zones::time_zone.eq(time_zone),
+            ))
+            .execute(db.deref()),
+        None => diesel::delete(row).execute(db.deref()),
+    }
+    .map(|_| ())
+    .map_err(|err| anyhow!("Failed to change the time zone of {} user - {}", user_id, err))
+}
+
+#[cfg(test)]
+mod _tests {
+    use super::*;
+    use crate::db::{get_user, model, testing::*, unseen_achievements};
+
+    #[test]
+    fn streaks_of_finished_days() {
+        let day = |day: u32| NaiveDate::from_ymd_opt(2026, 3, day).unwrap();
+        let days = [day(1), day(2), day(3), day(5), day(6)];
+
+        assert_eq!(streak(&days, day(6)), Streak { current: 2, longest: 3 });
+        assert_eq!(streak(&days, day(7)), Streak { current: 2, longest: 3 });
+        assert_eq!(streak(&days, day(8)), Streak { current: 0, longest: 3 });
+        assert_eq!(streak(&[], day(8)), Streak::default());
+    }
+
+    #[test]
+    fn local_days_follow_the_time_zone() {
+        let now = DateTime::from_timestamp(1_773_619_200, 0).unwrap(); // 2026-03-16T00:00:00Z
+
+        assert_eq!(local_day(None, now).to_string(), "2026-03-16");
+        assert_eq!(local_day(Some("America/New_York"), now).to_string(), "2026-03-15");
+        assert_eq!(local_day(Some("Asia/Tokyo"), now).to_string(), "2026-03-16");
+        assert_eq!(local_day(Some("Nowhere/Special"), now).to_string(), "2026-03-16");
+    }
+
+    #[test]
+    fn day_bounds_follow_the_time_zone() {
+        let day = NaiveDate::from_ymd_opt(2026, 3, 16).unwrap();
+        // The clocks of Havana go forward at the midnight of 2026-03-08
+        let skipped_midnight = NaiveDate::from_ymd_opt(2026, 3, 8).unwrap();
+
+        assert_eq!(day_bounds(None, day), (1_773_619_200, 1_773_705_600));
+        assert_eq!(day_bounds(Some("Asia/Tokyo"), day), (1_773_586_800, 1_773_673_200));
+        assert_eq!(
+            day_bounds(Some("America/Havana"), skipped_midnight),
+            (1_772_946_000, 1_773_028_800)
+        );
+    }
+
+    #[test]
+    fn daily_challenges_are_shared_and_answered_once() {
+        use chrono::{TimeZone, Utc};
+
+        let (pool, path) = temp_db();
+        let forms = (0..DAILY_QUESTIONS + 2)
+            .map(|index| test_form(Some(&format!("q{}", index)), &format!("Question {}", index), 1))
+            .collect();
+        import(forms, &pool, false).unwrap();
+        let mut users = Vec::new();
+        for name in &["ann", "bob"] {
+            users.push(new_user(&pool, name));
+        }
+        let now = Utc.with_ymd_and_hms(2026, 3, 16, 20, 0, 0).unwrap();
+        let answer = |user_id: i32, test_id: i32, answer_id: i32| model::NewUserAnswer {
+            answered_at: now.timestamp(),
+            ..model::NewUserAnswer::new(user_id, test_id, answer_id, answer_id == 1, (answer_id == 1) as i32 * 5)
+        };
+
+        let (today, tomorrow) = (now.date_naive(), now.date_naive() + Duration::days(1));
+        let record = |user: &User, test_id: i32, answer_id: i32, questions: usize| {
+            let answer = answer(user.id, test_id, answer_id);
+            let time_zone = time_zone(&pool, user.id).unwrap();
+            record_daily_answer(&pool, today, time_zone.as_deref(), questions, &answer).unwrap()
+        };
+
+        let set = daily_set(&pool, "2026-03-16").unwrap();
+        let same_set = daily_set(&pool, "2026-03-16").unwrap();
+        let next_set = daily_set(&pool, "2026-03-17").unwrap();
+        let (ann, bob) = (&users[0], &users[1]);
+        let mut finished = Vec::new();
+        for test_id in &set {
+            finished.push(record(ann, *test_id, 1, set.len()));
+        }
+        let answered_twice = record(ann, set[0], 1, set.len());
+        for test_id in &set[..3] {
+            record(bob, *test_id, 2, set.len());
+        }
+        let challenge = daily_challenge(&pool, ann, now).unwrap();
+        let leaderboard = daily_leaderboard(&pool, "2026-03-16", 1, 10).unwrap();
+        let far_page = daily_leaderboard(&pool, "2026-03-16", usize::MAX, 100).unwrap();
+        let ann_scores = get_user(&users_form("ann"), &pool).unwrap().scores;
+        set_time_zone(&pool, bob.id, Some("Asia/Tokyo")).unwrap();
+        let bobs_time_zone = time_zone(&pool, bob.id).unwrap();
+        let bobs_challenge = daily_challenge(&pool, bob, now).unwrap();
+        // The day of Tokyo has started before the day of UTC that bob has started ends
+        let bobs_overlapping_day = record_daily_answer(
+            &pool,
+            tomorrow,
+            bobs_time_zone.as_deref(),
+            set.len(),
+            &answer(bob.id, next_set[0], 1),
+        )
+        .unwrap();
+        let anns_next_day =
+            record_daily_answer(&pool, tomorrow, None, set.len(), &answer(ann.id, next_set[0], 1)).unwrap();
+        let ann_badges = unseen_achievements(&pool, users[0].id).unwrap();
+
+        // The pinned set doesn't take the new tests and loses the retired ones
+        let new_forms = (0..DAILY_QUESTIONS)
+            .map(|index| test_form(Some(&format!("new{}", index)), &format!("New question {}", index), 1))
+            .collect();
+        import(new_forms, &pool, false).unwrap();
+        diesel::update(tests::table.find(set[0]))
+            .set(tests::retired.eq(true))
+            .execute(pool.get().unwrap().deref())
+            .unwrap();
+        let set_after_import = daily_set(&pool, "2026-03-16").unwrap();
+        record(bob, set[3], 2, set_after_import.len());
+        let bobs_questions = daily_leaderboard(&pool, "2026-03-16", 1, 10).unwrap().entries[1].questions;
+
+        remove_temp_db(pool, path);
+        assert_eq!(set.len(), DAILY_QUESTIONS);
+        assert_eq!(set, same_set);
+        assert_ne!(set, next_set);
+        assert_eq!(
+            finished
+                .iter()
+                .filter(|finished| **finished == DailyAnswerOutcome::Recorded { finished: true })
+                .count(),
+            1
+        );
+        assert_eq!(finished.last(), Some(&DailyAnswerOutcome::Recorded { finished: true }));
+        assert_eq!(answered_twice, DailyAnswerOutcome::Answered);
+        assert_eq!(challenge.day, "2026-03-16");
+        assert_eq!(challenge.tests, set);
+        assert_eq!(challenge.correct, DAILY_QUESTIONS as i32);
+        assert!(challenge.finished);
+        assert_eq!(challenge.streak, Streak { current: 1, longest: 1 });
+        assert_eq!(ann_scores, DAILY_QUESTIONS as i32 * 5);
+        assert_eq!(leaderboard.total, 2);
+        assert!(far_page.entries.is_empty());
+        assert_eq!(
+            leaderboard.entries,
+            vec![
+                DailyLeaderboardEntry {
+                    rank: 1,
+                    name: "ann Lee".to_owned(),
+                    correct: DAILY_QUESTIONS as i32,
+                    answers: DAILY_QUESTIONS as i32,
+                    questions: DAILY_QUESTIONS as i32,
+                    finished_at: Some(now.timestamp()),
+                },
+                DailyLeaderboardEntry {
+                    rank: 2,
+                    name: "bob Lee".to_owned(),
+                    correct: 0,
+                    answers: 3,
+                    questions: DAILY_QUESTIONS as i32,
+                    finished_at: None,
+                },
+            ]
+        );
+        assert_eq!(bobs_time_zone.as_deref(), Some("Asia/Tokyo"));
+        assert_eq!(bobs_challenge.day, "2026-03-17");
+        assert_eq!(bobs_challenge.tests, next_set);
+        assert!(bobs_challenge.answers.is_empty());
+        assert_eq!(
+            bobs_overlapping_day,
+            DailyAnswerOutcome::Overlapping {
+                day: "2026-03-16".to_owned()
+            }
+        );
+        assert_eq!(anns_next_day, DailyAnswerOutcome::Recorded { finished: false });
+        assert_eq!(set_after_import, set[1..].to_vec());
+        assert_eq!(bobs_questions, DAILY_QUESTIONS as i32 - 1);
+        // The finished challenge is a perfect exam
+        assert_eq!(
+            ann_badges.iter().map(|earned| earned.id).collect::<Vec<_>>(),
+            vec!["first_perfect_exam"]
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use log::info;
+use std::{collections::BTreeMap, fmt, ops::Deref, path::Path};
+
+use super::{
+    get_tests, images,
+    model::{Image, Test, TestForm},
+    schema, DbPool, MAX_BOUND_IDS,
+};
+use crate::{
+    bundle::{BundleWriter, BUNDLE_TESTS_FILE},
+    formats::{self, ExportFormat},
+};
+
+#[derive(Default, Debug, PartialEq)]
+pub struct ExportSummary {
+    pub tests: usize,
+    pub images: usize,
+}
+
+impl fmt::Display for ExportSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} tests and {} images exported", self.tests, self.images)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Only the tests of these categories are exported, all of them when it's empty
+    pub categories: Vec<String>,
+    /// Only these tests are exported, all of them when it's empty
+    pub test_ids: Vec<i32>,
+}
+
+/// The served tests with their images, what every export format is written from
+pub struct ExportBank {
+    pub tests: Vec<Test>,
+    /// Only the images the tests show, keyed by their hash
+    pub images: BTreeMap<String, Image>,
+}
+
+impl ExportBank {
+    /// Loads the tests chosen by the category and id filters of the options
+    pub fn load(pool: &DbPool, options: &ExportOptions) -> anyhow::Result<Self> {
+        let mut tests = get_tests(pool)?;
+        if !options.categories.is_empty() {
+            tests.retain(|test| {
+                test.category
+                    .as_ref()
+                    .is_some_and(|category| options.categories.contains(category))
+            });
+        }
+        if !options.test_ids.is_empty() {
+            tests.retain(|test| options.test_ids.contains(&test.id));
+        }
+
+        let hashes = tests
+            .iter()
+            .filter_map(|test| test.image_hash.clone())
+            .collect::<Vec<_>>();
+
+        let db = pool.get().unwrap();
+        let mut images = BTreeMap::new();
+        for hashes in hashes.chunks(MAX_BOUND_IDS) {
+            let chunk = schema::images::table
+                .filter(schema::images::hash.eq_any(hashes))
+                .load::<Image>(db.deref())
+                .map_err(|err| anyhow!("Failed to load images - {}", err))?;
+            images.extend(chunk.into_iter().map(|image| (image.hash.clone(), image)));
+        }
+
+        Ok(ExportBank { tests, images })
+    }
+
+    pub fn image(&self, test: &Test) -> anyhow::Result<Option<&Image>> {
+        match test.image_hash.as_ref() {
+            Some(hash) => self
+                .images
+                .get(hash)
+                .map(Some)
+                .ok_or_else(|| anyhow!("{} image of {} test isn't stored", hash, test.id)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn answers(test: &Test) -> anyhow::Result<Vec<String>> {
+        Ok(serde_json::from_str(&test.answers)?)
+    }
+
+    /// Images are named by their hash, so tests sharing an image share the file and repeated exports don't rename them
+    pub fn image_file_name(image: &Image) -> String {
+        format!("images/{}.{}", image.hash, images::extension_of(&image.content_type))
+    }
+
+    /// Writes every image to the `images/` directory of the bundle
+    pub fn write_images(&self, bundle: &mut BundleWriter) -> anyhow::Result<usize> {
+        for image in self.images.values() {
+            bundle.add_file(&Self::image_file_name(image), &image.data)?;
+        }
+
+        Ok(self.images.len())
+    }
+}
+
+pub fn export_tests(pool: &DbPool, path: &Path, options: &ExportOptions) -> anyhow::Result<ExportSummary> {
+    let bank = ExportBank::load(pool, options)?;
+
+    let summary = match options.format {
+        ExportFormat::Bundle => write_bundle(&bank, path)?,
+        format => formats::export(&bank, path, format)?,
+    };
+
+    info!("{} to {}", summary, path.display());
+
+    Ok(summary)
+}
+
+/// Writes the tests in the import format with their images to a directory or a `.zip` archive
+fn write_bundle(bank: &ExportBank, path: &Path) -> anyhow::Result<ExportSummary> {
+    let mut bundle = BundleWriter::create(path)?;
+    let mut test_forms = Vec::with_capacity(bank.tests.len());
+
+    for test in bank.tests.iter() {
+        test_forms.push(TestForm {
+            key: test.key.clone(),
+            description: test.description.clone(),
+            answers: ExportBank::answers(test)?,
+            right_answer_id: test.right_answer_id,
+            image_path: bank.image(test)?.map(ExportBank::image_file_name),
+            image_data: None,
+            category: test.category.clone(),
+            explanation: test.explanation.clone(),
+        });
+    }
+
+    let images = bank.write_images(&mut bundle)?;
+    bundle.add_file(BUNDLE_TESTS_FILE, serde_json::to_string_pretty(&test_forms)?.as_bytes())?;
+    bundle.finish()?;
+
+    Ok(ExportSummary {
+        tests: test_forms.len(),
+        images,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::QuestionCache,
+        db::{insert_tests_to_db, testing::*, ImportOptions},
+        image_pipeline::{ImagePipeline, ImagePipelineConfig},
+    };
+
+    #[test]
+    fn export_tests_round_trips_through_a_zip_bundle() {
+        let (pool, path) = temp_db();
+        let (other_pool, other_path) = temp_db();
+        let dir = crate::bundle::TempDir::new("drive-tests-export-test").unwrap();
+        let pipeline = ImagePipeline::new(ImagePipelineConfig::default());
+        import_bank_with_image(&pool, dir.path());
+
+        let bundle = dir.path().join("bank.zip");
+        let exported = export_tests(&pool, &bundle, &format_options(ExportFormat::Bundle)).unwrap();
+        let options = ImportOptions::default();
+        let cache = QuestionCache::new();
+        let reimported = insert_tests_to_db(&bundle, &pool, &cache, &pipeline, options).unwrap();
+        let elsewhere = insert_tests_to_db(&bundle, &other_pool, &cache, &pipeline, options).unwrap();
+        let other_tests = get_tests(&other_pool).unwrap();
+
+        remove_temp_db(pool, path);
+        remove_temp_db(other_pool, other_path);
+        assert_eq!(exported, ExportSummary { tests: 2, images: 1 });
+        assert_eq!(reimported.summary.unchanged, 2);
+        assert_eq!(elsewhere.summary.created, 2);
+        assert_eq!(other_tests[0].key.as_deref(), Some("q1"));
+        assert!(other_tests[0].image_hash.is_some());
+    }
+
+    #[test]
+    fn export_bank_loads_more_images_than_sqlite_binds_at_once() {
+        use crate::db::schema::tests;
+        use diesel::insert_into;
+
+        let (pool, path) = temp_db();
+        let db = pool.get().unwrap();
+        for index in 0..1200 {
+            let hash = images::store_image(&db, format!("image {}", index).into_bytes()).unwrap();
+            insert_into(tests::table)
+                .values((
+                    tests::description.eq(format!("Test {}", index)),
+                    tests::answers.eq(r#"["Yes","No"]"#),
+                    tests::right_answer_id.eq(1),
+                    tests::image_hash.eq(hash),
+                ))
+                .execute(db.deref())
+                .unwrap();
+        }
+        drop(db);
+
+        let bank = ExportBank::load(&pool, &ExportOptions::default()).unwrap();
+
+        remove_temp_db(pool, path);
+        assert_eq!(bank.tests.len(), 1200);
+        assert_eq!(bank.images.len(), 1200);
+    }
+}
@@ -0,0 +1,183 @@
+use diesel::{
+    connection::SimpleConnection,
+    insert_or_ignore_into,
+    sql_types::{Binary, Integer, Nullable},
+    sqlite::SqliteConnection,
+    BoolExpressionMethods, Connection, ExpressionMethods, QueryDsl, RunQueryDsl,
+};
+use image::ImageFormat;
+use log::info;
+use openssl::sha;
+
+use super::{model::Image, schema};
+
+pub fn content_type_of(data: &[u8]) -> &'static str {
+    match image::guess_format(data) {
+        Ok(ImageFormat::Png) => "image/png",
+        Ok(ImageFormat::Jpeg) => "image/jpeg",
+        Ok(ImageFormat::Gif) => "image/gif",
+        Ok(ImageFormat::WebP) => "image/webp",
+        Ok(ImageFormat::Bmp) => "image/bmp",
+        Ok(ImageFormat::Ico) => "image/x-icon",
+        Ok(ImageFormat::Tiff) => "image/tiff",
+        _ => "application/octet-stream",
+    }
+}
+
+pub fn extension_of(content_type: &str) -> &'static str {
+    match content_type {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/bmp" => "bmp",
+        "image/x-icon" => "ico",
+        "image/tiff" => "tiff",
+        _ => "bin",
+    }
+}
+
+/// Images are keyed by the hex encoded SHA-256 of their data
+pub fn hash_of(data: &[u8]) -> String {
+    hex::encode(sha::sha256(data))
+}
+
+/// Stores the image unless the same one is already stored and returns its hash
+pub fn store_image(db: &SqliteConnection, image_data: Vec<u8>) -> anyhow::Result<String> {
+    use schema::images::dsl::*;
+
+    let image_hash = hash_of(&image_data);
+
+    insert_or_ignore_into(images)
+        .values(&Image {
+            hash: image_hash.clone(),
+            content_type: content_type_of(&image_data).to_owned(),
+            data: image_data,
+        })
+        .execute(db)
+        .map_err(|err| anyhow!("Failed to store {} image - {}", image_hash, err))?;
+
+    Ok(image_hash)
+}
+
+pub fn load_images(db: &SqliteConnection) -> anyhow::Result<Vec<Image>> {
+    use schema::images::dsl::*;
+
+    images
+        .load::<Image>(db)
+        .map_err(|err| anyhow!("Failed to load images - {}", err))
+}
+
+/// Removes the images no test refers to and returns how many were removed
+pub fn collect_garbage(db: &SqliteConnection) -> anyhow::Result<usize> {
+    let removed = diesel::sql_query(
+        "DELETE FROM images WHERE hash NOT IN (
+            SELECT image_hash FROM tests WHERE image_hash IS NOT NULL
+            UNION SELECT thumbnail_hash FROM tests WHERE thumbnail_hash IS NOT NULL
+            UNION SELECT retina_hash FROM tests WHERE retina_hash IS NOT NULL
+        )",
+    )
+    .execute(db)
+    .map_err(|err| anyhow!("Failed to remove unreferenced images - {}", err))?;
+
+    if removed != 0 {
+        info!("{} unreferenced images were removed", removed);
+    }
+
+    Ok(removed)
+}
+
+#[derive(QueryableByName)]
+struct LegacyTestImage {
+    #[sql_type = "Integer"]
+    test_id: i32,
+    #[sql_type = "Nullable<Binary>"]
+    image: Option<Vec<u8>>,
+    #[sql_type = "Nullable<Binary>"]
+    image_thumbnail: Option<Vec<u8>>,
+    #[sql_type = "Nullable<Binary>"]
+    image_retina: Option<Vec<u8>>,
+}
+
+/// Moves the images left by the `image_store` migration in `legacy_test_images` to the `images` table.
+/// Equal images of different tests are stored only once
+pub fn adopt_legacy_images(db: &SqliteConnection) -> anyhow::Result<()> {
+    use schema::tests::dsl::*;
+
+    let legacy_table_exists = diesel::select(diesel::dsl::sql::<diesel::sql_types::Bool>(
+        "EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'legacy_test_images')",
+    ))
+    .get_result::<bool>(db)?;
+    if !legacy_table_exists {
+        return Ok(());
+    }
+
+    let legacy_images =
+        diesel::sql_query("SELECT test_id, image, image_thumbnail, image_retina FROM legacy_test_images")
+            .load::<LegacyTestImage>(db)?;
+    let count = legacy_images.len();
+
+    db.transaction::<_, anyhow::Error, _>(|| {
+        for legacy in legacy_images {
+            let store = |data: Option<Vec<u8>>| data.map(|data| store_image(db, data)).transpose();
+
+            diesel::update(tests.filter(id.eq(legacy.test_id).and(image_hash.is_null())))
+                .set((
+                    image_hash.eq(store(legacy.image)?),
+                    thumbnail_hash.eq(store(legacy.image_thumbnail)?),
+                    retina_hash.eq(store(legacy.image_retina)?),
+                ))
+                .execute(db)?;
+        }
+
+        db.batch_execute("DROP TABLE legacy_test_images")?;
+        collect_garbage(db)?;
+
+        Ok(())
+    })?;
+
+    // Gives the space of the moved BLOBs back to the file system
+    db.batch_execute("VACUUM")?;
+
+    info!("{} inline test images were moved to the images store", count);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::testing::*;
+    use std::ops::Deref;
+    use uuid::Uuid;
+
+    #[test]
+    fn store_image_stores_equal_images_once() {
+        use schema::images::dsl::{hash, images as images_table};
+
+        let (pool, path) = temp_db();
+        let db = pool.get().unwrap();
+        let image_data = Uuid::new_v4().as_bytes().to_vec();
+
+        let first_hash = store_image(&db, image_data.clone()).unwrap();
+        let second_hash = store_image(&db, image_data).unwrap();
+        let stored: i64 = images_table
+            .filter(hash.eq(&first_hash))
+            .count()
+            .get_result(db.deref())
+            .unwrap();
+
+        collect_garbage(&db).unwrap();
+        let stored_after_gc: i64 = images_table
+            .filter(hash.eq(&first_hash))
+            .count()
+            .get_result(db.deref())
+            .unwrap();
+
+        drop(db);
+        remove_temp_db(pool, path);
+        assert_eq!(first_hash, second_hash);
+        assert_eq!(stored, 1);
+        assert_eq!(stored_after_gc, 0);
+    }
+}
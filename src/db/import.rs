@@ -0,0 +1,491 @@
+use diesel::{insert_into, sqlite::SqliteConnection, Connection, ExpressionMethods, QueryDsl, RunQueryDsl};
+use log::{debug, error, info, warn};
+use std::{collections::HashSet, fmt, ops::Deref, path::Path};
+
+use super::{
+    images,
+    model::{NewTest, Test, TestForm},
+    schema::tests,
+    validation::{validate_tests, Severity, SheetSource, ValidationReport},
+    DbPool, MAX_BOUND_IDS,
+};
+use crate::{
+    bundle::{extract_zip, is_zip, TempDir},
+    cache::QuestionCache,
+    formats,
+    image_pipeline::{ImagePipeline, PipelineReport},
+};
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct ImportOptions {
+    /// Retire the tests missing in the import, retired tests aren't served anymore
+    pub retire_missing: bool,
+    /// Validate the tests and count the changes without writing anything
+    pub dry_run: bool,
+    /// Import nothing if any test is invalid, otherwise only the invalid tests are skipped
+    pub strict: bool,
+}
+
+#[derive(Default, Debug, PartialEq)]
+pub struct ImportSummary {
+    pub created: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub retired: usize,
+}
+
+#[derive(Debug)]
+pub struct ImportReport {
+    pub summary: ImportSummary,
+    pub validation: ValidationReport,
+}
+
+/// Returned from the import transaction to roll a dry run back
+#[derive(Debug)]
+struct DryRun(ImportSummary);
+
+impl fmt::Display for DryRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the dry run import is rolled back")
+    }
+}
+
+impl std::error::Error for DryRun {}
+
+impl fmt::Display for ImportSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} tests created, {} updated, {} unchanged, {} retired",
+            self.created, self.updated, self.unchanged, self.retired
+        )
+    }
+}
+
+pub fn insert_tests_to_db(
+    path: &Path,
+    pool: &DbPool,
+    cache: &QuestionCache,
+    pipeline: &ImagePipeline,
+    options: ImportOptions,
+) -> anyhow::Result<ImportReport> {
+    debug!("There are new tests to be inserted");
+
+    let extracted = match is_zip(path) {
+        true => {
+            let dir = TempDir::new("drive-tests-import")?;
+            extract_zip(path, dir.path())?;
+            Some(dir)
+        }
+        false => None,
+    };
+    let path = match extracted.as_ref() {
+        Some(dir) => dir.path().to_owned(),
+        None => path.to_owned(),
+    };
+    // Bundles and packages are imported from the file describing them
+    let path = match path.is_dir() {
+        true => formats::package_entry(&path),
+        false => path,
+    };
+
+    let (test_forms, source) = formats::read_test_forms(&path)?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    import_located_tests(test_forms, source, base_dir, pool, cache, pipeline, options)
+}
+
+/// Inserts the new tests and updates the changed ones in a single transaction, so a failed import changes nothing.
+/// Tests are matched by their `key`, the tests without a key are matched by the description. A keyed test matches
+/// a keyless one with the same description when no test has its key yet, so keys can be added to an imported bank.
+/// Relative image paths are resolved against `base_dir`
+pub fn import_tests(
+    test_forms: Vec<TestForm>,
+    base_dir: &Path,
+    pool: &DbPool,
+    cache: &QuestionCache,
+    pipeline: &ImagePipeline,
+    options: ImportOptions,
+) -> anyhow::Result<ImportReport> {
+    import_located_tests(test_forms, None, base_dir, pool, cache, pipeline, options)
+}
+
+/// `import_tests` with the issues of the tests read from a sheet pointing to its cells
+fn import_located_tests(
+    test_forms: Vec<TestForm>,
+    source: Option<SheetSource>,
+    base_dir: &Path,
+    pool: &DbPool,
+    cache: &QuestionCache,
+    pipeline: &ImagePipeline,
+    options: ImportOptions,
+) -> anyhow::Result<ImportReport> {
+    let mut validation = validate_tests(&test_forms, source, base_dir);
+    let invalid_tests = validation.invalid_tests();
+
+    let mut tests_vec: Vec<NewTest> = Vec::with_capacity(test_forms.len());
+    let mut report = PipelineReport::default();
+
+    for (index, test_model) in test_forms.into_iter().enumerate() {
+        if invalid_tests.contains(&index) {
+            continue;
+        }
+
+        match test_model.into_test(base_dir, pipeline, &mut report) {
+            Ok(test) => tests_vec.push(test),
+            Err(err) => validation.error(index, "image_path", format!("{:#}", err)),
+        }
+    }
+
+    info!("{}", report);
+
+    for issue in validation.issues.iter() {
+        match issue.severity {
+            Severity::Error => error!("{}", issue),
+            Severity::Warning => warn!("{}", issue),
+        }
+    }
+
+    if options.strict && validation.has_errors() {
+        return Err(anyhow!(
+            "Nothing was imported since {} tests are invalid",
+            validation.invalid_tests().len()
+        ));
+    }
+
+    // The skipped invalid tests would be retired otherwise
+    let retire_missing = options.retire_missing && !validation.has_errors();
+    if options.retire_missing && !retire_missing {
+        warn!("The missing tests aren't retired since some tests are invalid");
+    }
+
+    let db = pool.get().unwrap();
+
+    let transaction_result = db.transaction(|| {
+        let summary = upsert_tests(db.deref(), tests_vec, retire_missing)?;
+        match options.dry_run {
+            true => Err(anyhow::Error::from(DryRun(summary))),
+            false => Ok(summary),
+        }
+    });
+
+    let summary = match transaction_result {
+        Ok(summary) => summary,
+        Err(err) => {
+            let DryRun(summary) = err.downcast::<DryRun>()?;
+            info!("Dry run, nothing was imported: {}", summary);
+            return Ok(ImportReport { summary, validation });
+        }
+    };
+    images::collect_garbage(db.deref())?;
+
+    info!("The tests were imported successfully: {}", summary);
+
+    cache.invalidate();
+
+    Ok(ImportReport { summary, validation })
+}
+
+fn upsert_tests(db: &SqliteConnection, tests_vec: Vec<NewTest>, retire_missing: bool) -> anyhow::Result<ImportSummary> {
+    let existing_tests = tests::table
+        .order(tests::id)
+        .load::<Test>(db)
+        .map_err(|err| anyhow!("Failed to load tests - {}", err))?;
+
+    let mut summary = ImportSummary::default();
+    let mut matched_ids = HashSet::new();
+
+    for test in tests_vec.into_iter() {
+        let unmatched = |existing: &&Test| !matched_ids.contains(&existing.id);
+        let keyless_with_description =
+            |existing: &&Test| existing.key.is_none() && existing.description == test.description;
+        let existing_test = match test.key.as_ref() {
+            Some(key) => existing_tests
+                .iter()
+                .filter(unmatched)
+                .find(|existing| existing.key.as_ref() == Some(key))
+                // The key is given to a test imported without it before
+                .or_else(|| existing_tests.iter().filter(unmatched).find(keyless_with_description)),
+            None => existing_tests.iter().filter(unmatched).find(keyless_with_description),
+        };
+
+        // The image exported from this DB is kept as is instead of the re-encoded one, so a round trip changes nothing
+        let exported_image = existing_test
+            .filter(|existing| existing.image_hash.is_some() && existing.image_hash == test.source_image_hash);
+        let (image_hash, thumbnail_hash, retina_hash) = match (exported_image, test.image) {
+            (Some(existing), _) => (
+                existing.image_hash.clone(),
+                existing.thumbnail_hash.clone(),
+                existing.retina_hash.clone(),
+            ),
+            (None, Some(processed)) => {
+                let store = |data: Vec<u8>| images::store_image(db, data).map(Some);
+                (
+                    store(processed.image)?,
+                    store(processed.thumbnail)?,
+                    processed
+                        .retina
+                        .map(|retina| images::store_image(db, retina))
+                        .transpose()?,
+                )
+            }
+            (None, None) => (None, None, None),
+        };
+
+        match existing_test {
+            Some(existing) => {
+                matched_ids.insert(existing.id);
+
+                if !existing.retired
+                    && existing.key == test.key
+                    && existing.description == test.description
+                    && existing.answers == test.answers
+                    && existing.right_answer_id == test.right_answer_id
+                    && existing.category == test.category
+                    && existing.explanation == test.explanation
+                    && existing.image_hash == image_hash
+                    && existing.thumbnail_hash == thumbnail_hash
+                    && existing.retina_hash == retina_hash
+                {
+                    summary.unchanged += 1;
+                    continue;
+                }
+
+                diesel::update(tests::table.filter(tests::id.eq(existing.id)))
+                    .set((
+                        tests::key.eq(test.key),
+                        tests::description.eq(test.description),
+                        tests::answers.eq(test.answers),
+                        tests::right_answer_id.eq(test.right_answer_id),
+                        tests::category.eq(test.category),
+                        tests::explanation.eq(test.explanation),
+                        tests::image_hash.eq(image_hash),
+                        tests::thumbnail_hash.eq(thumbnail_hash),
+                        tests::retina_hash.eq(retina_hash),
+                        tests::retired.eq(false),
+                    ))
+                    .execute(db)
+                    .map_err(|err| anyhow!("Failed to update {} test - {}", existing.id, err))?;
+                summary.updated += 1;
+            }
+            None => {
+                let insert_result = insert_into(tests::table)
+                    .values(&(
+                        tests::key.eq(test.key),
+                        tests::description.eq(test.description),
+                        tests::answers.eq(test.answers),
+                        tests::right_answer_id.eq(test.right_answer_id),
+                        tests::category.eq(test.category),
+                        tests::explanation.eq(test.explanation),
+                        tests::image_hash.eq(image_hash),
+                        tests::thumbnail_hash.eq(thumbnail_hash),
+                        tests::retina_hash.eq(retina_hash),
+                    ))
+                    .execute(db)
+                    .map_err(anyhow::Error::from)?;
+
+                if let 0 = insert_result {
+                    return Err(anyhow!("Failed to insert a row to the Test table"));
+                }
+                summary.created += 1;
+            }
+        }
+    }
+
+    if retire_missing {
+        let missing_ids = existing_tests
+            .iter()
+            .filter(|existing| !existing.retired && !matched_ids.contains(&existing.id))
+            .map(|existing| existing.id)
+            .collect::<Vec<_>>();
+        // Every id is a bound variable, so the update is split to stay within the limit of SQLite
+        for ids in missing_ids.chunks(MAX_BOUND_IDS) {
+            summary.retired += diesel::update(tests::table.filter(tests::id.eq_any(ids)))
+                .set(tests::retired.eq(true))
+                .execute(db)
+                .map_err(|err| anyhow!("Failed to retire the missing tests - {}", err))?;
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod _tests {
+    use super::*;
+    use crate::{
+        db::{get_tests, testing::*},
+        image_pipeline::ImagePipelineConfig,
+    };
+
+    #[test]
+    fn import_tests_twice_changes_nothing() {
+        let (pool, path) = temp_db();
+        let forms = || vec![test_form(Some("q1"), "First", 1), test_form(None, "Second", 2)];
+
+        let first = import(forms(), &pool, false).unwrap();
+        let second = import(forms(), &pool, false).unwrap();
+        let tests_count = get_tests(&pool).unwrap().len();
+
+        remove_temp_db(pool, path);
+        assert_eq!(
+            first,
+            ImportSummary {
+                created: 2,
+                ..ImportSummary::default()
+            }
+        );
+        assert_eq!(
+            second,
+            ImportSummary {
+                unchanged: 2,
+                ..ImportSummary::default()
+            }
+        );
+        assert_eq!(tests_count, 2);
+    }
+
+    #[test]
+    fn import_tests_updates_tests_by_key_and_keeps_their_ids() {
+        let (pool, path) = temp_db();
+
+        import(vec![test_form(Some("q1"), "First", 1)], &pool, false).unwrap();
+        let id_before = get_tests(&pool).unwrap()[0].id;
+        let summary = import(vec![test_form(Some("q1"), "First, reworded", 2)], &pool, false).unwrap();
+        let tests_after = get_tests(&pool).unwrap();
+
+        remove_temp_db(pool, path);
+        assert_eq!(summary.updated, 1);
+        assert_eq!(tests_after.len(), 1);
+        assert_eq!(tests_after[0].id, id_before);
+        assert_eq!(tests_after[0].description, "First, reworded");
+        assert_eq!(tests_after[0].right_answer_id, 2);
+    }
+
+    #[test]
+    fn import_tests_retires_missing_tests_and_brings_them_back() {
+        let (pool, path) = temp_db();
+        let both = || vec![test_form(Some("q1"), "First", 1), test_form(Some("q2"), "Second", 1)];
+
+        import(both(), &pool, false).unwrap();
+        let kept = import(vec![test_form(Some("q1"), "First", 1)], &pool, false).unwrap();
+        let retired = import(vec![test_form(Some("q1"), "First", 1)], &pool, true).unwrap();
+        let served_after_retire = get_tests(&pool).unwrap().len();
+        let restored = import(both(), &pool, true).unwrap();
+        let served_after_restore = get_tests(&pool).unwrap().len();
+
+        remove_temp_db(pool, path);
+        assert_eq!(kept.retired, 0);
+        assert_eq!(retired.retired, 1);
+        assert_eq!(served_after_retire, 1);
+        assert_eq!((restored.updated, restored.unchanged), (1, 1));
+        assert_eq!(served_after_restore, 2);
+    }
+
+    #[test]
+    fn import_tests_skips_invalid_tests() {
+        let (pool, path) = temp_db();
+
+        let report = import_with(
+            vec![
+                test_form(Some("q1"), "First", 1),
+                test_form(Some("q1"), "Second", 1),
+                test_form(None, "Third", 3),
+            ],
+            &pool,
+            ImportOptions::default(),
+        )
+        .unwrap();
+        let tests_count = get_tests(&pool).unwrap().len();
+
+        remove_temp_db(pool, path);
+        assert_eq!(report.summary.created, 1);
+        assert_eq!(report.validation.invalid_tests().len(), 2);
+        assert_eq!(tests_count, 1);
+    }
+
+    #[test]
+    fn import_tests_in_strict_mode_imports_nothing_if_any_test_is_invalid() {
+        let (pool, path) = temp_db();
+        let options = ImportOptions {
+            strict: true,
+            ..ImportOptions::default()
+        };
+
+        let result = import_with(
+            vec![test_form(Some("q1"), "First", 1), test_form(Some("q2"), "Second", 0)],
+            &pool,
+            options,
+        );
+        let tests_count = get_tests(&pool).unwrap().len();
+
+        remove_temp_db(pool, path);
+        assert!(result.is_err());
+        assert_eq!(tests_count, 0);
+    }
+
+    #[test]
+    fn import_tests_dry_run_counts_changes_without_writing() {
+        let (pool, path) = temp_db();
+        let options = ImportOptions {
+            dry_run: true,
+            ..ImportOptions::default()
+        };
+
+        import(vec![test_form(Some("q1"), "First", 1)], &pool, false).unwrap();
+        let report = import_with(
+            vec![test_form(Some("q1"), "First", 2), test_form(Some("q2"), "Second", 1)],
+            &pool,
+            options,
+        )
+        .unwrap();
+        let tests_after = get_tests(&pool).unwrap();
+
+        remove_temp_db(pool, path);
+        assert_eq!((report.summary.created, report.summary.updated), (1, 1));
+        assert_eq!(tests_after.len(), 1);
+        assert_eq!(tests_after[0].right_answer_id, 1);
+    }
+
+    #[test]
+    fn import_tests_invalidates_the_cache() {
+        let (pool, path) = temp_db();
+        let cache = QuestionCache::new();
+        let pipeline = ImagePipeline::new(ImagePipelineConfig::default());
+
+        import(vec![test_form(Some("q1"), "First", 1)], &pool, false).unwrap();
+        let cached_before = cache.load_bank(&pool).unwrap().tests().len();
+        import_tests(
+            vec![test_form(Some("q1"), "First", 1), test_form(Some("q2"), "Second", 1)],
+            Path::new(""),
+            &pool,
+            &cache,
+            &pipeline,
+            ImportOptions::default(),
+        )
+        .unwrap();
+        let invalidated = cache.bank().is_none();
+        let cached_after = cache.load_bank(&pool).unwrap().tests().len();
+
+        remove_temp_db(pool, path);
+        assert_eq!(cached_before, 1);
+        assert!(invalidated);
+        assert_eq!(cached_after, 2);
+    }
+
+    #[test]
+    fn import_tests_retires_more_tests_than_sqlite_binds_at_once() {
+        let (pool, path) = temp_db();
+        let forms = (0..1200)
+            .map(|index| test_form(Some(&format!("q{}", index)), &format!("Test {}", index), 1))
+            .collect();
+
+        import(forms, &pool, false).unwrap();
+        let summary = import(vec![test_form(Some("q0"), "Test 0", 1)], &pool, true).unwrap();
+        let served = get_tests(&pool).unwrap().len();
+
+        remove_temp_db(pool, path);
+        assert_eq!(summary.retired, 1199);
+        assert_eq!(served, 1);
+    }
+}
@@ -0,0 +1,299 @@
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use diesel::{
+    sql_query,
+    sql_types::{BigInt, Bool, Integer},
+    ExpressionMethods, QueryDsl, RunQueryDsl,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, ops::Deref};
+
+use super::{model::User, schema::users, DbPool};
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LeaderboardWindow {
+    #[default]
+    All,
+    Month,
+    Week,
+}
+
+impl LeaderboardWindow {
+    /// The start of the current calendar month or week (from Monday) in UTC, `None` for all the time
+    pub fn since(self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let today = now.date_naive();
+        let first_day = match self {
+            LeaderboardWindow::All => return None,
+            LeaderboardWindow::Month => today.with_day(1)?,
+            LeaderboardWindow::Week => today - Duration::days(today.weekday().num_days_from_monday() as i64),
+        };
+
+        Some(Utc.from_utc_datetime(&first_day.and_hms_opt(0, 0, 0)?))
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct LeaderboardEntry {
+    pub rank: usize,
+    /// The display name of the user or their full name
+    pub name: String,
+    pub scores: i64,
+}
+
+/// Where the caller stands, it's known even when they are hidden from the others
+#[derive(Serialize, Debug, PartialEq)]
+pub struct LeaderboardRank {
+    /// `None` when the user has no scores in the window
+    pub rank: Option<usize>,
+    pub scores: i64,
+    pub hidden: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Leaderboard {
+    pub window: LeaderboardWindow,
+    pub since: Option<String>,
+    pub page: usize,
+    pub per_page: usize,
+    /// Users on the whole leaderboard, not only on the page
+    pub total: usize,
+    pub entries: Vec<LeaderboardEntry>,
+    pub me: Option<LeaderboardRank>,
+}
+
+#[derive(QueryableByName)]
+struct WindowScores {
+    #[sql_type = "Integer"]
+    user_id: i32,
+    #[sql_type = "Bool"]
+    leaderboard_hidden: bool,
+    #[sql_type = "BigInt"]
+    scores: i64,
+}
+
+/// Ranks the users by the scores of their answers in the window, users with equal scores share the rank.
+/// Hidden users aren't ranked, so they don't push the others down
+pub fn leaderboard(
+    pool: &DbPool,
+    window: LeaderboardWindow,
+    page: usize,
+    per_page: usize,
+    caller: Option<&User>,
+    now: DateTime<Utc>,
+) -> anyhow::Result<Leaderboard> {
+    let since = window.since(now);
+    let db = pool.get().unwrap();
+
+    // Only the scores are aggregated for every user, the names are loaded for the page
+    let rows = sql_query(
+        "SELECT users.id AS user_id, users.leaderboard_hidden, SUM(user_answers.scores) AS scores
+         FROM user_answers INNER JOIN users ON users.id = user_answers.user_id
+         WHERE user_answers.answered_at >= ?
+         GROUP BY users.id
+         HAVING SUM(user_answers.scores) > 0
+         ORDER BY scores DESC, users.id",
+    )
+    .bind::<BigInt, _>(since.map_or(i64::MIN, |since| since.timestamp()))
+    .load::<WindowScores>(db.deref())
+    .map_err(|err| anyhow!("Failed to load the leaderboard - {}", err))?;
+
+    let visible = rows.iter().filter(|row| !row.leaderboard_hidden).collect::<Vec<_>>();
+    // The visible users are sorted by their scores, so the users with more scores are a prefix of them
+    let rank_of = |scores: i64| 1 + visible.partition_point(|row| row.scores > scores);
+
+    let page_rows = visible
+        .iter()
+        .skip(page.saturating_sub(1).saturating_mul(per_page))
+        .take(per_page)
+        .collect::<Vec<_>>();
+    let names = users::table
+        .filter(users::id.eq_any(page_rows.iter().map(|row| row.user_id).collect::<Vec<_>>()))
+        .select((users::id, users::name, users::second_name, users::display_name))
+        .load::<(i32, String, String, Option<String>)>(db.deref())
+        .map_err(|err| anyhow!("Failed to load the names of the leaderboard - {}", err))?
+        .into_iter()
+        .map(|(user_id, name, second_name, display_name)| {
+            (
+                user_id,
+                display_name.unwrap_or_else(|| format!("{} {}", name, second_name)),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+    let entries = page_rows
+        .iter()
+        .map(|row| LeaderboardEntry {
+            rank: rank_of(row.scores),
+            name: names.get(&row.user_id).cloned().unwrap_or_default(),
+            scores: row.scores,
+        })
+        .collect();
+
+    let me = caller.map(|caller| match rows.iter().find(|row| row.user_id == caller.id) {
+        Some(row) => LeaderboardRank {
+            rank: Some(rank_of(row.scores)),
+            scores: row.scores,
+            hidden: row.leaderboard_hidden,
+        },
+        None => LeaderboardRank {
+            rank: None,
+            scores: 0,
+            hidden: caller.leaderboard_hidden,
+        },
+    });
+
+    Ok(Leaderboard {
+        window,
+        since: since.map(|since| since.to_rfc3339()),
+        page,
+        per_page,
+        total: visible.len(),
+        entries,
+        me,
+    })
+}
+
+/// The display name is cleared with `None`, the real name is shown then
+pub fn set_leaderboard_privacy(
+    user_id: i32,
+    display_name: Option<&str>,
+    hidden: bool,
+    pool: &DbPool,
+) -> anyhow::Result<()> {
+    let db = pool.get().unwrap();
+
+    diesel::update(users::table.filter(users::id.eq(user_id)))
+        .set((
+            users::display_name.eq(display_name),
+            users::leaderboard_hidden.eq(hidden),
+        ))
+        .execute(db.deref())
+        .map_err(|err| anyhow!("Failed to change the privacy of {} user - {}", user_id, err))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{get_tests, get_user, record_answer, testing::*};
+
+    #[test]
+    fn leaderboard_ranks_the_scores_of_the_window_and_respects_privacy() {
+        use chrono::{TimeZone, Utc};
+
+        let (pool, path) = temp_db();
+        import(vec![test_form(Some("q1"), "First", 1)], &pool, false).unwrap();
+        let test_id = get_tests(&pool).unwrap()[0].id;
+        let users = ["ann", "bob", "eve"]
+            .iter()
+            .map(|name| new_user(&pool, name))
+            .collect::<Vec<_>>();
+
+        // Wednesday, the week starts on Monday the 12th and the month on the 1st
+        let now = Utc.with_ymd_and_hms(2026, 10, 14, 12, 0, 0).unwrap();
+        let answers = [
+            answer_at(
+                users[0].id,
+                test_id,
+                50,
+                Utc.with_ymd_and_hms(2026, 9, 20, 0, 0, 0).unwrap(),
+            ),
+            answer_at(
+                users[0].id,
+                test_id,
+                5,
+                Utc.with_ymd_and_hms(2026, 10, 13, 0, 0, 0).unwrap(),
+            ),
+            answer_at(
+                users[1].id,
+                test_id,
+                10,
+                Utc.with_ymd_and_hms(2026, 10, 2, 0, 0, 0).unwrap(),
+            ),
+            answer_at(
+                users[2].id,
+                test_id,
+                10,
+                Utc.with_ymd_and_hms(2026, 10, 12, 0, 0, 0).unwrap(),
+            ),
+            answer_at(
+                users[2].id,
+                test_id,
+                0,
+                Utc.with_ymd_and_hms(2026, 10, 14, 0, 0, 0).unwrap(),
+            ),
+        ];
+        for answer in answers.iter() {
+            record_answer(answer, &pool).unwrap();
+        }
+        let ranks = |window| {
+            leaderboard(&pool, window, 1, 10, Some(&users[0]), now)
+                .unwrap()
+                .entries
+                .into_iter()
+                .map(|entry| (entry.rank, entry.name, entry.scores))
+                .collect::<Vec<_>>()
+        };
+
+        let all_time = ranks(LeaderboardWindow::All);
+        let month = ranks(LeaderboardWindow::Month);
+        let week = ranks(LeaderboardWindow::Week);
+        // The tie goes on over the page boundary
+        let second_page = leaderboard(&pool, LeaderboardWindow::All, 2, 2, None, now)
+            .unwrap()
+            .entries;
+        let far_page = leaderboard(&pool, LeaderboardWindow::All, usize::MAX, 100, None, now)
+            .unwrap()
+            .entries;
+        set_leaderboard_privacy(users[2].id, Some("Speedy"), false, &pool).unwrap();
+        set_leaderboard_privacy(users[0].id, None, true, &pool).unwrap();
+        let private = leaderboard(&pool, LeaderboardWindow::Month, 1, 1, Some(&users[0]), now).unwrap();
+        let ann_scores = get_user(&users_form("ann"), &pool).unwrap().scores;
+
+        remove_temp_db(pool, path);
+        assert_eq!(ann_scores, 55);
+        assert_eq!(
+            all_time,
+            vec![
+                (1, "ann Lee".to_owned(), 55),
+                (2, "bob Lee".to_owned(), 10),
+                (2, "eve Lee".to_owned(), 10)
+            ]
+        );
+        assert_eq!(
+            month,
+            vec![
+                (1, "bob Lee".to_owned(), 10),
+                (1, "eve Lee".to_owned(), 10),
+                (3, "ann Lee".to_owned(), 5)
+            ]
+        );
+        assert_eq!(week, vec![(1, "eve Lee".to_owned(), 10), (2, "ann Lee".to_owned(), 5)]);
+        assert_eq!(
+            second_page,
+            vec![LeaderboardEntry {
+                rank: 2,
+                name: "eve Lee".to_owned(),
+                scores: 10
+            }]
+        );
+        assert!(far_page.is_empty());
+        assert_eq!(private.total, 2);
+        assert_eq!(
+            private.entries,
+            vec![LeaderboardEntry {
+                rank: 1,
+                name: "bob Lee".to_owned(),
+                scores: 10
+            }]
+        );
+        assert_eq!(
+            private.me,
+            Some(LeaderboardRank {
+                rank: Some(3),
+                scores: 5,
+                hidden: true
+            })
+        );
+    }
+}
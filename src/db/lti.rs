@@ -0,0 +1,69 @@
+use diesel::{
+    dsl::exists, insert_into, select, BoolExpressionMethods, Connection, ExpressionMethods, OptionalExtension,
+    QueryDsl, RunQueryDsl,
+};
+use std::ops::Deref;
+
+use super::{
+    encrypt_password,
+    model::User,
+    schema::{lti_users, users},
+    DbPool,
+};
+
+/// The local user of an LMS user, created on their first launch with a random password.
+/// Local users are told apart by their names, so when the names are taken the LMS user id is added to the second name
+pub fn lti_user(
+    pool: &DbPool,
+    issuer: &str,
+    subject: &str,
+    given_name: &str,
+    family_name: &str,
+) -> anyhow::Result<User> {
+    let db = pool.get().unwrap();
+
+    db.transaction(|| {
+        let mapped = lti_users::table
+            .inner_join(users::table)
+            .filter(lti_users::issuer.eq(issuer).and(lti_users::subject.eq(subject)))
+            .select(users::all_columns)
+            .first::<User>(db.deref())
+            .optional()?;
+        if let Some(user) = mapped {
+            return Ok(user);
+        }
+
+        // Encrypting is slow, so the password is only made for a new user
+        let password = encrypt_password(hex::encode(rand::random::<[u8; 16]>()))?;
+
+        let same_names = users::name.eq(given_name).and(users::second_name.eq(family_name));
+        let second_name = match select(exists(users::table.filter(same_names))).get_result(db.deref())? {
+            true => format!("{} ({})", family_name, subject),
+            false => family_name.to_owned(),
+        };
+
+        insert_into(users::table)
+            .values((
+                users::name.eq(given_name),
+                users::second_name.eq(&second_name),
+                users::password.eq(&password),
+                users::scores.eq(0),
+            ))
+            .execute(db.deref())?;
+        let user = users::table
+            .filter(users::name.eq(given_name).and(users::second_name.eq(&second_name)))
+            .order(users::id.desc())
+            .first::<User>(db.deref())?;
+
+        insert_into(lti_users::table)
+            .values((
+                lti_users::issuer.eq(issuer),
+                lti_users::subject.eq(subject),
+                lti_users::user_id.eq(user.id),
+            ))
+            .execute(db.deref())?;
+
+        Ok(user)
+    })
+    .map_err(|err: anyhow::Error| anyhow!("Failed to map {} user of {} - {}", subject, issuer, err))
+}
@@ -0,0 +1,218 @@
+use diesel::{
+    delete, dsl::count_star, replace_into, sqlite::Sqlite, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl,
+    SqliteConnection,
+};
+use serde::Serialize;
+use std::ops::Deref;
+
+use super::{
+    model::{Marathon, NewUserAnswer},
+    schema::{marathons, tests},
+    DbPool,
+};
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct MarathonProgress {
+    /// The next test to answer, `None` once the marathon is finished
+    #[serde(skip)]
+    pub test_id: Option<i32>,
+    pub category: Option<String>,
+    /// Tests of the marathon answered so far
+    pub position: i64,
+    /// Tests of the marathon, including the ones added to the bank after it started
+    pub total: i64,
+    pub answers: i32,
+    pub correct: i32,
+    pub accuracy: Option<f64>,
+    pub finished: bool,
+    /// Unix time in seconds
+    pub started_at: i64,
+}
+
+/// The served tests the marathon walks through
+fn marathon_tests(category: Option<&str>) -> tests::BoxedQuery<'_, Sqlite> {
+    let query = tests::table.filter(tests::retired.eq(false)).into_boxed();
+    match category {
+        Some(category) => query.filter(tests::category.eq(category)),
+        None => query,
+    }
+}
+
+fn next_test(db: &SqliteConnection, marathon: &Marathon) -> diesel::QueryResult<Option<i32>> {
+    marathon_tests(marathon.category.as_deref())
+        .filter(tests::id.gt(marathon.last_test_id))
+        .select(tests::id)
+        .order(tests::id)
+        .first::<i32>(db)
+        .optional()
+}
+
+/// Moves the marathon of the user along when the answer is to its next test,
+/// it's called in the transaction recording the answer
+pub(super) fn advance(db: &SqliteConnection, answer: &NewUserAnswer) -> diesel::QueryResult<()> {
+    let marathon = match marathons::table.find(answer.user_id).first::<Marathon>(db).optional()? {
+        Some(marathon) => marathon,
+        None => return Ok(()),
+    };
+    if next_test(db, &marathon)? != Some(answer.test_id) {
+        return Ok(());
+    }
+
+    diesel::update(marathons::table.find(answer.user_id))
+        .set((
+            marathons::last_test_id.eq(answer.test_id),
+            marathons::answers.eq(marathons::answers + 1),
+            marathons::correct.eq(marathons::correct + answer.correct as i32),
+        ))
+        .execute(db)?;
+
+    Ok(())
+}
+
+/// Starts the marathon of the user from the beginning, the previous one is dropped.
+/// `false` when there are no tests to walk through, the previous marathon is kept then
+pub fn start_marathon(pool: &DbPool, user_id: i32, category: Option<String>, now: i64) -> anyhow::Result<bool> {
+    let db = pool.get().unwrap();
+
+    let tests = marathon_tests(category.as_deref())
+        .select(count_star())
+        .first::<i64>(db.deref())
+        .map_err(|err| anyhow!("Failed to count the tests of a marathon - {}", err))?;
+    if tests == 0 {
+        return Ok(false);
+    }
+
+    let marathon = Marathon {
+        user_id,
+        category,
+        last_test_id: 0,
+        answers: 0,
+        correct: 0,
+        started_at: now,
+    };
+    replace_into(marathons::table)
+        .values(&marathon)
+        .execute(db.deref())
+        .map_err(|err| anyhow!("Failed to start a marathon of {} user - {}", user_id, err))?;
+
+    Ok(true)
+}
+
+/// `false` when the user has no marathon
+pub fn end_marathon(pool: &DbPool, user_id: i32) -> anyhow::Result<bool> {
+    let db = pool.get().unwrap();
+
+    delete(marathons::table.find(user_id))
+        .execute(db.deref())
+        .map(|deleted| deleted > 0)
+        .map_err(|err| anyhow!("Failed to end the marathon of {} user - {}", user_id, err))
+}
+
+/// `None` when the user hasn't started a marathon
+pub fn marathon(pool: &DbPool, user_id: i32) -> anyhow::Result<Option<MarathonProgress>> {
+    let db = pool.get().unwrap();
+
+    let progress = || -> diesel::QueryResult<Option<MarathonProgress>> {
+        let marathon = match marathons::table
+            .find(user_id)
+            .first::<Marathon>(db.deref())
+            .optional()?
+        {
+            Some(marathon) => marathon,
+            None => return Ok(None),
+        };
+        let test_id = next_test(db.deref(), &marathon)?;
+        let category = marathon.category.as_deref();
+        let total = marathon_tests(category).select(count_star()).first::<i64>(db.deref())?;
+        let position = marathon_tests(category)
+            .filter(tests::id.le(marathon.last_test_id))
+            .select(count_star())
+            .first::<i64>(db.deref())?;
+
+        Ok(Some(MarathonProgress {
+            test_id,
+            position,
+            total,
+            answers: marathon.answers,
+            correct: marathon.correct,
+            accuracy: match marathon.answers {
+                0 => None,
+                answers => Some(marathon.correct as f64 / answers as f64),
+            },
+            finished: test_id.is_none(),
+            started_at: marathon.started_at,
+            category: marathon.category,
+        }))
+    };
+
+    progress().map_err(|err| anyhow!("Failed to load the marathon of {} user - {}", user_id, err))
+}
+
+#[cfg(test)]
+mod _tests {
+    use super::*;
+    use crate::db::{get_tests, model, record_answer, testing::*};
+
+    #[test]
+    fn marathon_walks_through_the_category_once_and_starts_over() {
+        let (pool, path) = temp_db();
+        let in_signs = |key: &str| model::TestForm {
+            category: Some("Signs".to_owned()),
+            ..test_form(Some(key), key, 1)
+        };
+        let forms = vec![in_signs("s1"), test_form(Some("other"), "Other", 1), in_signs("s2")];
+        import(forms, &pool, false).unwrap();
+        let ids = get_tests(&pool).unwrap().iter().map(|test| test.id).collect::<Vec<_>>();
+        let user_id = new_user(&pool, "ann").id;
+        let answer = |test_id, correct| {
+            record_answer(&model::NewUserAnswer::new(user_id, test_id, 1, correct, 0), &pool).unwrap();
+            marathon(&pool, user_id).unwrap().unwrap()
+        };
+
+        let before_start = marathon(&pool, user_id).unwrap();
+        let started_without_tests = start_marathon(&pool, user_id, Some("Lights".to_owned()), 100).unwrap();
+        assert!(start_marathon(&pool, user_id, Some("Signs".to_owned()), 100).unwrap());
+        let started = marathon(&pool, user_id).unwrap().unwrap();
+        let out_of_order = answer(ids[2], true);
+        let first = answer(ids[0], false);
+        let finished = answer(ids[2], true);
+        let kept = start_marathon(&pool, user_id, Some("Lights".to_owned()), 200).unwrap();
+        let after_empty_start = marathon(&pool, user_id).unwrap().unwrap();
+        assert!(start_marathon(&pool, user_id, None, 200).unwrap());
+        let restarted = marathon(&pool, user_id).unwrap().unwrap();
+        let ended = end_marathon(&pool, user_id).unwrap();
+        let ended_twice = end_marathon(&pool, user_id).unwrap();
+
+        remove_temp_db(pool, path);
+        assert_eq!(before_start, None);
+        assert!(!started_without_tests);
+        assert!(!kept);
+        assert_eq!(after_empty_start, finished);
+        assert_eq!(
+            started,
+            MarathonProgress {
+                test_id: Some(ids[0]),
+                category: Some("Signs".to_owned()),
+                position: 0,
+                total: 2,
+                answers: 0,
+                correct: 0,
+                accuracy: None,
+                finished: false,
+                started_at: 100,
+            }
+        );
+        assert_eq!(out_of_order, started);
+        assert_eq!((first.test_id, first.position, first.answers), (Some(ids[2]), 1, 1));
+        assert_eq!(first.accuracy, Some(0.0));
+        assert_eq!((finished.test_id, finished.position, finished.correct), (None, 2, 1));
+        assert_eq!(finished.accuracy, Some(0.5));
+        assert!(finished.finished);
+        assert_eq!(
+            (restarted.test_id, restarted.total, restarted.answers),
+            (Some(ids[0]), 3, 0)
+        );
+        assert!(ended);
+        assert!(!ended_twice);
+    }
+}
@@ -0,0 +1,89 @@
+use diesel::{BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl};
+use std::{collections::BTreeMap, ops::Deref};
+
+use super::{
+    schema::{tests, user_answers},
+    DbPool,
+};
+
+#[derive(Default)]
+struct AnswerRun {
+    answered_wrong: bool,
+    /// Right answers in a row since the last wrong one
+    right_in_row: usize,
+}
+
+/// The served tests the user answered wrong and hasn't answered right `clear_after` times in a row since,
+/// in the order of the tests ids
+pub fn mistakes(pool: &DbPool, user_id: i32, clear_after: usize) -> anyhow::Result<Vec<i32>> {
+    let db = pool.get().unwrap();
+
+    let answers = user_answers::table
+        .inner_join(tests::table)
+        .filter(user_answers::user_id.eq(user_id).and(tests::retired.eq(false)))
+        .select((user_answers::test_id, user_answers::correct))
+        .order((user_answers::answered_at, user_answers::id))
+        .load::<(i32, bool)>(db.deref())
+        .map_err(|err| anyhow!("Failed to load answers of {} user - {}", user_id, err))?;
+
+    let mut runs = BTreeMap::<i32, AnswerRun>::new();
+    for (test_id, correct) in answers {
+        let run = runs.entry(test_id).or_default();
+        match correct {
+            true => run.right_in_row += 1,
+            false => {
+                run.answered_wrong = true;
+                run.right_in_row = 0;
+            }
+        }
+    }
+
+    Ok(runs
+        .into_iter()
+        .filter(|(_, run)| run.answered_wrong && run.right_in_row < clear_after)
+        .map(|(test_id, _)| test_id)
+        .collect())
+}
+
+#[cfg(test)]
+mod _tests {
+    use super::*;
+    use crate::db::{get_tests, model, record_answer, testing::*};
+
+    #[test]
+    fn mistakes_stay_until_answered_right_enough_times_in_a_row() {
+        let (pool, path) = temp_db();
+        let forms = vec![
+            test_form(Some("q1"), "First", 1),
+            test_form(Some("q2"), "Second", 1),
+            test_form(Some("q3"), "Third", 1),
+        ];
+        import(forms, &pool, false).unwrap();
+        let ids = get_tests(&pool).unwrap().iter().map(|test| test.id).collect::<Vec<_>>();
+        let user_id = new_user(&pool, "ann").id;
+
+        let answers = [
+            (ids[0], false),
+            (ids[1], true),
+            (ids[2], false),
+            (ids[0], true),
+            (ids[2], true),
+        ];
+        for (at, (test_id, correct)) in answers.iter().enumerate() {
+            let answer = model::NewUserAnswer {
+                answered_at: at as i64,
+                ..model::NewUserAnswer::new(user_id, *test_id, 1, *correct, 0)
+            };
+            record_answer(&answer, &pool).unwrap();
+        }
+        let cleared_by_one = mistakes(&pool, user_id, 1).unwrap();
+        let cleared_by_two = mistakes(&pool, user_id, 2).unwrap();
+        record_answer(&model::NewUserAnswer::new(user_id, ids[0], 2, false, 0), &pool).unwrap();
+        let answered_wrong_again = mistakes(&pool, user_id, 1).unwrap();
+
+        remove_temp_db(pool, path);
+        assert_eq!(cleared_by_one, Vec::<i32>::new());
+        assert_eq!(cleared_by_two, vec![ids[0], ids[2]]);
+        assert_eq!(answered_wrong_again, vec![ids[0]]);
+    }
+}
@@ -6,9 +6,8 @@ use diesel::{
     r2d2::{ConnectionManager, CustomizeConnection, Pool},
     select,
     sqlite::SqliteConnection,
-    BoolExpressionMethods, ExpressionMethods, QueryDsl, RunQueryDsl,
+    BoolExpressionMethods, Connection, ExpressionMethods, QueryDsl, RunQueryDsl,
 };
-use log::{debug, info};
 use rand::rngs::OsRng;
 use rsa::{pem, PaddingScheme, PublicKey, RSAPrivateKey, RSAPublicKey};
 
@@ -18,18 +17,62 @@ use std::{
     fs::File,
     io::Read,
     ops::Deref,
-    path::Path,
     time::Duration,
 };
 
+mod achievements;
+mod calibration;
+mod collections;
+mod daily;
+mod export;
+pub mod images;
+mod import;
+mod leaderboard;
+mod lti;
+mod marathon;
+mod mistakes;
 pub mod model;
+mod progress;
+mod quality;
+mod review;
 pub mod schema;
+#[cfg(test)]
+pub(crate) mod testing;
+pub mod validation;
 use crate::utils;
-use model::{Test, TestForm, UserForm};
-use schema::{tests, users};
+use model::UserForm;
+use schema::{tests, user_answers, users};
+
+pub use achievements::{
+    achievements, mark_achievements_seen, record_exam, unseen_achievements, Achievement, EarnedAchievement, ExamSource,
+};
+pub use calibration::{adaptive_test, calibrate_tests, spawn_calibration, AdaptivePick, CalibrationSummary};
+pub use collections::{
+    add_to_collection, bookmark, bookmarks, collection_tests, collections, create_collection, delete_collection,
+    remove_bookmark, remove_from_collection, Bookmark, Collection,
+};
+pub use daily::{
+    daily_challenge, daily_leaderboard, daily_set, local_day, record_daily_answer, set_time_zone, time_zone,
+    DailyAnswer, DailyAnswerOutcome, DailyChallenge, DailyLeaderboard, DailyLeaderboardEntry, Streak, DAILY_QUESTIONS,
+};
+pub use export::{export_tests, ExportBank, ExportOptions, ExportSummary};
+pub use import::{import_tests, insert_tests_to_db, ImportOptions, ImportReport, ImportSummary};
+pub use leaderboard::{
+    leaderboard, set_leaderboard_privacy, Leaderboard, LeaderboardEntry, LeaderboardRank, LeaderboardWindow,
+};
+pub use lti::lti_user;
+pub use marathon::{end_marathon, marathon, start_marathon, MarathonProgress};
+pub use mistakes::mistakes;
+pub use progress::{progress, CategoryProgress, Progress, TrendPeriod, TrendPoint};
+pub use quality::{question_quality, AnswerFrequency, QualityFlag, QuestionQuality};
+pub use review::{review_test, ReviewPick};
+
+embed_migrations!();
 
 pub type DbPool = Pool<ConnectionManager<SqliteConnection>>;
 pub const DEFAULT_DATABASE_URL: &str = "drive_tests_db.db";
+/// SQLite binds at most 999 variables in a statement, so the longer lists of ids are queried in chunks
+const MAX_BOUND_IDS: usize = 500;
 
 #[derive(Debug)]
 struct ConnectionCustomizer {
@@ -65,9 +108,14 @@ pub fn establish_connection() -> DbPool {
         DEFAULT_DATABASE_URL.to_string()
     });
 
+    establish_connection_to(&database_url)
+}
+
+/// Opens the pool to the given DB and brings it up to date
+pub fn establish_connection_to(database_url: &str) -> DbPool {
     let manager = ConnectionManager::<SqliteConnection>::new(database_url);
 
-    Pool::builder()
+    let pool = Pool::builder()
         .max_size(16)
         .connection_customizer(Box::new(ConnectionCustomizer {
             enable_wal: true,
@@ -75,7 +123,14 @@ pub fn establish_connection() -> DbPool {
             busy_timeout: Some(Duration::from_secs(5)),
         }))
         .build(manager)
-        .expect("Failed to crate DB pool")
+        .expect("Failed to crate DB pool");
+
+    let db = pool.get().unwrap();
+    embedded_migrations::run(db.deref()).expect("Failed to run the DB migrations");
+    images::adopt_legacy_images(db.deref()).expect("Failed to move the inline test images to the images store");
+    drop(db);
+
+    pool
 }
 
 pub fn registry_new_user(user: UserForm, pool: Data<DbPool>) -> anyhow::Result<()> {
@@ -178,24 +233,22 @@ pub fn verify_password(user: UserForm, pool: Data<DbPool>) -> anyhow::Result<boo
 
     Ok(decrypted_password == user.password)
 }
-pub fn get_test(pool: Data<DbPool>) -> anyhow::Result<model::Test> {
+pub fn get_images(pool: &DbPool) -> anyhow::Result<Vec<model::Image>> {
+    let db = pool.get().unwrap();
+
+    images::load_images(db.deref())
+}
+
+pub fn get_tests(pool: &DbPool) -> anyhow::Result<Vec<model::Test>> {
     use self::tests::dsl::*;
 
     let db = pool.get().unwrap();
-    let count: u32 = tests
-        .count()
-        .get_result::<i64>(db.deref())
-        .map_err(|err| anyhow!("Failed to get tests count - {}", err))? as u32;
 
-    let rand_test: i32 = (rand::random::<u32>() % count) as i32 + 1;
-
-    let test = tests
+    tests
+        .filter(retired.eq(false))
         .order(id)
-        .filter(id.eq(rand_test))
-        .first::<model::Test>(db.deref())
-        .map_err(|err| anyhow!("Failed to get a rand test - {}", err))?;
-
-    Ok(test)
+        .load::<model::Test>(db.deref())
+        .map_err(|err| anyhow!("Failed to load tests - {}", err))
 }
 
 pub fn add_scores(user: &UserForm, add_scores: u32, pool: &Data<DbPool>) -> anyhow::Result<()> {
@@ -232,6 +285,47 @@ pub fn add_scores(user: &UserForm, add_scores: u32, pool: &Data<DbPool>) -> anyh
     Ok(())
 }
 
+/// Saves the answer to the score history, adds its scores to the user total and reschedules the test for the user.
+/// The badges the answer brings are awarded too
+pub fn record_answer(answer: &model::NewUserAnswer, pool: &DbPool) -> anyhow::Result<()> {
+    let db = pool.get().unwrap();
+
+    db.transaction(|| save_answer(db.deref(), answer))
+        .map_err(|err: diesel::result::Error| {
+            anyhow!("Failed to record an answer of {} user - {}", answer.user_id, err)
+        })
+}
+
+/// `record_answer` in a transaction of the caller
+fn save_answer(db: &SqliteConnection, answer: &model::NewUserAnswer) -> diesel::QueryResult<()> {
+    insert_into(user_answers::table).values(answer).execute(db)?;
+    review::schedule(db, answer)?;
+    marathon::advance(db, answer)?;
+    diesel::update(users::table.filter(users::id.eq(answer.user_id)))
+        .set(users::scores.eq(users::scores + answer.scores))
+        .execute(db)?;
+
+    achievements::award(db, answer.user_id, answer.answered_at)
+}
+
+pub fn get_user(user: &UserForm, pool: &DbPool) -> anyhow::Result<model::User> {
+    use self::users::dsl::*;
+    let db = pool.get().unwrap();
+
+    users
+        .order(id)
+        .filter((name.eq(user.name.clone())).and(second_name.eq(user.second_name.clone())))
+        .first::<model::User>(db.deref())
+        .map_err(|err| {
+            anyhow!(
+                "Failed to find in {} {} user in the DB - {}",
+                user.name,
+                user.second_name,
+                err
+            )
+        })
+}
+
 pub fn get_scores(user: &UserForm, pool: &Data<DbPool>) -> anyhow::Result<u32> {
     use self::users::dsl::*;
     let db = pool.get().unwrap();
@@ -275,56 +369,14 @@ pub fn check_test_answer(test_id: u32, answer_id: u32, pool: &Data<DbPool>) -> a
     Ok(selected_test.right_answer_id == answer_id)
 }
 
-pub fn insert_tests_to_db(path: &Path, db: &DbPool) -> anyhow::Result<()> {
-    use schema::tests::dsl::*;
-
-    debug!("There are new tests to be inserted");
-
-    let mut file = File::open(path)?;
-
-    let mut buffer = String::new();
-    file.read_to_string(&mut buffer)?;
-
-    let test_forms = serde_json::from_str::<Vec<TestForm>>(&buffer)?;
-
-    let mut tests_vec: Vec<Test> = Vec::with_capacity(test_forms.len());
-
-    for test_model in test_forms.into_iter() {
-        let test = test_model.into_test()?;
-        tests_vec.push(test);
-    }
-
-    let db = db.get().unwrap();
-
-    for test in tests_vec.into_iter() {
-        let insert_result = insert_into(tests)
-            .values(&(
-                description.eq(test.description),
-                answers.eq(test.answers),
-                right_answer_id.eq(test.right_answer_id),
-                image.eq(test.image),
-            ))
-            .execute(db.deref())
-            .map_err(anyhow::Error::from)?;
-
-        if let 0 = insert_result {
-            return Err(anyhow!("Failed to insert a row to the Test table"));
-        }
-    }
-
-    info!("The new tests were inserted successfully");
-    Ok(())
-}
-
 #[cfg(test)]
 mod _tests {
     use super::*;
     use actix_web::web;
     use lazy_static::lazy_static;
+    use testing::PASSWORD;
     use uuid::Uuid;
 
-    const PASSWORD: &str = "password";
-
     lazy_static! {
         static ref DB: DbPool = establish_connection();
     }
@@ -444,4 +496,70 @@ mod _tests {
         remove_user_from_db(user, &db);
         assert_eq!(scores, rand_scores);
     }
+
+    #[test]
+    fn reverting_the_user_answers_keeps_the_lti_users() {
+        use schema::lti_users;
+        use testing::{remove_temp_db, revert_migrations, temp_db};
+
+        let (pool, path) = temp_db();
+        let user = lti_user(&pool, "https://lms.example", "learner-1", "ann", "Lee").unwrap();
+        set_leaderboard_privacy(user.id, Some("Speedy"), true, &pool).unwrap();
+        let lti_users = |pool: &DbPool| {
+            lti_users::table
+                .select(lti_users::user_id)
+                .load::<i32>(pool.get().unwrap().deref())
+                .unwrap()
+        };
+
+        // Every migration from the achievements back to the user answers
+        revert_migrations(&pool, 8);
+        let reverted_lti_users = lti_users(&pool);
+        drop(pool);
+        let pool = establish_connection_to(&path);
+        let redone_user = lti_user(&pool, "https://lms.example", "learner-1", "ann", "Lee").unwrap();
+        let redone_lti_users = lti_users(&pool);
+
+        remove_temp_db(pool, path);
+        assert_eq!(reverted_lti_users, vec![user.id]);
+        assert_eq!(redone_user.id, user.id);
+        assert_eq!(redone_user.display_name, None);
+        assert!(!redone_user.leaderboard_hidden);
+        assert_eq!(redone_lti_users, vec![user.id]);
+    }
+
+    #[test]
+    fn reverting_the_daily_challenges_keeps_the_users_and_their_history() {
+        use schema::user_answers;
+        use testing::{import, new_user, remove_temp_db, revert_migrations, temp_db, test_form, users_form};
+
+        let (pool, path) = temp_db();
+        import(vec![test_form(Some("q1"), "First", 1)], &pool, false).unwrap();
+        let test_id = daily_set(&pool, "2026-03-16").unwrap()[0];
+        let user = new_user(&pool, "ann");
+        record_answer(&model::NewUserAnswer::new(user.id, test_id, 1, true, 5), &pool).unwrap();
+        set_time_zone(&pool, user.id, Some("Asia/Tokyo")).unwrap();
+        let answers = |pool: &DbPool| {
+            user_answers::table
+                .count()
+                .get_result::<i64>(pool.get().unwrap().deref())
+                .unwrap()
+        };
+
+        // The achievements go first, then the daily challenges
+        revert_migrations(&pool, 2);
+        let reverted_answers = answers(&pool);
+        drop(pool);
+        let pool = establish_connection_to(&path);
+        let redone_user = get_user(&users_form("ann"), &pool).unwrap();
+        let redone_answers = answers(&pool);
+        let redone_time_zone = time_zone(&pool, user.id).unwrap();
+
+        remove_temp_db(pool, path);
+        assert_eq!(reverted_answers, 1);
+        assert_eq!(redone_user.id, user.id);
+        assert_eq!(redone_user.scores, 5);
+        assert_eq!(redone_time_zone, None);
+        assert_eq!(redone_answers, 1);
+    }
 }
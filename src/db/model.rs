@@ -1,6 +1,13 @@
-use crate::db::schema::{tests, users};
-use image::{io::Reader as ImageReader, ImageOutputFormat};
+use crate::{
+    db::{
+        self,
+        schema::{images, marathons, review_states, test_calibrations, tests, user_answers, users},
+    },
+    image_pipeline::{ImagePipeline, PipelineReport, ProcessedImage},
+};
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
 
 #[derive(Queryable, Deserialize, Insertable)]
 #[table_name = "users"]
@@ -10,6 +17,10 @@ pub struct User {
     pub second_name: String,
     pub password: String,
     pub scores: i32,
+    /// The name shown on the leaderboard instead of the real one
+    pub display_name: Option<String>,
+    /// The user isn't shown on the leaderboard at all
+    pub leaderboard_hidden: bool,
 }
 
 #[derive(Queryable, Deserialize, Insertable)]
@@ -19,7 +30,121 @@ pub struct Test {
     pub description: String,
     pub answers: String,
     pub right_answer_id: i32,
-    pub image: Option<Vec<u8>>,
+    pub image_hash: Option<String>,
+    pub thumbnail_hash: Option<String>,
+    pub retina_hash: Option<String>,
+    pub key: Option<String>,
+    pub retired: bool,
+    pub category: Option<String>,
+    /// Why the right answer is right, shown after the test is answered
+    pub explanation: Option<String>,
+}
+
+/// An answer of the score history
+#[derive(Queryable, Clone, Debug)]
+pub struct UserAnswer {
+    pub id: i32,
+    pub user_id: i32,
+    pub test_id: i32,
+    pub answer_id: i32,
+    pub correct: bool,
+    /// Scores the answer brought
+    pub scores: i32,
+    /// Unix time in seconds
+    pub answered_at: i64,
+    /// Milliseconds the user took to answer, `None` when the client didn't report it
+    pub response_ms: Option<i32>,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[table_name = "user_answers"]
+pub struct NewUserAnswer {
+    pub user_id: i32,
+    pub test_id: i32,
+    pub answer_id: i32,
+    pub correct: bool,
+    /// Scores the answer brought
+    pub scores: i32,
+    /// Unix time in seconds
+    pub answered_at: i64,
+    /// Milliseconds the user took to answer, `None` when the client didn't report it
+    pub response_ms: Option<i32>,
+}
+
+impl NewUserAnswer {
+    /// An answer given right now
+    pub fn new(user_id: i32, test_id: i32, answer_id: i32, correct: bool, scores: i32) -> Self {
+        NewUserAnswer {
+            user_id,
+            test_id,
+            answer_id,
+            correct,
+            scores,
+            answered_at: chrono::Utc::now().timestamp(),
+            response_ms: None,
+        }
+    }
+}
+
+/// Where a test is in the spaced repetition schedule of a user
+#[derive(Queryable, Insertable, Clone, Debug, PartialEq)]
+#[table_name = "review_states"]
+pub struct ReviewState {
+    pub user_id: i32,
+    pub test_id: i32,
+    /// Right answers in a row
+    pub repetitions: i32,
+    pub ease_factor: f64,
+    pub interval_days: i32,
+    /// Unix time in seconds the test is to be reviewed at
+    pub due_at: i64,
+}
+
+/// Where a user is in their walk through the bank
+#[derive(Queryable, Insertable, Clone, Debug, PartialEq)]
+#[table_name = "marathons"]
+pub struct Marathon {
+    pub user_id: i32,
+    pub category: Option<String>,
+    pub last_test_id: i32,
+    pub answers: i32,
+    pub correct: i32,
+    /// Unix time in seconds
+    pub started_at: i64,
+}
+
+/// IRT parameters of a test fitted by the calibration job
+#[derive(Queryable, Insertable, Clone, Debug, PartialEq)]
+#[table_name = "test_calibrations"]
+pub struct TestCalibration {
+    pub test_id: i32,
+    pub difficulty: f64,
+    /// Always 1 for the 1PL model
+    pub discrimination: f64,
+    pub answers: i32,
+    /// Unix time in seconds
+    pub calibrated_at: i64,
+}
+
+#[derive(Queryable, Insertable)]
+#[table_name = "images"]
+pub struct Image {
+    pub hash: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// A test ready to be inserted, its images are stored in the `images` table separately
+pub struct NewTest {
+    pub key: Option<String>,
+    pub description: String,
+    pub answers: String,
+    pub right_answer_id: i32,
+    pub category: Option<String>,
+    pub explanation: Option<String>,
+    pub image: Option<ProcessedImage>,
+    /// Hash of the image file before processing, equal to the stored hash when the image was exported from the DB
+    pub source_image_hash: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -29,44 +154,65 @@ pub struct UserForm {
     pub password: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct TestForm {
+    /// Stable identifier of the test across imports, tests without it are identified by their description
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
     pub description: String,
     pub answers: Vec<String>,
     pub right_answer_id: i32,
+    /// Relative paths are resolved against the directory of the imported file
     pub image_path: Option<String>,
+    /// Image embedded in the imported file itself, used instead of `image_path`
+    #[serde(skip)]
+    pub image_data: Option<Vec<u8>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<String>,
 }
 
 impl TestForm {
-    pub fn into_test(self) -> anyhow::Result<Test> {
+    pub fn into_test(
+        self,
+        base_dir: &Path,
+        pipeline: &ImagePipeline,
+        report: &mut PipelineReport,
+    ) -> anyhow::Result<NewTest> {
         let answers = serde_json::to_string(&self.answers)?;
-        let mut image = None;
-
-        if self.image_path.is_some() {
-            let image_path = self.image_path.as_ref().unwrap();
-            let image_quality = 75;
 
-            let image_output_format = if image_path.ends_with(".jpeg") {
-                ImageOutputFormat::Jpeg(image_quality)
-            } else {
-                ImageOutputFormat::Png
-            };
+        let image = match (self.image_data, self.image_path.as_ref()) {
+            (Some(data), _) => Some((data, "embedded".to_owned())),
+            (None, Some(image_path)) => {
+                let image_path = base_dir.join(image_path);
+                let data =
+                    fs::read(&image_path).with_context(|| format!("failed to read {} image", image_path.display()))?;
+                Some((data, image_path.display().to_string()))
+            }
+            (None, None) => None,
+        };
 
-            let mut buffer: Vec<u8> = Vec::new();
-
-            ImageReader::open(image_path)?
-                .decode()?
-                .write_to(&mut buffer, image_output_format)?;
-
-            image = Some(buffer);
-        }
+        let (image, source_image_hash) = match image {
+            Some((data, name)) => {
+                let processed = pipeline
+                    .process(&data)
+                    .with_context(|| format!("failed to process {} image", name))?;
+                report.add(&processed);
+                (Some(processed), Some(db::images::hash_of(&data)))
+            }
+            None => (None, None),
+        };
 
-        Ok(Test {
-            id: 0,
+        Ok(NewTest {
+            key: self.key,
             description: self.description,
             answers,
             right_answer_id: self.right_answer_id,
+            category: self.category,
+            explanation: self.explanation,
             image,
+            source_image_hash,
         })
     }
 }
@@ -0,0 +1,280 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::Deref,
+};
+
+use super::{
+    schema::{tests, user_answers},
+    DbPool,
+};
+
+/// Periods of the trend
+const TREND_PERIODS: i64 = 12;
+/// Answers a category needs before it can be called one of the weakest
+const MIN_WEAKEST_ANSWERS: usize = 5;
+const WEAKEST_CATEGORIES: usize = 3;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TrendPeriod {
+    Day,
+    #[default]
+    Week,
+}
+
+impl TrendPeriod {
+    /// The first day of the period the date is in, weeks start on Monday
+    fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            TrendPeriod::Day => date,
+            TrendPeriod::Week => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+        }
+    }
+
+    fn length(self) -> Duration {
+        match self {
+            TrendPeriod::Day => Duration::days(1),
+            TrendPeriod::Week => Duration::weeks(1),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct CategoryProgress {
+    /// `None` for the tests without a category
+    pub category: Option<String>,
+    /// Tests of the category in the bank
+    pub total: usize,
+    /// Tests of the category the user has answered at least once
+    pub seen: usize,
+    pub answers: usize,
+    pub correct: usize,
+    /// `None` until the user answers a test of the category
+    pub accuracy: Option<f64>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TrendPoint {
+    /// The first day of the period
+    pub start: NaiveDate,
+    pub answers: usize,
+    pub correct: usize,
+    pub accuracy: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Progress {
+    pub total: usize,
+    pub seen: usize,
+    pub answers: usize,
+    pub correct: usize,
+    pub accuracy: Option<f64>,
+    pub categories: Vec<CategoryProgress>,
+    pub period: TrendPeriod,
+    /// Accuracy of the last periods in UTC, the oldest first, periods without answers included
+    pub trend: Vec<TrendPoint>,
+    /// The categories with the lowest accuracy among the ones with enough answers to judge
+    pub weakest: Vec<CategoryProgress>,
+}
+
+fn accuracy(correct: usize, answers: usize) -> Option<f64> {
+    match answers {
+        0 => None,
+        answers => Some(correct as f64 / answers as f64),
+    }
+}
+
+/// The progress of the user over the served tests, computed from their recorded answers
+pub fn progress(pool: &DbPool, user_id: i32, period: TrendPeriod, now: DateTime<Utc>) -> anyhow::Result<Progress> {
+    let db = pool.get().unwrap();
+
+    let bank = tests::table
+        .filter(tests::retired.eq(false))
+        .select((tests::id, tests::category))
+        .load::<(i32, Option<String>)>(db.deref())
+        .map_err(|err| anyhow!("Failed to load tests - {}", err))?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let answers = user_answers::table
+        .filter(user_answers::user_id.eq(user_id))
+        .select((user_answers::test_id, user_answers::correct, user_answers::answered_at))
+        .load::<(i32, bool, i64)>(db.deref())
+        .map_err(|err| anyhow!("Failed to load answers of {} user - {}", user_id, err))?
+        .into_iter()
+        // Retired tests aren't in the bank anymore
+        .filter(|(test_id, _, _)| bank.contains_key(test_id))
+        .collect::<Vec<_>>();
+
+    let mut categories = BTreeMap::<Option<String>, CategoryProgress>::new();
+    for category in bank.values() {
+        let progress = categories.entry(category.clone()).or_default();
+        progress.category = category.clone();
+        progress.total += 1;
+    }
+    let mut seen = HashSet::new();
+    for (test_id, correct, _) in answers.iter() {
+        let progress = categories.get_mut(&bank[test_id]).unwrap();
+        progress.answers += 1;
+        progress.correct += *correct as usize;
+        if seen.insert(*test_id) {
+            progress.seen += 1;
+        }
+    }
+    let categories = categories
+        .into_values()
+        .map(|progress| CategoryProgress {
+            accuracy: accuracy(progress.correct, progress.answers),
+            ..progress
+        })
+        .collect::<Vec<_>>();
+
+    let mut weakest = categories
+        .iter()
+        .filter(|progress| progress.answers >= MIN_WEAKEST_ANSWERS)
+        .cloned()
+        .collect::<Vec<_>>();
+    weakest.sort_by(|a, b| a.accuracy.partial_cmp(&b.accuracy).unwrap());
+    weakest.truncate(WEAKEST_CATEGORIES);
+
+    let last_period = period.start(now.date_naive());
+    let first_period = last_period - period.length() * (TREND_PERIODS - 1) as i32;
+    let mut trend = (0..TREND_PERIODS)
+        .map(|index| TrendPoint {
+            start: first_period + period.length() * index as i32,
+            answers: 0,
+            correct: 0,
+            accuracy: None,
+        })
+        .collect::<Vec<_>>();
+    for (_, correct, answered_at) in answers.iter() {
+        let date = match DateTime::from_timestamp(*answered_at, 0) {
+            Some(answered_at) => answered_at.date_naive(),
+            None => continue,
+        };
+        if let Some(point) = trend.iter_mut().find(|point| point.start == period.start(date)) {
+            point.answers += 1;
+            point.correct += *correct as usize;
+        }
+    }
+    for point in trend.iter_mut() {
+        point.accuracy = accuracy(point.correct, point.answers);
+    }
+
+    let correct = answers.iter().filter(|(_, correct, _)| *correct).count();
+    Ok(Progress {
+        total: bank.len(),
+        seen: seen.len(),
+        answers: answers.len(),
+        correct,
+        accuracy: accuracy(correct, answers.len()),
+        categories,
+        period,
+        trend,
+        weakest,
+    })
+}
+
+#[cfg(test)]
+mod _tests {
+    use super::*;
+    use crate::db::{get_tests, model, record_answer, testing::*};
+
+    #[test]
+    fn progress_is_computed_per_category_and_period_from_the_answers() {
+        use chrono::{NaiveDate, TimeZone, Utc};
+
+        let (pool, path) = temp_db();
+        let forms = vec![
+            model::TestForm {
+                category: Some("Signs".to_owned()),
+                ..test_form(Some("s1"), "Sign 1", 1)
+            },
+            model::TestForm {
+                category: Some("Signs".to_owned()),
+                ..test_form(Some("s2"), "Sign 2", 1)
+            },
+            model::TestForm {
+                category: Some("Markings".to_owned()),
+                ..test_form(Some("m1"), "Marking 1", 1)
+            },
+            test_form(Some("o1"), "Other 1", 1),
+        ];
+        import(forms, &pool, false).unwrap();
+        let ids = get_tests(&pool)
+            .unwrap()
+            .into_iter()
+            .map(|test| (test.key.unwrap(), test.id))
+            .collect::<std::collections::HashMap<_, _>>();
+        let user_id = new_user(&pool, "ann").id;
+
+        let now = Utc.with_ymd_and_hms(2026, 10, 14, 12, 0, 0).unwrap();
+        let last_week = Utc.with_ymd_and_hms(2026, 10, 6, 12, 0, 0).unwrap();
+        let mut answers = vec![answer_at(user_id, ids["m1"], 5, last_week)];
+        for _ in 0..3 {
+            answers.push(answer_at(user_id, ids["s1"], 5, last_week));
+            answers.push(answer_at(user_id, ids["s1"], 0, now));
+        }
+        for answer in answers.iter() {
+            record_answer(answer, &pool).unwrap();
+        }
+
+        let weekly = progress(&pool, user_id, TrendPeriod::Week, now).unwrap();
+        let daily = progress(&pool, user_id, TrendPeriod::Day, now).unwrap();
+
+        remove_temp_db(pool, path);
+        assert_eq!(
+            (weekly.total, weekly.seen, weekly.answers, weekly.correct),
+            (4, 2, 7, 4)
+        );
+        assert_eq!(
+            weekly.categories,
+            vec![
+                CategoryProgress {
+                    category: None,
+                    total: 1,
+                    ..CategoryProgress::default()
+                },
+                CategoryProgress {
+                    category: Some("Markings".to_owned()),
+                    total: 1,
+                    seen: 1,
+                    answers: 1,
+                    correct: 1,
+                    accuracy: Some(1.0)
+                },
+                CategoryProgress {
+                    category: Some("Signs".to_owned()),
+                    total: 2,
+                    seen: 1,
+                    answers: 6,
+                    correct: 3,
+                    accuracy: Some(0.5)
+                },
+            ]
+        );
+        assert_eq!(weekly.weakest, vec![weekly.categories[2].clone()]);
+        assert_eq!(weekly.trend.len(), 12);
+        assert_eq!(
+            weekly.trend[10..],
+            [
+                TrendPoint {
+                    start: NaiveDate::from_ymd_opt(2026, 10, 5).unwrap(),
+                    answers: 4,
+                    correct: 4,
+                    accuracy: Some(1.0)
+                },
+                TrendPoint {
+                    start: NaiveDate::from_ymd_opt(2026, 10, 12).unwrap(),
+                    answers: 3,
+                    correct: 0,
+                    accuracy: Some(0.0)
+                },
+            ]
+        );
+        assert_eq!(daily.trend.last().unwrap().start, now.date_naive());
+        assert_eq!(daily.trend.iter().map(|point| point.answers).sum::<usize>(), 7);
+    }
+}
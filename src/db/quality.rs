@@ -0,0 +1,268 @@
+use diesel::{QueryDsl, RunQueryDsl};
+use serde::Serialize;
+use std::{collections::HashMap, ops::Deref};
+
+use super::{
+    model::Test,
+    schema::{tests, user_answers},
+    DbPool,
+};
+
+/// Share of the answerers in the upper and the lower groups of the discrimination index
+const GROUP_SHARE: f64 = 0.27;
+/// Answers a test needs before its answerers are split into the groups
+const MIN_GROUPED_ANSWERS: usize = 10;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityFlag {
+    /// The strong learners choose a wrong answer more often than the right one
+    PossibleMiskey,
+    /// The weak learners answer the test right more often than the strong ones
+    NegativeDiscrimination,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct AnswerFrequency {
+    /// 1-based like `right_answer_id`
+    pub answer_id: i32,
+    pub answer: String,
+    pub right: bool,
+    pub count: usize,
+    pub share: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct QuestionQuality {
+    pub test_id: i32,
+    pub key: Option<String>,
+    pub description: String,
+    pub category: Option<String>,
+    pub retired: bool,
+    pub answers: usize,
+    /// Share of the users who answered any test that answered this one
+    pub answer_rate: Option<f64>,
+    /// Share of the right answers
+    pub p_value: Option<f64>,
+    /// Right answers share of the strongest 27% of the answerers minus the one of the weakest 27%
+    pub discrimination: Option<f64>,
+    pub frequencies: Vec<AnswerFrequency>,
+    pub average_response_ms: Option<f64>,
+    /// The wrong answer the strong learners prefer to the right one
+    pub suspected_answer_id: Option<i32>,
+    pub flags: Vec<QualityFlag>,
+}
+
+#[derive(Clone, Copy)]
+struct FirstAnswer {
+    user_id: i32,
+    answer_id: i32,
+    correct: bool,
+    response_ms: Option<i32>,
+}
+
+fn share(count: usize, total: usize) -> Option<f64> {
+    match total {
+        0 => None,
+        total => Some(count as f64 / total as f64),
+    }
+}
+
+/// Classical item analysis of every test over the first answer of every user to it,
+/// the strength of a user is their share of the right answers
+pub fn question_quality(pool: &DbPool) -> anyhow::Result<Vec<QuestionQuality>> {
+    let db = pool.get().unwrap();
+
+    let tests = tests::table
+        .order(tests::id)
+        .load::<Test>(db.deref())
+        .map_err(|err| anyhow!("Failed to load tests - {}", err))?;
+    let answers = user_answers::table
+        .select((
+            user_answers::user_id,
+            user_answers::test_id,
+            user_answers::answer_id,
+            user_answers::correct,
+            user_answers::response_ms,
+        ))
+        .order((user_answers::answered_at, user_answers::id))
+        .load::<(i32, i32, i32, bool, Option<i32>)>(db.deref())
+        .map_err(|err| anyhow!("Failed to load the answers - {}", err))?;
+
+    let mut first_answers = HashMap::new();
+    for (user_id, test_id, answer_id, correct, response_ms) in answers {
+        first_answers.entry((user_id, test_id)).or_insert(FirstAnswer {
+            user_id,
+            answer_id,
+            correct,
+            response_ms,
+        });
+    }
+    let mut strength = HashMap::<i32, (usize, usize)>::new();
+    let mut by_test = HashMap::<i32, Vec<FirstAnswer>>::new();
+    for ((user_id, test_id), answer) in first_answers.iter() {
+        let (right, total) = strength.entry(*user_id).or_default();
+        *right += answer.correct as usize;
+        *total += 1;
+        by_test.entry(*test_id).or_default().push(*answer);
+    }
+    let strength = strength
+        .into_iter()
+        .map(|(user_id, (right, total))| (user_id, right as f64 / total as f64))
+        .collect::<HashMap<_, _>>();
+
+    tests
+        .into_iter()
+        .map(|test| {
+            let options = serde_json::from_str::<Vec<String>>(&test.answers)
+                .map_err(|err| anyhow!("Failed to parse answers of {} test - {}", test.id, err))?;
+            let mut answers = by_test.remove(&test.id).unwrap_or_default();
+            // The weakest answerers first, ties in a stable order
+            answers.sort_by(|a, b| {
+                strength[&a.user_id]
+                    .partial_cmp(&strength[&b.user_id])
+                    .unwrap()
+                    .then(a.user_id.cmp(&b.user_id))
+            });
+
+            // Counted against the current key, so the answers recorded before the test was re-keyed count too
+            let count_of = |answers: &[FirstAnswer], answer_id: i32| {
+                answers.iter().filter(|answer| answer.answer_id == answer_id).count()
+            };
+            let right = count_of(&answers, test.right_answer_id);
+            let frequencies = (1..=options.len() as i32)
+                .zip(options)
+                .map(|(answer_id, answer)| AnswerFrequency {
+                    answer_id,
+                    answer,
+                    right: answer_id == test.right_answer_id,
+                    count: count_of(&answers, answer_id),
+                    share: share(count_of(&answers, answer_id), answers.len()),
+                })
+                .collect::<Vec<_>>();
+
+            let (discrimination, suspected_answer_id) = match answers.len() >= MIN_GROUPED_ANSWERS {
+                true => {
+                    let group = (answers.len() as f64 * GROUP_SHARE).ceil() as usize;
+                    let (lower, upper) = (&answers[..group], &answers[answers.len() - group..]);
+                    let right_share = |group: &[_]| count_of(group, test.right_answer_id) as f64 / group.len() as f64;
+                    let suspected = frequencies
+                        .iter()
+                        .filter(|frequency| !frequency.right)
+                        .map(|frequency| (frequency.answer_id, count_of(upper, frequency.answer_id)))
+                        .filter(|(_, count)| *count > count_of(upper, test.right_answer_id))
+                        .max_by_key(|(_, count)| *count)
+                        .map(|(answer_id, _)| answer_id);
+                    (Some(right_share(upper) - right_share(lower)), suspected)
+                }
+                false => (None, None),
+            };
+            let mut flags = Vec::new();
+            if suspected_answer_id.is_some() {
+                flags.push(QualityFlag::PossibleMiskey);
+            }
+            if discrimination.is_some_and(|discrimination| discrimination < 0.0) {
+                flags.push(QualityFlag::NegativeDiscrimination);
+            }
+
+            let response_times = answers
+                .iter()
+                .filter_map(|answer| answer.response_ms)
+                .collect::<Vec<_>>();
+            let average_response_ms = match response_times.len() {
+                0 => None,
+                count => Some(response_times.iter().map(|ms| *ms as f64).sum::<f64>() / count as f64),
+            };
+
+            Ok(QuestionQuality {
+                test_id: test.id,
+                key: test.key,
+                description: test.description,
+                category: test.category,
+                retired: test.retired,
+                answers: answers.len(),
+                answer_rate: share(answers.len(), strength.len()),
+                p_value: share(right, answers.len()),
+                discrimination,
+                frequencies,
+                average_response_ms,
+                suspected_answer_id,
+                flags,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod _tests {
+    use super::*;
+    use crate::db::{get_tests, model, record_answer, testing::*};
+
+    #[test]
+    fn question_quality_flags_a_test_the_strong_learners_answer_otherwise() {
+        let (pool, path) = temp_db();
+        let forms = vec![
+            test_form(Some("a"), "First anchor", 1),
+            test_form(Some("b"), "Second anchor", 1),
+            test_form(Some("miskeyed"), "Mis-keyed", 1),
+        ];
+        import(forms, &pool, false).unwrap();
+        let ids = get_tests(&pool).unwrap().iter().map(|test| test.id).collect::<Vec<_>>();
+        for learner in 0..12 {
+            let user_id = new_user(&pool, &format!("learner{}", learner)).id;
+            // The first half knows the anchors and picks the second answer of the mis-keyed test
+            let strong = learner < 6;
+            let anchor_answer = if strong { 1 } else { 2 };
+            for test_id in &ids[..2] {
+                let answer = model::NewUserAnswer::new(user_id, *test_id, anchor_answer, strong, 0);
+                record_answer(&answer, &pool).unwrap();
+            }
+            let answer = model::NewUserAnswer {
+                response_ms: Some(if strong { 1000 } else { 3000 }),
+                ..model::NewUserAnswer::new(user_id, ids[2], 3 - anchor_answer, !strong, 0)
+            };
+            record_answer(&answer, &pool).unwrap();
+            // Only the first answer counts
+            record_answer(&model::NewUserAnswer::new(user_id, ids[2], 1, true, 0), &pool).unwrap();
+        }
+
+        let report = question_quality(&pool).unwrap();
+
+        remove_temp_db(pool, path);
+        assert_eq!(report.len(), 3);
+        let (anchor, miskeyed) = (&report[0], &report[2]);
+        assert_eq!(anchor.answers, 12);
+        assert_eq!(anchor.answer_rate, Some(1.0));
+        assert_eq!(anchor.p_value, Some(0.5));
+        assert_eq!(anchor.discrimination, Some(1.0));
+        assert_eq!(anchor.flags, vec![]);
+        assert_eq!(anchor.average_response_ms, None);
+        assert_eq!(miskeyed.p_value, Some(0.5));
+        assert_eq!(miskeyed.discrimination, Some(-1.0));
+        assert_eq!(miskeyed.suspected_answer_id, Some(2));
+        assert_eq!(
+            miskeyed.flags,
+            vec![QualityFlag::PossibleMiskey, QualityFlag::NegativeDiscrimination]
+        );
+        assert_eq!(miskeyed.average_response_ms, Some(2000.0));
+        assert_eq!(
+            miskeyed.frequencies,
+            vec![
+                AnswerFrequency {
+                    answer_id: 1,
+                    answer: "Yes".to_owned(),
+                    right: true,
+                    count: 6,
+                    share: Some(0.5),
+                },
+                AnswerFrequency {
+                    answer_id: 2,
+                    answer: "No".to_owned(),
+                    right: false,
+                    count: 6,
+                    share: Some(0.5),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use diesel::{
+    dsl::sql, replace_into, sql_types::Integer, BoolExpressionMethods, ExpressionMethods, OptionalExtension, QueryDsl,
+    RunQueryDsl, SqliteConnection,
+};
+use std::ops::Deref;
+
+use super::{
+    model::{NewUserAnswer, ReviewState},
+    schema::{review_states, tests},
+    DbPool,
+};
+
+const INITIAL_EASE_FACTOR: f64 = 2.5;
+const MIN_EASE_FACTOR: f64 = 1.3;
+const SECONDS_IN_DAY: i64 = 24 * 60 * 60;
+/// SM-2 grades the recall from 0 to 5, an answer is only right or wrong here
+const RIGHT_ANSWER_QUALITY: f64 = 4.0;
+const WRONG_ANSWER_QUALITY: f64 = 1.0;
+
+impl ReviewState {
+    pub fn new(user_id: i32, test_id: i32) -> Self {
+        ReviewState {
+            user_id,
+            test_id,
+            repetitions: 0,
+            ease_factor: INITIAL_EASE_FACTOR,
+            interval_days: 0,
+            due_at: 0,
+        }
+    }
+
+    /// The SM-2 schedule after an answer: the interval grows by the ease factor with every right answer in a row
+    /// and a wrong answer starts the test over from a day
+    pub fn after_answer(&self, correct: bool, answered_at: i64) -> Self {
+        let quality = match correct {
+            true => RIGHT_ANSWER_QUALITY,
+            false => WRONG_ANSWER_QUALITY,
+        };
+        let (repetitions, interval_days) = match (correct, self.repetitions) {
+            (false, _) => (0, 1),
+            (true, 0) => (1, 1),
+            (true, 1) => (2, 6),
+            (true, repetitions) => (
+                repetitions + 1,
+                (self.interval_days as f64 * self.ease_factor).round() as i32,
+            ),
+        };
+        let ease_factor = self.ease_factor + 0.1 - (5.0 - quality) * (0.08 + (5.0 - quality) * 0.02);
+
+        ReviewState {
+            repetitions,
+            ease_factor: ease_factor.max(MIN_EASE_FACTOR),
+            interval_days,
+            due_at: answered_at + interval_days as i64 * SECONDS_IN_DAY,
+            ..self.clone()
+        }
+    }
+}
+
+/// Moves the test of the answer along the schedule of the user, it's called in the transaction recording the answer
+pub(super) fn schedule(db: &SqliteConnection, answer: &NewUserAnswer) -> diesel::QueryResult<()> {
+    let state = review_states::table
+        .find((answer.user_id, answer.test_id))
+        .first::<ReviewState>(db)
+        .optional()?
+        .unwrap_or_else(|| ReviewState::new(answer.user_id, answer.test_id));
+
+    replace_into(review_states::table)
+        .values(&state.after_answer(answer.correct, answer.answered_at))
+        .execute(db)?;
+
+    Ok(())
+}
+
+pub struct ReviewPick {
+    pub test_id: i32,
+    /// `None` for a test the user hasn't seen yet
+    pub due_at: Option<i64>,
+}
+
+/// The served test most due for review: the most overdue one, an unseen one when nothing is due
+/// and the one due the soonest when the user has seen every test
+pub fn review_test(pool: &DbPool, user_id: i32, now: i64) -> anyhow::Result<Option<ReviewPick>> {
+    let db = pool.get().unwrap();
+    let scheduled = || {
+        review_states::table
+            .inner_join(tests::table)
+            .filter(review_states::user_id.eq(user_id).and(tests::retired.eq(false)))
+            .select((review_states::test_id, review_states::due_at))
+            .order((review_states::due_at, review_states::test_id))
+    };
+
+    let due = scheduled()
+        .filter(review_states::due_at.le(now))
+        .first::<(i32, i64)>(db.deref())
+        .optional()
+        .map_err(|err| anyhow!("Failed to find the due tests of {} user - {}", user_id, err))?;
+    if let Some((test_id, due_at)) = due {
+        return Ok(Some(ReviewPick {
+            test_id,
+            due_at: Some(due_at),
+        }));
+    }
+
+    let seen = review_states::table
+        .filter(review_states::user_id.eq(user_id))
+        .select(review_states::test_id);
+    let unseen = tests::table
+        .filter(tests::retired.eq(false).and(tests::id.ne_all(seen)))
+        .select(tests::id)
+        .order(sql::<Integer>("RANDOM()"))
+        .first::<i32>(db.deref())
+        .optional()
+        .map_err(|err| anyhow!("Failed to find the unseen tests of {} user - {}", user_id, err))?;
+    if let Some(test_id) = unseen {
+        return Ok(Some(ReviewPick { test_id, due_at: None }));
+    }
+
+    scheduled()
+        .first::<(i32, i64)>(db.deref())
+        .optional()
+        .map(|next| {
+            next.map(|(test_id, due_at)| ReviewPick {
+                test_id,
+                due_at: Some(due_at),
+            })
+        })
+        .map_err(|err| anyhow!("Failed to find the scheduled tests of {} user - {}", user_id, err))
+}
+
+#[cfg(test)]
+mod _tests {
+    use super::*;
+    use crate::db::{get_tests, model, record_answer, testing::*};
+
+    #[test]
+    fn review_schedule_follows_sm2() {
+        let day = 24 * 60 * 60;
+        let mut state = model::ReviewState::new(1, 1);
+        let mut intervals = Vec::new();
+        for (answer, correct) in [true, true, true, false, true].iter().enumerate() {
+            state = state.after_answer(*correct, answer as i64 * day);
+            intervals.push(state.interval_days);
+        }
+
+        assert_eq!(intervals, vec![1, 6, 15, 1, 1]);
+        assert_eq!(state.repetitions, 1);
+        assert_eq!(state.due_at, 5 * day);
+        assert!((state.ease_factor - 1.96).abs() < 1e-9);
+        for _ in 0..10 {
+            state = state.after_answer(false, 0);
+        }
+        assert_eq!(state.ease_factor, 1.3);
+    }
+
+    #[test]
+    fn review_test_serves_due_then_unseen_then_the_soonest_tests() {
+        let (pool, path) = temp_db();
+        let forms = vec![test_form(Some("q1"), "First", 1), test_form(Some("q2"), "Second", 1)];
+        import(forms, &pool, false).unwrap();
+        let ids = get_tests(&pool).unwrap().iter().map(|test| test.id).collect::<Vec<_>>();
+        let user_id = new_user(&pool, "ann").id;
+        let day = 24 * 60 * 60;
+        let pick = |now| {
+            review_test(&pool, user_id, now)
+                .unwrap()
+                .map(|pick| (pick.test_id, pick.due_at))
+        };
+
+        let answer = |test_id, correct, answered_at| model::NewUserAnswer {
+            answered_at,
+            ..model::NewUserAnswer::new(user_id, test_id, 1, correct, 0)
+        };
+        record_answer(&answer(ids[0], true, 0), &pool).unwrap();
+        let unseen = pick(day / 2);
+        let due = pick(2 * day);
+        record_answer(&answer(ids[1], false, 0), &pool).unwrap();
+        record_answer(&answer(ids[0], true, day), &pool).unwrap();
+        let soonest = pick(day / 2);
+        let most_overdue = pick(10 * day);
+
+        remove_temp_db(pool, path);
+        assert_eq!(unseen, Some((ids[1], None)));
+        assert_eq!(due, Some((ids[0], Some(day))));
+        assert_eq!(soonest, Some((ids[1], Some(day))));
+        assert_eq!(most_overdue, Some((ids[1], Some(day))));
+    }
+}
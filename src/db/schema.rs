@@ -1,10 +1,161 @@
+table! {
+    bookmarks (user_id, test_id) {
+        user_id -> Integer,
+        test_id -> Integer,
+        created_at -> BigInt,
+    }
+}
+
+table! {
+    collection_tests (collection_id, test_id) {
+        collection_id -> Integer,
+        user_id -> Integer,
+        test_id -> Integer,
+    }
+}
+
+table! {
+    collections (id) {
+        id -> Integer,
+        user_id -> Integer,
+        name -> Text,
+        created_at -> BigInt,
+    }
+}
+
+table! {
+    daily_answers (user_id, day, test_id) {
+        user_id -> Integer,
+        day -> Text,
+        test_id -> Integer,
+        answer_id -> Integer,
+        correct -> Bool,
+    }
+}
+
+table! {
+    daily_attempts (user_id, day) {
+        user_id -> Integer,
+        day -> Text,
+        questions -> Integer,
+        answers -> Integer,
+        correct -> Integer,
+        finished_at -> Nullable<BigInt>,
+        starts_at -> BigInt,
+        ends_at -> BigInt,
+    }
+}
+
+table! {
+    daily_sets (day, position) {
+        day -> Text,
+        position -> Integer,
+        test_id -> Integer,
+    }
+}
+
+table! {
+    exams (id) {
+        id -> Integer,
+        user_id -> Integer,
+        source -> Text,
+        questions -> Integer,
+        correct -> Integer,
+        finished_at -> BigInt,
+    }
+}
+
+table! {
+    images (hash) {
+        hash -> Text,
+        content_type -> Text,
+        data -> Binary,
+    }
+}
+
+table! {
+    lti_users (id) {
+        id -> Integer,
+        issuer -> Text,
+        subject -> Text,
+        user_id -> Integer,
+    }
+}
+
+table! {
+    marathons (user_id) {
+        user_id -> Integer,
+        category -> Nullable<Text>,
+        last_test_id -> Integer,
+        answers -> Integer,
+        correct -> Integer,
+        started_at -> BigInt,
+    }
+}
+
+table! {
+    review_states (user_id, test_id) {
+        user_id -> Integer,
+        test_id -> Integer,
+        repetitions -> Integer,
+        ease_factor -> Double,
+        interval_days -> Integer,
+        due_at -> BigInt,
+    }
+}
+
+table! {
+    test_calibrations (test_id) {
+        test_id -> Integer,
+        difficulty -> Double,
+        discrimination -> Double,
+        answers -> Integer,
+        calibrated_at -> BigInt,
+    }
+}
+
 table! {
     tests (id) {
         id -> Integer,
         description -> Text,
         answers -> Text,
         right_answer_id -> Integer,
-        image -> Nullable<Binary>,
+        image_hash -> Nullable<Text>,
+        thumbnail_hash -> Nullable<Text>,
+        retina_hash -> Nullable<Text>,
+        key -> Nullable<Text>,
+        retired -> Bool,
+        category -> Nullable<Text>,
+        explanation -> Nullable<Text>,
+    }
+}
+
+table! {
+    user_achievements (user_id, achievement) {
+        user_id -> Integer,
+        achievement -> Text,
+        earned_at -> BigInt,
+        seen -> Bool,
+    }
+}
+
+table! {
+    user_answers (id) {
+        id -> Integer,
+        user_id -> Integer,
+        test_id -> Integer,
+        answer_id -> Integer,
+        correct -> Bool,
+        scores -> Integer,
+        answered_at -> BigInt,
+        response_ms -> Nullable<Integer>,
+    }
+}
+
+table! {
+    user_time_zones (user_id) {
+        user_id -> Integer,
+        time_zone -> Text,
     }
 }
 
@@ -15,7 +166,46 @@ table! {
         second_name -> Text,
         password -> Text,
         scores -> Integer,
+        display_name -> Nullable<Text>,
+        leaderboard_hidden -> Bool,
     }
 }
 
-allow_tables_to_appear_in_same_query!(tests, users,);
+joinable!(bookmarks -> tests (test_id));
+joinable!(bookmarks -> users (user_id));
+joinable!(collection_tests -> collections (collection_id));
+joinable!(collection_tests -> tests (test_id));
+joinable!(collections -> users (user_id));
+joinable!(daily_answers -> tests (test_id));
+joinable!(daily_attempts -> users (user_id));
+joinable!(daily_sets -> tests (test_id));
+joinable!(exams -> users (user_id));
+joinable!(lti_users -> users (user_id));
+joinable!(marathons -> users (user_id));
+joinable!(review_states -> tests (test_id));
+joinable!(review_states -> users (user_id));
+joinable!(test_calibrations -> tests (test_id));
+joinable!(user_achievements -> users (user_id));
+joinable!(user_answers -> tests (test_id));
+joinable!(user_answers -> users (user_id));
+joinable!(user_time_zones -> users (user_id));
+
+allow_tables_to_appear_in_same_query!(
+    bookmarks,
+    collection_tests,
+    collections,
+    daily_answers,
+    daily_attempts,
+    daily_sets,
+    exams,
+    images,
+    lti_users,
+    marathons,
+    review_states,
+    test_calibrations,
+    tests,
+    user_achievements,
+    user_answers,
+    user_time_zones,
+    users,
+);
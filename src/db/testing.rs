@@ -0,0 +1,123 @@
+//! Fixtures shared by the tests of the DB and of the formats, every test works on a DB file of its own
+
+use actix_web::web::Data;
+use std::{env, ops::Deref, path::Path};
+use uuid::Uuid;
+
+use super::{
+    establish_connection_to, get_user, import_tests,
+    model::{self, UserForm},
+    registry_new_user, DbPool, ExportOptions, ImportOptions, ImportReport, ImportSummary,
+};
+use crate::{
+    cache::QuestionCache,
+    formats::ExportFormat,
+    image_pipeline::{ImagePipeline, ImagePipelineConfig},
+};
+
+pub const PASSWORD: &str = "password";
+
+pub fn temp_db() -> (DbPool, String) {
+    let path = env::temp_dir()
+        .join(format!("{}.db", Uuid::new_v4()))
+        .to_string_lossy()
+        .into_owned();
+    (establish_connection_to(&path), path)
+}
+
+pub fn remove_temp_db(pool: DbPool, path: String) {
+    drop(pool);
+    for suffix in &["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+    }
+}
+
+pub fn test_form(key: Option<&str>, description: &str, right_answer_id: i32) -> model::TestForm {
+    model::TestForm {
+        key: key.map(str::to_owned),
+        description: description.to_owned(),
+        answers: vec!["Yes".to_owned(), "No".to_owned()],
+        right_answer_id,
+        image_path: None,
+        image_data: None,
+        category: None,
+        explanation: None,
+    }
+}
+
+pub fn import_with(forms: Vec<model::TestForm>, pool: &DbPool, options: ImportOptions) -> anyhow::Result<ImportReport> {
+    let pipeline = ImagePipeline::new(ImagePipelineConfig::default());
+    import_tests(forms, Path::new(""), pool, &QuestionCache::new(), &pipeline, options)
+}
+
+pub fn import(forms: Vec<model::TestForm>, pool: &DbPool, retire_missing: bool) -> anyhow::Result<ImportSummary> {
+    let options = ImportOptions {
+        retire_missing,
+        ..ImportOptions::default()
+    };
+    import_with(forms, pool, options).map(|report| report.summary)
+}
+
+pub fn format_options(format: ExportFormat) -> ExportOptions {
+    ExportOptions {
+        format,
+        ..ExportOptions::default()
+    }
+}
+
+/// Imports a test with an image and a keyless one, the image is written to `dir`
+pub fn import_bank_with_image(pool: &DbPool, dir: &Path) {
+    let mut image = Vec::new();
+    ::image::DynamicImage::new_rgb8(40, 30)
+        .write_to(&mut image, ::image::ImageOutputFormat::Png)
+        .unwrap();
+    std::fs::write(dir.join("sign.png"), image).unwrap();
+
+    let mut with_image = test_form(Some("q1"), "First", 1);
+    with_image.image_path = Some("sign.png".to_owned());
+    with_image.explanation = Some("Because".to_owned());
+    import_tests(
+        vec![with_image, test_form(None, "Second", 2)],
+        dir,
+        pool,
+        &QuestionCache::new(),
+        &ImagePipeline::new(ImagePipelineConfig::default()),
+        ImportOptions::default(),
+    )
+    .unwrap();
+}
+
+pub fn answer_at(
+    user_id: i32,
+    test_id: i32,
+    scores: i32,
+    answered_at: chrono::DateTime<chrono::Utc>,
+) -> model::NewUserAnswer {
+    model::NewUserAnswer {
+        answered_at: answered_at.timestamp(),
+        ..model::NewUserAnswer::new(user_id, test_id, 1, scores > 0, scores)
+    }
+}
+
+pub fn users_form(name: &str) -> UserForm {
+    UserForm {
+        name: name.to_owned(),
+        second_name: "Lee".to_owned(),
+        password: PASSWORD.to_owned(),
+    }
+}
+
+/// Registers a user with the name from `users_form`
+pub fn new_user(pool: &DbPool, name: &str) -> model::User {
+    let user = users_form(name);
+    registry_new_user(user.clone(), Data::new(pool.clone())).unwrap();
+    get_user(&user, pool).unwrap()
+}
+
+/// Reverts the latest migrations of the DB one by one, `establish_connection_to` runs them again
+pub fn revert_migrations(pool: &DbPool, count: usize) {
+    let db = pool.get().unwrap();
+    for _ in 0..count {
+        diesel_migrations::revert_latest_migration(db.deref()).unwrap();
+    }
+}
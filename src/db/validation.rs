@@ -0,0 +1,355 @@
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::Path,
+};
+
+use super::model::TestForm;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The test can't be imported
+    Error,
+    /// The test is imported but probably isn't what its author meant
+    Warning,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Issue {
+    /// Index of the test in the imported array
+    pub test: usize,
+    pub severity: Severity,
+    /// JSON pointer to the invalid value in the imported file, `None` for the sheets
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pointer: Option<String>,
+    /// 1-based row of the sheet, the header row included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub row: Option<usize>,
+    /// The column of the sheet as its header names it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub column: Option<String>,
+    pub message: String,
+}
+
+/// Where the tests of a CSV file or a spreadsheet come from, so the issues point to the cells
+#[derive(Default, Debug, PartialEq)]
+pub struct SheetSource {
+    /// 1-based row of every test, the header row included
+    pub rows: Vec<usize>,
+    /// The header of the column of every field, a numbered answer column is `answers/{index}`
+    pub columns: HashMap<String, String>,
+    /// Tests whose correct answer cell is the number of an answer and the text of another one, both from 1
+    pub ambiguous_right_answers: HashMap<usize, (i32, i32)>,
+}
+
+impl SheetSource {
+    fn column(&self, field: &str) -> Option<String> {
+        self.columns
+            .get(field)
+            // A single column holds every answer
+            .or_else(|| self.columns.get(field.split('/').next()?))
+            .cloned()
+    }
+}
+
+#[derive(Serialize, Default, Debug, PartialEq)]
+pub struct ValidationReport {
+    pub tests: usize,
+    pub issues: Vec<Issue>,
+    #[serde(skip)]
+    source: Option<SheetSource>,
+}
+
+impl ValidationReport {
+    pub fn error(&mut self, test: usize, field: &str, message: String) {
+        self.push(test, Severity::Error, field, message);
+    }
+
+    pub fn warning(&mut self, test: usize, field: &str, message: String) {
+        self.push(test, Severity::Warning, field, message);
+    }
+
+    fn push(&mut self, test: usize, severity: Severity, field: &str, message: String) {
+        let (pointer, row, column) = match self.source.as_ref() {
+            Some(source) => (None, source.rows.get(test).copied(), source.column(field)),
+            None => (Some(format!("/{}/{}", test, field)), None, None),
+        };
+        self.issues.push(Issue {
+            test,
+            severity,
+            pointer,
+            row,
+            column,
+            message,
+        });
+    }
+
+    /// How the messages refer to another test
+    fn test_location(&self, test: usize) -> String {
+        match self.source.as_ref().and_then(|source| source.rows.get(test)) {
+            Some(row) => format!("row {}", row),
+            None => format!("/{}", test),
+        }
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Issue> {
+        self.issues.iter().filter(|issue| issue.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Issue> {
+        self.issues.iter().filter(|issue| issue.severity == Severity::Warning)
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+
+    /// Indexes of the tests with at least one error
+    pub fn invalid_tests(&self) -> HashSet<usize> {
+        self.errors().map(|issue| issue.test).collect()
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        match (self.pointer.as_ref(), self.row, self.column.as_ref()) {
+            (Some(pointer), _, _) => write!(f, "{} at {}: {}", severity, pointer, self.message),
+            (None, Some(row), Some(column)) => {
+                write!(f, "{} at row {}, column `{}`: {}", severity, row, column, self.message)
+            }
+            (None, Some(row), None) => write!(f, "{} at row {}: {}", severity, row, self.message),
+            (None, None, _) => write!(f, "{} at test {}: {}", severity, self.test, self.message),
+        }
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in self.issues.iter() {
+            writeln!(f, "{}", issue)?;
+        }
+
+        write!(
+            f,
+            "{} tests checked: {} invalid, {} errors, {} warnings",
+            self.tests,
+            self.invalid_tests().len(),
+            self.errors().count(),
+            self.warnings().count()
+        )
+    }
+}
+
+/// Checks the tests before anything is written to the DB, relative image paths are resolved against `base_dir`.
+/// Images are only checked to exist here, broken ones are reported when they are processed.
+/// The issues of the tests read from a sheet point to its rows and columns instead of the JSON pointers
+pub fn validate_tests(test_forms: &[TestForm], source: Option<SheetSource>, base_dir: &Path) -> ValidationReport {
+    let mut report = ValidationReport {
+        tests: test_forms.len(),
+        source,
+        ..ValidationReport::default()
+    };
+    let mut keys = HashMap::new();
+    let mut descriptions = HashMap::new();
+
+    for (index, test_form) in test_forms.iter().enumerate() {
+        if let Some(key) = test_form.key.as_ref() {
+            if key.trim().is_empty() {
+                report.error(index, "key", "the key is empty".to_owned());
+            } else if let Some(first) = first_index(&mut keys, key.as_str(), index) {
+                let message = format!("{} key is already used by {}", key, report.test_location(first));
+                report.error(index, "key", message);
+            }
+        }
+
+        if test_form.description.trim().is_empty() {
+            report.error(index, "description", "the description is empty".to_owned());
+        } else if test_form.key.is_none() {
+            // Tests without a key are matched by their description on the next import
+            if let Some(first) = first_index(&mut descriptions, test_form.description.as_str(), index) {
+                let message = format!(
+                    "{} has the same description, give both tests a key",
+                    report.test_location(first)
+                );
+                report.warning(index, "description", message);
+            }
+        }
+
+        validate_answers(&mut report, index, test_form);
+
+        if let Some(image_path) = test_form.image_path.as_ref() {
+            let resolved = base_dir.join(image_path);
+            if !resolved.is_file() {
+                report.error(index, "image_path", format!("{} isn't a file", resolved.display()));
+            }
+        }
+    }
+
+    report
+}
+
+fn validate_answers(report: &mut ValidationReport, index: usize, test_form: &TestForm) {
+    let answers = &test_form.answers;
+    if answers.is_empty() {
+        report.error(index, "answers", "there are no answers".to_owned());
+        return;
+    }
+    if answers.len() == 1 {
+        report.warning(index, "answers", "there is only one answer".to_owned());
+    }
+
+    let mut seen = HashMap::new();
+    for (answer_index, answer) in answers.iter().enumerate() {
+        let field = format!("answers/{}", answer_index);
+        if answer.trim().is_empty() {
+            report.error(index, &field, "the answer is empty".to_owned());
+        } else if let Some(first) = first_index(&mut seen, answer.trim(), answer_index) {
+            // The answers are numbered from 1 like `right_answer_id`
+            report.error(index, &field, format!("the answer repeats the answer {}", first + 1));
+        }
+    }
+
+    let ambiguous = report
+        .source
+        .as_ref()
+        .and_then(|source| source.ambiguous_right_answers.get(&index).copied());
+    if let Some((number, text)) = ambiguous {
+        let message = format!(
+            "{} is the number of the answer {} and the text of the answer {}",
+            answers[text as usize - 1],
+            number,
+            text
+        );
+        report.error(index, "right_answer_id", message);
+    }
+
+    // `right_answer_id` starts from 1
+    if test_form.right_answer_id < 1 || test_form.right_answer_id as usize > answers.len() {
+        report.error(
+            index,
+            "right_answer_id",
+            format!(
+                "{} isn't within the {} answers, it starts from 1",
+                test_form.right_answer_id,
+                answers.len()
+            ),
+        );
+    }
+}
+
+/// Returns the index the value was first seen at or remembers `index` for it
+fn first_index<'a>(seen: &mut HashMap<&'a str, usize>, value: &'a str, index: usize) -> Option<usize> {
+    match seen.get(value) {
+        Some(&first) => Some(first),
+        None => {
+            seen.insert(value, index);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_form(answers: &[&str], right_answer_id: i32) -> TestForm {
+        TestForm {
+            key: None,
+            description: "Description".to_owned(),
+            answers: answers.iter().map(|answer| answer.to_string()).collect(),
+            right_answer_id,
+            image_path: None,
+            image_data: None,
+            category: None,
+            explanation: None,
+        }
+    }
+
+    fn pointers(report: &ValidationReport, severity: Severity) -> Vec<&str> {
+        report
+            .issues
+            .iter()
+            .filter(|issue| issue.severity == severity)
+            .filter_map(|issue| issue.pointer.as_deref())
+            .collect()
+    }
+
+    #[test]
+    fn validate_tests_points_to_the_invalid_answers() {
+        let report = validate_tests(
+            &[test_form(&["Yes", "No"], 3), test_form(&["Yes", "", "Yes"], 1)],
+            None,
+            Path::new(""),
+        );
+
+        assert_eq!(
+            pointers(&report, Severity::Error),
+            vec!["/0/right_answer_id", "/1/answers/1", "/1/answers/2"]
+        );
+        assert_eq!(pointers(&report, Severity::Warning), vec!["/1/description"]);
+        assert_eq!(report.invalid_tests(), vec![0, 1].into_iter().collect());
+    }
+
+    #[test]
+    fn validate_tests_resolves_images_against_the_base_dir() {
+        let mut test = test_form(&["Yes", "No"], 1);
+        test.image_path = Some("Cargo.toml".to_owned());
+
+        let from_crate_dir = validate_tests(std::slice::from_ref(&test), None, Path::new(env!("CARGO_MANIFEST_DIR")));
+        let from_src_dir = validate_tests(&[test], None, &Path::new(env!("CARGO_MANIFEST_DIR")).join("src"));
+
+        assert!(!from_crate_dir.has_errors());
+        assert_eq!(pointers(&from_src_dir, Severity::Error), vec!["/0/image_path"]);
+    }
+
+    #[test]
+    fn validate_tests_rejects_empty_and_single_answers_differently() {
+        let report = validate_tests(&[test_form(&[], 1), test_form(&["Yes"], 1)], None, Path::new(""));
+
+        assert_eq!(pointers(&report, Severity::Error), vec!["/0/answers"]);
+        assert_eq!(
+            pointers(&report, Severity::Warning),
+            vec!["/1/description", "/1/answers"]
+        );
+    }
+
+    #[test]
+    fn validate_tests_points_to_the_cells_of_a_sheet() {
+        let source = SheetSource {
+            rows: vec![2, 5],
+            columns: vec![
+                ("description".to_owned(), "Question".to_owned()),
+                ("answers".to_owned(), "Answers".to_owned()),
+            ]
+            .into_iter()
+            .collect(),
+            ..SheetSource::default()
+        };
+
+        let report = validate_tests(
+            &[test_form(&["Yes", "No"], 1), test_form(&["Yes", "Yes"], 1)],
+            Some(source),
+            Path::new(""),
+        );
+
+        let locations = report
+            .issues
+            .iter()
+            .map(|issue| (issue.pointer.as_deref(), issue.row, issue.column.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            locations,
+            vec![(None, Some(5), Some("Question")), (None, Some(5), Some("Answers"))]
+        );
+        assert_eq!(
+            report.issues[0].message,
+            "row 2 has the same description, give both tests a key"
+        );
+    }
+}
@@ -0,0 +1,403 @@
+//! Anki decks: an `.apkg` zip with the `collection.anki2` SQLite collection and the media files named by their index.
+//! Every test is a note of the "Drive test" note type with the question and the answers on the front of its card
+//! and the right answer with the explanation on the back
+use anyhow::Context;
+use diesel::{
+    connection::SimpleConnection,
+    sql_query,
+    sql_types::{BigInt, Text},
+    Connection, RunQueryDsl, SqliteConnection,
+};
+use openssl::sha;
+use serde_json::json;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{html::text_to_html, xml::escape};
+use crate::{
+    bundle::{BundleWriter, TempDir},
+    db::{images, model::Test, ExportBank, ExportSummary},
+};
+
+const COLLECTION_FILE: &str = "collection.anki2";
+const MEDIA_FILE: &str = "media";
+/// Ids of the note type and the deck never change, so importing a newer export updates the notes of the older one
+const MODEL_ID: i64 = 1_603_000_000_001;
+const DECK_ID: i64 = 1_603_000_000_002;
+const DECK_NAME: &str = "Drive tests";
+const FIELDS: &[&str] = &["Question", "Image", "Answers", "RightAnswer", "Explanation"];
+/// Anki separates the fields of a note with the unit separator
+const FIELD_SEPARATOR: &str = "\u{1f}";
+
+/// Schema 11 collection, the one every Anki version imports
+const SCHEMA: &str = "
+CREATE TABLE col (
+    id integer primary key, crt integer not null, mod integer not null, scm integer not null, ver integer not null,
+    dty integer not null, usn integer not null, ls integer not null, conf text not null, models text not null,
+    decks text not null, dconf text not null, tags text not null
+);
+CREATE TABLE notes (
+    id integer primary key, guid text not null, mid integer not null, mod integer not null, usn integer not null,
+    tags text not null, flds text not null, sfld integer not null, csum integer not null, flags integer not null,
+    data text not null
+);
+CREATE TABLE cards (
+    id integer primary key, nid integer not null, did integer not null, ord integer not null, mod integer not null,
+    usn integer not null, type integer not null, queue integer not null, due integer not null, ivl integer not null,
+    factor integer not null, reps integer not null, lapses integer not null, left integer not null,
+    odue integer not null, odid integer not null, flags integer not null, data text not null
+);
+CREATE TABLE revlog (
+    id integer primary key, cid integer not null, usn integer not null, ease integer not null, ivl integer not null,
+    lastIvl integer not null, factor integer not null, time integer not null, type integer not null
+);
+CREATE TABLE graves (usn integer not null, oid integer not null, type integer not null);
+CREATE INDEX ix_notes_usn on notes (usn);
+CREATE INDEX ix_cards_usn on cards (usn);
+CREATE INDEX ix_revlog_usn on revlog (usn);
+CREATE INDEX ix_cards_nid on cards (nid);
+CREATE INDEX ix_cards_sched on cards (did, queue, due);
+CREATE INDEX ix_revlog_cid on revlog (cid);
+CREATE INDEX ix_notes_csum on notes (csum);
+";
+
+const CSS: &str =
+    ".card { font-family: arial; font-size: 20px; text-align: left; color: black; background-color: white; }
+.right { font-weight: bold; }
+.explanation { margin-top: 1em; font-style: italic; }
+img { max-width: 100%; }";
+
+/// Writes the bank as an Anki deck, the images become its media
+pub fn write_deck(bank: &ExportBank, path: &Path) -> anyhow::Result<ExportSummary> {
+    let dir = TempDir::new("drive-tests-anki")?;
+    let collection_path = dir.path().join(COLLECTION_FILE);
+
+    let media = bank
+        .images
+        .values()
+        .map(|image| {
+            let file_name = format!("{}.{}", image.hash, images::extension_of(&image.content_type));
+            (image.hash.clone(), (file_name, image))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let collection_url = collection_path.to_string_lossy();
+    let db = SqliteConnection::establish(&collection_url)
+        .with_context(|| format!("failed to create {}", collection_path.display()))?;
+    db.transaction::<_, anyhow::Error, _>(|| {
+        write_collection(&db, bank, |test| {
+            media.get(test.image_hash.as_ref()?).map(|(name, _)| name.as_str())
+        })
+    })?;
+    drop(db);
+
+    let mut deck = BundleWriter::create_zip(path)?;
+    let collection =
+        fs::read(&collection_path).with_context(|| format!("failed to read {}", collection_path.display()))?;
+    deck.add_file(COLLECTION_FILE, &collection)?;
+
+    let mut media_names = BTreeMap::new();
+    for (index, (file_name, image)) in media.values().enumerate() {
+        deck.add_file(&index.to_string(), &image.data)?;
+        media_names.insert(index.to_string(), file_name.clone());
+    }
+    deck.add_file(MEDIA_FILE, serde_json::to_string(&media_names)?.as_bytes())?;
+    deck.finish()?;
+
+    Ok(ExportSummary {
+        tests: bank.tests.len(),
+        images: media.len(),
+    })
+}
+
+fn write_collection<'a>(
+    db: &SqliteConnection,
+    bank: &ExportBank,
+    image_name: impl Fn(&Test) -> Option<&'a str>,
+) -> anyhow::Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let (seconds, millis) = (now.as_secs() as i64, now.as_millis() as i64);
+
+    db.batch_execute(SCHEMA)?;
+    sql_query(
+        "INSERT INTO col (id, crt, mod, scm, ver, dty, usn, ls, conf, models, decks, dconf, tags) \
+         VALUES (1, ?, ?, ?, 11, 0, 0, 0, ?, ?, ?, ?, '{}')",
+    )
+    .bind::<BigInt, _>(seconds)
+    .bind::<BigInt, _>(millis)
+    .bind::<BigInt, _>(millis)
+    .bind::<Text, _>(conf().to_string())
+    .bind::<Text, _>(models(seconds).to_string())
+    .bind::<Text, _>(decks(seconds).to_string())
+    .bind::<Text, _>(deck_configs(seconds).to_string())
+    .execute(db)?;
+
+    for (index, test) in bank.tests.iter().enumerate() {
+        let id = millis + index as i64;
+        let fields = fields(test, image_name(test))?;
+
+        sql_query(
+            "INSERT INTO notes (id, guid, mid, mod, usn, tags, flds, sfld, csum, flags, data) \
+             VALUES (?, ?, ?, ?, -1, ?, ?, ?, ?, 0, '')",
+        )
+        .bind::<BigInt, _>(id)
+        .bind::<Text, _>(guid(test))
+        .bind::<BigInt, _>(MODEL_ID)
+        .bind::<BigInt, _>(seconds)
+        .bind::<Text, _>(tags(test))
+        .bind::<Text, _>(fields.join(FIELD_SEPARATOR))
+        .bind::<Text, _>(&test.description)
+        .bind::<BigInt, _>(checksum(&test.description))
+        .execute(db)?;
+
+        // A new card, shown in the order of the tests
+        sql_query(
+            "INSERT INTO cards (id, nid, did, ord, mod, usn, type, queue, due, ivl, factor, reps, lapses, left, odue, \
+             odid, flags, data) VALUES (?, ?, ?, 0, ?, -1, 0, 0, ?, 0, 0, 0, 0, 0, 0, 0, 0, '')",
+        )
+        .bind::<BigInt, _>(id)
+        .bind::<BigInt, _>(id)
+        .bind::<BigInt, _>(DECK_ID)
+        .bind::<BigInt, _>(seconds)
+        .bind::<BigInt, _>(index as i64 + 1)
+        .execute(db)?;
+    }
+
+    Ok(())
+}
+
+fn fields(test: &Test, image_name: Option<&str>) -> anyhow::Result<Vec<String>> {
+    let answers = ExportBank::answers(test)?;
+    let right_answer = (test.right_answer_id as usize)
+        .checked_sub(1)
+        .and_then(|index| answers.get(index))
+        .ok_or_else(|| anyhow!("{} test has no {} answer", test.id, test.right_answer_id))?;
+
+    Ok(vec![
+        text_to_html(&test.description),
+        image_name
+            .map(|name| format!("<img src=\"{}\">", escape(name)))
+            .unwrap_or_default(),
+        format!(
+            "<ol>{}</ol>",
+            answers
+                .iter()
+                .map(|answer| format!("<li>{}</li>", escape(answer)))
+                .collect::<String>()
+        ),
+        escape(right_answer),
+        test.explanation.as_deref().map(text_to_html).unwrap_or_default(),
+    ])
+}
+
+/// Stable across exports, Anki updates the notes with the same guid instead of duplicating them
+fn guid(test: &Test) -> String {
+    match test.key.as_ref() {
+        Some(key) => format!("drive-tests:{}", key),
+        None => format!("drive-tests:test_{}", test.id),
+    }
+}
+
+/// The category as a tag, tags can't have spaces
+fn tags(test: &Test) -> String {
+    match test.category.as_ref() {
+        Some(category) => format!(" {} ", category.split_whitespace().collect::<Vec<_>>().join("_")),
+        None => String::new(),
+    }
+}
+
+/// First 8 hex digits of the SHA-1 of the sort field, the way Anki finds duplicates
+fn checksum(sort_field: &str) -> i64 {
+    let hash = sha::sha1(sort_field.as_bytes());
+    u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]]) as i64
+}
+
+fn conf() -> serde_json::Value {
+    json!({
+        "activeDecks": [DECK_ID],
+        "curDeck": DECK_ID,
+        "newSpread": 0,
+        "collapseTime": 1200,
+        "timeLim": 0,
+        "estTimes": true,
+        "dueCounts": true,
+        "curModel": MODEL_ID.to_string(),
+        "nextPos": 1,
+        "sortType": "noteFld",
+        "sortBackwards": false,
+        "addToCur": true,
+    })
+}
+
+fn models(seconds: i64) -> serde_json::Value {
+    let fields = FIELDS
+        .iter()
+        .enumerate()
+        .map(|(ord, name)| {
+            json!({
+                "name": name, "ord": ord, "sticky": false, "rtl": false, "font": "Arial", "size": 20, "media": [],
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        MODEL_ID.to_string(): {
+            "id": MODEL_ID,
+            "name": "Drive test",
+            "type": 0,
+            "mod": seconds,
+            "usn": -1,
+            "sortf": 0,
+            "did": DECK_ID,
+            "tmpls": [{
+                "name": "Test",
+                "ord": 0,
+                "qfmt": "{{Question}}{{#Image}}<div>{{Image}}</div>{{/Image}}{{Answers}}",
+                "afmt": "{{FrontSide}}<hr id=answer><div class=right>{{RightAnswer}}</div>\
+                         {{#Explanation}}<div class=explanation>{{Explanation}}</div>{{/Explanation}}",
+                "did": null,
+                "bqfmt": "",
+                "bafmt": "",
+            }],
+            "flds": fields,
+            "css": CSS,
+            "latexPre": "\\documentclass[12pt]{article}\n\\special{papersize=3in,5in}\n\\usepackage[utf8]{inputenc}\n\
+                         \\usepackage{amssymb,amsmath}\n\\pagestyle{empty}\n\\setlength{\\parindent}{0in}\n\
+                         \\begin{document}\n",
+            "latexPost": "\\end{document}",
+            "tags": [],
+            "vers": [],
+            "req": [[0, "any", [0]]],
+        }
+    })
+}
+
+fn deck(id: i64, name: &str, seconds: i64) -> serde_json::Value {
+    json!({
+        "id": id,
+        "name": name,
+        "mod": seconds,
+        "usn": -1,
+        "lrnToday": [0, 0],
+        "revToday": [0, 0],
+        "newToday": [0, 0],
+        "timeToday": [0, 0],
+        "collapsed": false,
+        "browserCollapsed": false,
+        "desc": "",
+        "dyn": 0,
+        "conf": 1,
+        "extendNew": 0,
+        "extendRev": 0,
+    })
+}
+
+/// Anki expects the default deck to be there
+fn decks(seconds: i64) -> serde_json::Value {
+    json!({
+        "1": deck(1, "Default", seconds),
+        DECK_ID.to_string(): deck(DECK_ID, DECK_NAME, seconds),
+    })
+}
+
+fn deck_configs(seconds: i64) -> serde_json::Value {
+    json!({
+        "1": {
+            "id": 1,
+            "name": "Default",
+            "mod": seconds,
+            "usn": -1,
+            "maxTaken": 60,
+            "autoplay": true,
+            "timer": 0,
+            "replayq": true,
+            "dyn": false,
+            "new": {
+                "delays": [1, 10], "ints": [1, 4, 7], "initialFactor": 2500, "order": 1, "perDay": 20,
+                "bury": true, "separate": true,
+            },
+            "rev": {
+                "perDay": 200, "ease4": 1.3, "fuzz": 0.05, "ivlFct": 1, "maxIvl": 36500, "minSpace": 1,
+                "bury": true, "hardFactor": 1.2,
+            },
+            "lapse": { "delays": [10], "mult": 0, "minInt": 1, "leechFails": 8, "leechAction": 0 },
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{export_tests, testing::*, ExportOptions, ExportSummary},
+        formats::ExportFormat,
+    };
+    use diesel::{select, RunQueryDsl, SqliteConnection};
+
+    #[test]
+    fn fields_put_the_right_answer_and_the_explanation_on_the_back() {
+        let test = Test {
+            id: 7,
+            description: "Who goes first?".to_owned(),
+            answers: r#"["The tram", "The car & the bus"]"#.to_owned(),
+            right_answer_id: 2,
+            image_hash: None,
+            thumbnail_hash: None,
+            retina_hash: None,
+            key: None,
+            retired: false,
+            category: Some("Road signs".to_owned()),
+            explanation: Some("Trams go first".to_owned()),
+        };
+
+        let fields = fields(&test, Some("sign.png")).unwrap();
+
+        assert_eq!(fields.len(), FIELDS.len());
+        assert_eq!(fields[1], r#"<img src="sign.png">"#);
+        assert_eq!(fields[2], "<ol><li>The tram</li><li>The car &amp; the bus</li></ol>");
+        assert_eq!(fields[3], "The car &amp; the bus");
+        assert_eq!(fields[4], "<p>Trams go first</p>");
+        assert_eq!(guid(&test), "drive-tests:test_7");
+        assert_eq!(tags(&test), " Road_signs ");
+    }
+
+    #[test]
+    fn export_tests_writes_anki_decks_of_the_chosen_categories() {
+        use diesel::{dsl::sql, sql_types::BigInt, Connection};
+
+        let (pool, path) = temp_db();
+        let dir = crate::bundle::TempDir::new("drive-tests-anki-test").unwrap();
+        import_bank_with_image(&pool, dir.path());
+        let mut with_category = test_form(Some("q3"), "Third", 1);
+        with_category.category = Some("Signs".to_owned());
+        import(vec![with_category], &pool, false).unwrap();
+
+        let deck = dir.path().join("bank.apkg");
+        let all = export_tests(&pool, &deck, &format_options(ExportFormat::Anki)).unwrap();
+        let options = ExportOptions {
+            format: ExportFormat::Anki,
+            categories: vec!["Signs".to_owned()],
+            ..ExportOptions::default()
+        };
+        let signs = export_tests(&pool, &deck, &options).unwrap();
+
+        let extracted = dir.path().join("extracted");
+        crate::bundle::extract_zip(&deck, &extracted).unwrap();
+        let collection = SqliteConnection::establish(&extracted.join("collection.anki2").to_string_lossy()).unwrap();
+        let count = |table: &str| {
+            select(sql::<BigInt>(&format!("(SELECT COUNT(*) FROM {})", table)))
+                .get_result::<i64>(&collection)
+                .unwrap()
+        };
+
+        remove_temp_db(pool, path);
+        assert_eq!(all, ExportSummary { tests: 3, images: 1 });
+        assert_eq!(signs, ExportSummary { tests: 1, images: 0 });
+        assert_eq!((count("notes"), count("cards")), (1, 1));
+        assert_eq!(std::fs::read_to_string(extracted.join("media")).unwrap(), "{}");
+    }
+}
@@ -0,0 +1,332 @@
+//! GIFT, the text format of Moodle: `::key:: description {=right ~wrong ####explanation}` per paragraph.
+//! Only multiple choice and true/false questions are supported, GIFT has no way to embed the images
+use anyhow::Context;
+use log::warn;
+use std::{fs, path::Path};
+
+use super::{
+    html::html_to_text,
+    moodle::{category_name, category_path},
+    xml::normalize_text,
+};
+use crate::db::{model::TestForm, ExportBank, ExportSummary};
+
+/// Characters with a meaning in GIFT, they are escaped with a backslash in the text
+const SPECIAL_CHARACTERS: &[char] = &['\\', '~', '=', '#', '{', '}', ':'];
+const CATEGORY: &str = "$CATEGORY:";
+
+/// Reads the supported questions of a GIFT file, image paths of `[html]` questions are relative to the file
+pub fn read_gift(path: &Path) -> anyhow::Result<Vec<TestForm>> {
+    let gift = fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(parse_gift(&gift))
+}
+
+fn parse_gift(gift: &str) -> Vec<TestForm> {
+    let mut test_forms = Vec::new();
+    let mut category = None;
+    let mut question = Vec::new();
+
+    // A blank line ends a question
+    for line in gift.lines().chain(std::iter::once("")) {
+        let trimmed = line.trim();
+        if trimmed.starts_with("//") {
+            continue;
+        }
+        if let Some(path) = trimmed.strip_prefix(CATEGORY) {
+            category = category_name(path);
+            continue;
+        }
+        if !trimmed.is_empty() {
+            question.push(line);
+            continue;
+        }
+        if question.is_empty() {
+            continue;
+        }
+
+        let text = question.join("\n");
+        question.clear();
+        match parse_question(&text, category.clone()) {
+            Ok(test_form) => test_forms.push(test_form),
+            Err(err) => warn!(
+                "\"{}\" question is skipped: {:#}",
+                text.lines().next().unwrap_or_default(),
+                err
+            ),
+        }
+    }
+
+    test_forms
+}
+
+/// Byte index of the first `pattern` which isn't escaped
+fn find_unescaped(text: &str, pattern: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if text[index..].starts_with(pattern) {
+            return Some(index);
+        }
+    }
+
+    None
+}
+
+/// Drops the escaping backslashes, `\n` is a line break
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some(c) => unescaped.push(c),
+                None => unescaped.push('\\'),
+            },
+            (c, false) => unescaped.push(c),
+        }
+    }
+
+    unescaped
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            c if SPECIAL_CHARACTERS.contains(&c) => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Text in the question format, only `[html]` has any markup to strip
+fn text(text: &str, format: &str) -> (String, Vec<String>) {
+    match format {
+        "html" => {
+            let html_text = html_to_text(&unescape(text));
+            (html_text.text, html_text.images)
+        }
+        _ => (normalize_text(&unescape(text)), Vec::new()),
+    }
+}
+
+fn parse_question(question: &str, category: Option<String>) -> anyhow::Result<TestForm> {
+    let mut rest = question.trim();
+
+    let mut key = None;
+    if let Some(title) = rest.strip_prefix("::") {
+        let end = find_unescaped(title, "::").ok_or_else(|| anyhow!("the title isn't closed"))?;
+        key = Some(unescape(&title[..end]).trim().to_owned()).filter(|key| !key.is_empty());
+        rest = title[end + 2..].trim_start();
+    }
+
+    let mut format = "";
+    if rest.starts_with('[') {
+        if let Some(end) = rest.find(']') {
+            format = &rest[1..end];
+            rest = &rest[end + 1..];
+        }
+    }
+
+    let open = find_unescaped(rest, "{").ok_or_else(|| anyhow!("it has no answers"))?;
+    let close = open + find_unescaped(&rest[open..], "}").ok_or_else(|| anyhow!("the answers aren't closed"))?;
+
+    // Text after the answers makes a "missing word" question
+    let after = rest[close + 1..].trim();
+    let mut description = rest[..open].trim().to_owned();
+    if !after.is_empty() {
+        description = format!("{} _____ {}", description, after);
+    }
+    let (description, images) = text(&description, format);
+
+    let mut answers = &rest[open + 1..close];
+    let mut explanation = None;
+    if let Some(start) = find_unescaped(answers, "####") {
+        let (general_feedback, _) = text(&answers[start + 4..], format);
+        explanation = Some(general_feedback).filter(|explanation| !explanation.is_empty());
+        answers = &answers[..start];
+    }
+
+    let (answers, right_answer_id) = parse_answers(answers, format)?;
+
+    Ok(TestForm {
+        key,
+        description,
+        answers,
+        right_answer_id,
+        image_path: images.into_iter().next().filter(|image| !image.contains("://")),
+        image_data: None,
+        category,
+        explanation,
+    })
+}
+
+fn parse_answers(answers: &str, format: &str) -> anyhow::Result<(Vec<String>, i32)> {
+    let true_false = answers.split('#').next().unwrap_or_default().trim();
+    match true_false.to_uppercase().as_str() {
+        "T" | "TRUE" => return Ok((vec!["True".to_owned(), "False".to_owned()], 1)),
+        "F" | "FALSE" => return Ok((vec!["True".to_owned(), "False".to_owned()], 2)),
+        _ => {}
+    }
+    if answers.trim_start().starts_with('#') {
+        return Err(anyhow!("numerical questions aren't supported"));
+    }
+
+    // Start of every answer with whether it's a right one
+    let mut starts = Vec::new();
+    let mut escaped = false;
+    for (index, c) in answers.char_indices() {
+        match (escaped, c) {
+            (true, _) => escaped = false,
+            (false, '\\') => escaped = true,
+            (false, '=') | (false, '~') => starts.push((index, c == '=')),
+            _ => {}
+        }
+    }
+
+    let mut parsed = Vec::with_capacity(starts.len());
+    let mut right_answers = Vec::new();
+    for (position, (start, mut right)) in starts.iter().enumerate() {
+        let end = starts.get(position + 1).map_or(answers.len(), |(end, _)| *end);
+        let mut answer = &answers[start + 1..end];
+
+        // Feedback of the answer isn't kept
+        if let Some(feedback) = find_unescaped(answer, "#") {
+            answer = &answer[..feedback];
+        }
+        if find_unescaped(answer, "->").is_some() {
+            return Err(anyhow!("matching questions aren't supported"));
+        }
+        if let Some(weight) = answer.trim_start().strip_prefix('%') {
+            let end = weight
+                .find('%')
+                .ok_or_else(|| anyhow!("an answer weight isn't closed"))?;
+            let weight = weight[..end].trim().parse::<f64>().unwrap_or_default();
+            if weight > 0.0 && weight < 100.0 {
+                return Err(anyhow!("questions with several right answers aren't supported"));
+            }
+            right = weight >= 100.0;
+            answer = &answer.trim_start()[end + 2..];
+        }
+
+        if right {
+            right_answers.push(parsed.len() as i32 + 1);
+        }
+        parsed.push(text(answer, format).0);
+    }
+
+    if !starts.iter().any(|(_, right)| !right) {
+        return Err(anyhow!("short answer questions aren't supported"));
+    }
+    // `0` is left for the validation to report
+    match right_answers.as_slice() {
+        [right_answer_id] => Ok((parsed, *right_answer_id)),
+        [] => Ok((parsed, 0)),
+        _ => Err(anyhow!("questions with several right answers aren't supported")),
+    }
+}
+
+/// Writes the bank as a GIFT file grouped by category, the images are left out
+pub fn write_gift(bank: &ExportBank, path: &Path) -> anyhow::Result<ExportSummary> {
+    let mut tests = bank.tests.iter().collect::<Vec<_>>();
+    tests.sort_by(|a, b| a.category.cmp(&b.category));
+
+    let mut gift = String::new();
+    let mut category = None;
+    let mut skipped_images = 0;
+    for test in tests {
+        if category != Some(&test.category) {
+            category = Some(&test.category);
+            gift.push_str(&format!("{} {}\n\n", CATEGORY, category_path(test.category.as_deref())));
+        }
+        if test.image_hash.is_some() {
+            skipped_images += 1;
+        }
+
+        if let Some(key) = test.key.as_ref() {
+            gift.push_str(&format!("::{}:: ", escape(key)));
+        }
+        gift.push_str(&escape(&test.description));
+        gift.push_str(" {\n");
+        for (index, answer) in ExportBank::answers(test)?.iter().enumerate() {
+            let mark = match index as i32 + 1 == test.right_answer_id {
+                true => '=',
+                false => '~',
+            };
+            gift.push_str(&format!("\t{}{}\n", mark, escape(answer)));
+        }
+        if let Some(explanation) = test.explanation.as_ref() {
+            gift.push_str(&format!("\t####{}\n", escape(explanation)));
+        }
+        gift.push_str("}\n\n");
+    }
+
+    fs::write(path, gift).with_context(|| format!("failed to write {}", path.display()))?;
+
+    if skipped_images > 0 {
+        warn!(
+            "GIFT can't hold images, {} tests are exported without theirs",
+            skipped_images
+        );
+    }
+
+    Ok(ExportSummary {
+        tests: bank.tests.len(),
+        images: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_gift_reads_choice_questions() {
+        let gift = r#"// Road signs
+$CATEGORY: $course$/top/Signs
+
+::sign-1:: What does the sign mean\: give way or stop? {
+    =Give way # right
+    ~Stop
+    ####Triangular signs\nwarn
+}
+
+Is the sign round? {T}
+
+::Several:: Pick two {~%50%A ~%50%B ~C}
+
+Short answer {=Yes}
+"#;
+
+        let test_forms = parse_gift(gift);
+
+        assert_eq!(test_forms.len(), 2);
+        assert_eq!(test_forms[0].key.as_deref(), Some("sign-1"));
+        assert_eq!(test_forms[0].description, "What does the sign mean: give way or stop?");
+        assert_eq!(test_forms[0].answers, vec!["Give way", "Stop"]);
+        assert_eq!(test_forms[0].right_answer_id, 1);
+        assert_eq!(test_forms[0].category.as_deref(), Some("Signs"));
+        assert_eq!(test_forms[0].explanation.as_deref(), Some("Triangular signs\nwarn"));
+        assert_eq!(test_forms[1].key, None);
+        assert_eq!(test_forms[1].answers, vec!["True", "False"]);
+        assert_eq!(test_forms[1].right_answer_id, 1);
+    }
+
+    #[test]
+    fn escape_is_undone_by_unescape() {
+        let text = "a = b: {c} ~ #d\\\nnext line";
+
+        assert_eq!(unescape(&escape(text)), text);
+        assert_eq!(find_unescaped(&escape(text), "="), None);
+    }
+}
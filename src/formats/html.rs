@@ -0,0 +1,119 @@
+use super::xml::{escape, normalize_text};
+
+/// Elements ending a line of the text
+const BLOCK_ELEMENTS: &[&str] = &[
+    "p", "div", "br", "li", "tr", "h1", "h2", "h3", "h4", "h5", "h6", "ul", "ol", "table",
+];
+
+/// Plain text of an HTML fragment
+pub struct HtmlText {
+    pub text: String,
+    /// `src` of the images in the order they appear
+    pub images: Vec<String>,
+}
+
+/// HTML of the quiz formats is written by hand and often isn't XML, so the tags are only stripped, not parsed
+pub fn html_to_text(html: &str) -> HtmlText {
+    let mut text = String::new();
+    let mut images = Vec::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start].replace(['\n', '\r', '\t'], " "));
+        rest = &rest[start..];
+
+        if rest.starts_with("<!--") {
+            rest = rest.find("-->").map_or("", |end| &rest[end + 3..]);
+            continue;
+        }
+
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        if BLOCK_ELEMENTS.contains(&name.as_str()) {
+            text.push('\n');
+        } else if name == "img" {
+            images.extend(attribute(tag, "src"));
+        }
+    }
+    text.push_str(&rest.replace(['\n', '\r', '\t'], " "));
+
+    HtmlText {
+        text: normalize_text(&unescape(&text)),
+        images,
+    }
+}
+
+/// Value of a quoted attribute of a tag
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lowercase = tag.to_lowercase();
+    let mut search_from = 0;
+
+    while let Some(position) = lowercase[search_from..].find(name) {
+        let start = search_from + position;
+        search_from = start + name.len();
+
+        let preceded_by_space = tag[..start].ends_with(char::is_whitespace);
+        let value = tag[search_from..].trim_start();
+        if !preceded_by_space || !value.starts_with('=') {
+            continue;
+        }
+
+        let value = value[1..].trim_start();
+        let quote = value.chars().next()?;
+        return match quote {
+            '"' | '\'' => value[1..].split(quote).next().map(unescape),
+            _ => value.split(char::is_whitespace).next().map(unescape),
+        };
+    }
+
+    None
+}
+
+/// Decodes the XML entities and the common HTML one, text with unknown entities is left as is
+pub fn unescape(text: &str) -> String {
+    let text = text.replace("&nbsp;", " ");
+    match quick_xml::escape::unescape(&text) {
+        Ok(unescaped) => unescaped.into_owned(),
+        Err(_) => text,
+    }
+}
+
+/// A paragraph per line of the text
+pub fn text_to_html(text: &str) -> String {
+    text.lines().map(|line| format!("<p>{}</p>", escape(line))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_to_text_keeps_lines_and_images() {
+        let html = text_to_html("Who goes first?\nA & B")
+            + r#"<p><img alt="" src='@@PLUGINFILE@@/sign%201.png'><br>&nbsp;</p>"#;
+
+        let html_text = html_to_text(&html);
+
+        assert_eq!(html_text.text, "Who goes first?\nA & B");
+        assert_eq!(html_text.images, vec!["@@PLUGINFILE@@/sign%201.png"]);
+    }
+
+    #[test]
+    fn html_to_text_skips_comments_and_inline_markup() {
+        let html_text = html_to_text("<!-- <p>draft</p> -->What does the <b>sign</b>\n mean?");
+
+        assert_eq!(html_text.text, "What does the sign mean?");
+        assert!(html_text.images.is_empty());
+    }
+}
@@ -0,0 +1,106 @@
+//! Readers and writers of the question bank formats other than the JSON one
+use anyhow::Context;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    bundle::BUNDLE_TESTS_FILE,
+    db::{model::TestForm, validation::SheetSource, ExportBank, ExportSummary},
+    formats::scorm::ScormVersion,
+};
+
+pub mod anki;
+pub mod gift;
+mod html;
+pub mod moodle;
+pub mod qti;
+pub mod scorm;
+pub mod sheet;
+mod xml;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ExportFormat {
+    /// `tests.json` in the import format with the images
+    #[default]
+    Bundle,
+    /// IMS QTI 2.1 content package
+    Qti,
+    /// Moodle XML file with the images embedded
+    Moodle,
+    /// GIFT text file, without the images
+    Gift,
+    /// Anki `.apkg` deck with the images as its media
+    Anki,
+    /// SCORM 1.2 package with a player of the tests
+    Scorm12,
+    /// SCORM 2004 package with a player of the tests
+    Scorm2004,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> anyhow::Result<Self> {
+        match format.to_lowercase().as_str() {
+            "bundle" | "json" => Ok(ExportFormat::Bundle),
+            "qti" => Ok(ExportFormat::Qti),
+            "moodle" | "xml" => Ok(ExportFormat::Moodle),
+            "gift" => Ok(ExportFormat::Gift),
+            "anki" | "apkg" => Ok(ExportFormat::Anki),
+            "scorm12" | "scorm1.2" => Ok(ExportFormat::Scorm12),
+            "scorm" | "scorm2004" => Ok(ExportFormat::Scorm2004),
+            _ => Err(anyhow!("{} isn't a supported export format", format)),
+        }
+    }
+}
+
+/// Writes the bank in any format but the bundle one which is written by `db::export_tests` itself
+pub fn export(bank: &ExportBank, path: &Path, format: ExportFormat) -> anyhow::Result<ExportSummary> {
+    match format {
+        ExportFormat::Qti => qti::write_package(bank, path),
+        ExportFormat::Moodle => moodle::write_quiz(bank, path),
+        ExportFormat::Gift => gift::write_gift(bank, path),
+        ExportFormat::Anki => anki::write_deck(bank, path),
+        ExportFormat::Scorm12 => scorm::write_package(bank, path, ScormVersion::Scorm12),
+        ExportFormat::Scorm2004 => scorm::write_package(bank, path, ScormVersion::Scorm2004),
+        ExportFormat::Bundle => Err(anyhow!("bundles are written by db::export_tests")),
+    }
+}
+
+/// The file describing the tests of an exported bundle or of an extracted package
+pub fn package_entry(dir: &Path) -> PathBuf {
+    let manifest = dir.join(qti::MANIFEST_FILE);
+    match manifest.is_file() {
+        true => manifest,
+        false => dir.join(BUNDLE_TESTS_FILE),
+    }
+}
+
+/// Reads the tests from a JSON, CSV, spreadsheet, Moodle XML, GIFT or QTI manifest file chosen by its name,
+/// the tests of a CSV file or a spreadsheet come with the cells they are read from
+pub fn read_test_forms(path: &Path) -> anyhow::Result<(Vec<TestForm>, Option<SheetSource>)> {
+    if path.file_name().is_some_and(|name| name == qti::MANIFEST_FILE) {
+        return qti::read_package(path).map(|test_forms| (test_forms, None));
+    }
+
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "csv" => sheet::read_csv(path).map(|(test_forms, source)| (test_forms, Some(source))),
+        "xlsx" | "xlsm" | "xls" | "ods" => {
+            sheet::read_spreadsheet(path).map(|(test_forms, source)| (test_forms, Some(source)))
+        }
+        "xml" => moodle::read_quiz(path).map(|test_forms| (test_forms, None)),
+        "gift" => gift::read_gift(path).map(|test_forms| (test_forms, None)),
+        _ => {
+            let buffer = fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+            Ok((serde_json::from_str::<Vec<TestForm>>(&buffer)?, None))
+        }
+    }
+}
@@ -0,0 +1,505 @@
+//! Moodle XML question banks: a `quiz` of `question` elements with the images embedded as base64.
+//! Only single answer `multichoice` and `truefalse` questions are supported, the other ones are skipped on import
+use anyhow::Context;
+use log::warn;
+use openssl::base64;
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use std::{fs, path::Path};
+
+use super::{
+    html::{html_to_text, text_to_html},
+    xml::{self, escape, normalize_text},
+};
+use crate::db::{
+    images,
+    model::{Test, TestForm},
+    ExportBank, ExportSummary,
+};
+
+/// Images embedded in the question text are referenced through this prefix
+const PLUGINFILE: &str = "@@PLUGINFILE@@/";
+/// Contexts a category path may start with
+const CATEGORY_CONTEXTS: &[&str] = &["$course$/", "$module$/", "$system$/", "$cat1$/", "top/"];
+
+/// A text element with its `format`
+#[derive(Default)]
+struct FormattedText {
+    format: String,
+    text: String,
+}
+
+impl FormattedText {
+    fn new(element: &BytesStart) -> anyhow::Result<Self> {
+        Ok(FormattedText {
+            format: xml::attribute(element, "format")?.unwrap_or_else(|| "html".to_owned()),
+            text: String::new(),
+        })
+    }
+
+    /// HTML and the auto-format, which is HTML as well once the text has any markup, are converted to plain text
+    fn to_text(&self) -> (String, Vec<String>) {
+        match self.format.as_str() {
+            "plain_text" | "markdown" => (normalize_text(&self.text), Vec::new()),
+            _ => {
+                let html_text = html_to_text(&self.text);
+                (html_text.text, html_text.images)
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct QuestionParser {
+    /// Names of the open elements inside the `question`
+    elements: Vec<String>,
+    question_type: String,
+    name: String,
+    idnumber: String,
+    single: Option<String>,
+    question_text: FormattedText,
+    /// Embedded files by their name
+    files: Vec<(String, String)>,
+    answers: Vec<(f64, FormattedText)>,
+    general_feedback: FormattedText,
+    category: String,
+}
+
+impl QuestionParser {
+    fn parent(&self) -> Option<&str> {
+        self.elements
+            .len()
+            .checked_sub(2)
+            .map(|index| self.elements[index].as_str())
+    }
+
+    fn open(&mut self, name: &str, element: &BytesStart) -> anyhow::Result<()> {
+        match (self.elements.last().map(String::as_str), name) {
+            (Some("question"), "questiontext") => self.question_text = FormattedText::new(element)?,
+            (Some("question"), "generalfeedback") => self.general_feedback = FormattedText::new(element)?,
+            (Some("question"), "answer") => {
+                let fraction = xml::attribute(element, "fraction")?.unwrap_or_default();
+                let fraction = fraction
+                    .trim()
+                    .parse::<f64>()
+                    .with_context(|| format!("{} isn't an answer fraction", fraction))?;
+                self.answers.push((fraction, FormattedText::new(element)?));
+            }
+            (Some("questiontext"), "file") => {
+                let file_name = xml::attribute(element, "name")?.unwrap_or_default();
+                self.files.push((file_name, String::new()));
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str) {
+        let element = self.elements.last().map(String::as_str);
+        match (self.parent(), element) {
+            (Some("questiontext"), Some("text")) => self.question_text.text.push_str(text),
+            (Some("questiontext"), Some("file")) => {
+                if let Some((_, data)) = self.files.last_mut() {
+                    data.push_str(text);
+                }
+            }
+            (Some("generalfeedback"), Some("text")) => self.general_feedback.text.push_str(text),
+            (Some("answer"), Some("text")) => {
+                if let Some((_, answer)) = self.answers.last_mut() {
+                    answer.text.push_str(text);
+                }
+            }
+            (Some("name"), Some("text")) => self.name.push_str(text),
+            (Some("category"), Some("text")) => self.category.push_str(text),
+            (Some("question"), Some("idnumber")) => self.idnumber.push_str(text),
+            (Some("question"), Some("single")) => self.single.get_or_insert_with(String::new).push_str(text),
+            _ => {}
+        }
+    }
+
+    /// The embedded file shown by the question text, relative paths of the other images are kept as is
+    fn image(&self, sources: &[String]) -> anyhow::Result<(Option<String>, Option<Vec<u8>>)> {
+        let source = match sources.first() {
+            Some(source) => source,
+            None => return Ok((None, None)),
+        };
+
+        let file_name = match source.strip_prefix(PLUGINFILE) {
+            Some(file_name) => percent_decode(file_name),
+            None if source.contains("://") || source.starts_with("data:") => {
+                return Err(anyhow!("{} image isn't embedded", source))
+            }
+            None => return Ok((Some(source.clone()), None)),
+        };
+
+        let data = self
+            .files
+            .iter()
+            .find(|(name, _)| *name == file_name)
+            .map(|(_, data)| data)
+            .ok_or_else(|| anyhow!("{} image isn't embedded", file_name))?;
+        let data = data.split_whitespace().collect::<String>();
+        let data = base64::decode_block(&data).with_context(|| format!("{} image isn't valid base64", file_name))?;
+
+        Ok((None, Some(data)))
+    }
+
+    fn into_test_form(self, category: Option<String>) -> anyhow::Result<TestForm> {
+        if self.single.as_deref().is_some_and(|single| single.trim() == "false") {
+            return Err(anyhow!("questions with several right answers aren't supported"));
+        }
+        if self.answers.is_empty() {
+            return Err(anyhow!("it has no answers"));
+        }
+
+        let right_answers = self
+            .answers
+            .iter()
+            .enumerate()
+            .filter(|(_, (fraction, _))| *fraction >= 100.0)
+            .map(|(index, _)| index as i32 + 1)
+            .collect::<Vec<_>>();
+        // `0` is left for the validation to report
+        let right_answer_id = match right_answers.as_slice() {
+            [right_answer_id] => *right_answer_id,
+            [] => 0,
+            _ => return Err(anyhow!("questions with several right answers aren't supported")),
+        };
+
+        let (description, sources) = self.question_text.to_text();
+        let (image_path, image_data) = self.image(&sources)?;
+        let (explanation, _) = self.general_feedback.to_text();
+        let key = self.idnumber.trim().to_owned();
+
+        Ok(TestForm {
+            key: Some(key).filter(|key| !key.is_empty()),
+            description,
+            answers: self.answers.iter().map(|(_, answer)| answer.to_text().0).collect(),
+            right_answer_id,
+            image_path,
+            image_data,
+            category,
+            explanation: Some(explanation).filter(|explanation| !explanation.is_empty()),
+        })
+    }
+}
+
+/// Category of the questions following a `category` question, its context and the default `top` category are dropped
+pub(super) fn category_name(path: &str) -> Option<String> {
+    let mut path = path.trim();
+    while let Some(context) = CATEGORY_CONTEXTS.iter().find(|context| path.starts_with(*context)) {
+        path = &path[context.len()..];
+    }
+
+    match path {
+        "" | "top" => None,
+        path => Some(path.to_owned()),
+    }
+}
+
+/// Tests without a category go to the default one
+pub(super) fn category_path(category: Option<&str>) -> String {
+    match category {
+        Some(category) => format!("$course$/top/{}", category),
+        None => "$course$/top".to_owned(),
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        let byte = text
+            .get(index + 1..index + 3)
+            .filter(|_| bytes[index] == b'%')
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match byte {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Reads the supported questions of a Moodle XML file
+pub fn read_quiz(path: &Path) -> anyhow::Result<Vec<TestForm>> {
+    let quiz = fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    parse_quiz(&quiz)
+}
+
+fn parse_quiz(quiz: &str) -> anyhow::Result<Vec<TestForm>> {
+    let mut reader = Reader::from_str(quiz);
+    let mut test_forms = Vec::new();
+    let mut category = None;
+    let mut question: Option<QuestionParser> = None;
+    let mut index = 0;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => {
+                let name = xml::local_name(&element);
+                if let Some(question) = question.as_mut() {
+                    question.open(&name, &element)?;
+                    question.elements.push(name);
+                } else if name == "question" {
+                    index += 1;
+                    question = Some(QuestionParser {
+                        elements: vec![name],
+                        question_type: xml::attribute(&element, "type")?.unwrap_or_default(),
+                        ..QuestionParser::default()
+                    });
+                }
+            }
+            Event::Empty(element) => {
+                if let Some(question) = question.as_mut() {
+                    question.open(&xml::local_name(&element), &element)?;
+                }
+            }
+            Event::End(_) => {
+                let closed = question.as_mut().and_then(|question| {
+                    question.elements.pop();
+                    match question.elements.is_empty() {
+                        true => Some(()),
+                        false => None,
+                    }
+                });
+                if closed.is_none() {
+                    continue;
+                }
+
+                let parser = question.take().unwrap_or_default();
+                let name = parser.name.trim().to_owned();
+                match parser.question_type.as_str() {
+                    "category" => category = category_name(&parser.category),
+                    "multichoice" | "truefalse" => match parser.into_test_form(category.clone()) {
+                        Ok(test_form) => test_forms.push(test_form),
+                        Err(err) => warn!("{} question \"{}\" is skipped: {:#}", index, name, err),
+                    },
+                    question_type => warn!(
+                        "{} question \"{}\" is skipped: {} questions aren't supported",
+                        index, name, question_type
+                    ),
+                }
+            }
+            Event::Text(text) => {
+                if let Some(question) = question.as_mut() {
+                    question.text(&xml::text(&text)?);
+                }
+            }
+            Event::CData(cdata) => {
+                if let Some(question) = question.as_mut() {
+                    question.text(&xml::cdata(cdata)?);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(test_forms)
+}
+
+/// Writes the bank as a Moodle XML file with the images embedded, the tests are grouped by category
+pub fn write_quiz(bank: &ExportBank, path: &Path) -> anyhow::Result<ExportSummary> {
+    let mut tests = bank.tests.iter().collect::<Vec<_>>();
+    tests.sort_by(|a, b| a.category.cmp(&b.category));
+
+    let mut quiz = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<quiz>\n");
+    let mut category = None;
+    for test in tests {
+        if category != Some(&test.category) {
+            category = Some(&test.category);
+            quiz.push_str(&format!(
+                "  <question type=\"category\">\n    <category>\n      <text>{}</text>\n    </category>\n  </question>\n",
+                escape(&category_path(test.category.as_deref()))
+            ));
+        }
+        quiz.push_str(&question_xml(bank, test)?);
+    }
+    quiz.push_str("</quiz>\n");
+
+    fs::write(path, quiz).with_context(|| format!("failed to write {}", path.display()))?;
+
+    Ok(ExportSummary {
+        tests: bank.tests.len(),
+        images: bank.images.len(),
+    })
+}
+
+/// Moodle shows the question name in its bank, the first line of the description is good enough for that
+fn question_name(test: &Test) -> String {
+    let line = test.description.lines().next().unwrap_or_default();
+    match line.char_indices().nth(80) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_owned(),
+    }
+}
+
+fn question_xml(bank: &ExportBank, test: &Test) -> anyhow::Result<String> {
+    let mut question_text = text_to_html(&test.description);
+    let mut file = String::new();
+    if let Some(image) = bank.image(test)? {
+        let file_name = format!("{}.{}", image.hash, images::extension_of(&image.content_type));
+        question_text.push_str(&format!("<p><img src=\"{}{}\" alt=\"\"></p>", PLUGINFILE, file_name));
+        file = format!(
+            "      <file name=\"{}\" path=\"/\" encoding=\"base64\">{}</file>\n",
+            file_name,
+            base64::encode_block(&image.data)
+        );
+    }
+
+    let general_feedback = test.explanation.as_deref().map(text_to_html).unwrap_or_default();
+    let idnumber = test
+        .key
+        .as_ref()
+        .map(|key| format!("    <idnumber>{}</idnumber>\n", escape(key)))
+        .unwrap_or_default();
+    let answers = ExportBank::answers(test)?
+        .iter()
+        .enumerate()
+        .map(|(index, answer)| {
+            let fraction = match index as i32 + 1 == test.right_answer_id {
+                true => 100,
+                false => 0,
+            };
+            format!(
+                "    <answer fraction=\"{}\" format=\"plain_text\">\n      <text>{}</text>\n    </answer>\n",
+                fraction,
+                escape(answer)
+            )
+        })
+        .collect::<String>();
+
+    Ok(format!(
+        r#"  <question type="multichoice">
+    <name>
+      <text>{name}</text>
+    </name>
+    <questiontext format="html">
+      <text>{question_text}</text>
+{file}    </questiontext>
+    <generalfeedback format="html">
+      <text>{general_feedback}</text>
+    </generalfeedback>
+    <defaultgrade>1</defaultgrade>
+    <penalty>0</penalty>
+    <hidden>0</hidden>
+{idnumber}    <single>true</single>
+    <shuffleanswers>false</shuffleanswers>
+    <answernumbering>123</answernumbering>
+{answers}  </question>
+"#,
+        name = escape(&question_name(test)),
+        question_text = escape(&question_text),
+        file = file,
+        general_feedback = escape(&general_feedback),
+        idnumber = idnumber,
+        answers = answers,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::QuestionCache,
+        db::{export_tests, insert_tests_to_db, testing::*, ExportSummary, ImportOptions},
+        formats::{self, ExportFormat},
+        image_pipeline::{ImagePipeline, ImagePipelineConfig},
+    };
+
+    #[test]
+    fn parse_quiz_reads_moodle_exports() {
+        let quiz = r#"<?xml version="1.0" encoding="UTF-8"?>
+<quiz>
+  <question type="category">
+    <category><text>$course$/top/Road signs</text></category>
+  </question>
+  <question type="multichoice">
+    <name><text>Give way</text></name>
+    <questiontext format="html">
+      <text><![CDATA[<p>What does the sign mean?</p><p><img src="@@PLUGINFILE@@/give%20way.png"></p>]]></text>
+      <file name="give way.png" path="/" encoding="base64">iVBORw0K</file>
+    </questiontext>
+    <generalfeedback format="moodle_auto_format"><text>The sign is triangular</text></generalfeedback>
+    <idnumber>signs-1</idnumber>
+    <single>true</single>
+    <answer fraction="0" format="html"><text><![CDATA[<p>Stop</p>]]></text></answer>
+    <answer fraction="100.0000000" format="html"><text>Give way</text></answer>
+  </question>
+  <question type="multichoice">
+    <name><text>Several</text></name>
+    <questiontext format="html"><text>Pick two</text></questiontext>
+    <single>false</single>
+    <answer fraction="50"><text>A</text></answer>
+    <answer fraction="50"><text>B</text></answer>
+  </question>
+  <question type="shortanswer">
+    <name><text>Short</text></name>
+    <questiontext format="html"><text>Type it</text></questiontext>
+  </question>
+</quiz>"#;
+
+        let test_forms = parse_quiz(quiz).unwrap();
+
+        assert_eq!(test_forms.len(), 1);
+        let test_form = &test_forms[0];
+        assert_eq!(test_form.key.as_deref(), Some("signs-1"));
+        assert_eq!(test_form.description, "What does the sign mean?");
+        assert_eq!(test_form.answers, vec!["Stop", "Give way"]);
+        assert_eq!(test_form.right_answer_id, 2);
+        assert_eq!(test_form.image_data.as_deref(), Some(&b"\x89PNG\r\n"[..]));
+        assert_eq!(test_form.category.as_deref(), Some("Road signs"));
+        assert_eq!(test_form.explanation.as_deref(), Some("The sign is triangular"));
+    }
+
+    #[test]
+    fn category_name_drops_the_context() {
+        assert_eq!(
+            category_name("$course$/top/Signs/Warning").as_deref(),
+            Some("Signs/Warning")
+        );
+        assert_eq!(category_name("$course$/top"), None);
+        assert_eq!(category_name("Markings").as_deref(), Some("Markings"));
+    }
+
+    #[test]
+    fn export_tests_round_trips_through_moodle_xml_and_gift() {
+        let (pool, path) = temp_db();
+        let dir = crate::bundle::TempDir::new("drive-tests-moodle-test").unwrap();
+        let pipeline = ImagePipeline::new(ImagePipelineConfig::default());
+        let options = ImportOptions::default();
+        let cache = QuestionCache::new();
+        import_bank_with_image(&pool, dir.path());
+
+        let quiz = dir.path().join("bank.xml");
+        let moodle = export_tests(&pool, &quiz, &format_options(ExportFormat::Moodle)).unwrap();
+        // The embedded image is the stored one, so nothing changes
+        let moodle_reimported = insert_tests_to_db(&quiz, &pool, &cache, &pipeline, options).unwrap();
+
+        let gift = dir.path().join("bank.gift");
+        let gift_exported = export_tests(&pool, &gift, &format_options(ExportFormat::Gift)).unwrap();
+        let (gift_tests, _) = formats::read_test_forms(&gift).unwrap();
+
+        remove_temp_db(pool, path);
+        assert_eq!(moodle, ExportSummary { tests: 2, images: 1 });
+        assert_eq!(moodle_reimported.summary.unchanged, 2);
+        assert_eq!(gift_exported, ExportSummary { tests: 2, images: 0 });
+        assert_eq!(gift_tests.len(), 2);
+        assert_eq!(gift_tests[0].key.as_deref(), Some("q1"));
+        assert_eq!(gift_tests[0].explanation.as_deref(), Some("Because"));
+        assert_eq!(gift_tests[1].key, None);
+    }
+}
@@ -0,0 +1,561 @@
+//! IMS QTI 2.1 content packages: `imsmanifest.xml` listing an `assessmentItem` file per test.
+//! Only single choice items are supported, the other ones are skipped on import
+use anyhow::Context;
+use log::warn;
+use quick_xml::{events::Event, Reader};
+use std::{collections::HashSet, fs, path::Path};
+
+use super::xml::{self, escape, normalize_text};
+use crate::{
+    bundle::BundleWriter,
+    db::{
+        model::{Test, TestForm},
+        ExportBank, ExportSummary,
+    },
+};
+
+pub const MANIFEST_FILE: &str = "imsmanifest.xml";
+
+const ITEM_RESOURCE_TYPE: &str = "imsqti_item_xmlv2p1";
+const RESPONSE: &str = "RESPONSE";
+/// `identifier` of the `modalFeedback` with the test explanation
+const EXPLANATION: &str = "EXPLANATION";
+/// Namespace of the `testKey` element in the metadata of an item resource
+const KEY_NAMESPACE: &str = "urn:drive-tests:key";
+
+/// An item of the manifest with the key of its test, the item identifier can't keep every key as it is
+struct ItemResource {
+    href: String,
+    /// `Some(None)` for a keyless test, `None` when the package has no keys like the ones of other tools
+    key: Option<Option<String>>,
+}
+
+/// Reads the choice items of an extracted package, image paths become relative to the manifest directory
+pub fn read_package(manifest_path: &Path) -> anyhow::Result<Vec<TestForm>> {
+    let dir = manifest_path.parent().unwrap_or_else(|| Path::new(""));
+    let manifest =
+        fs::read_to_string(manifest_path).with_context(|| format!("failed to read {}", manifest_path.display()))?;
+
+    let mut test_forms = Vec::new();
+    for ItemResource { href, key } in item_resources(&manifest)? {
+        let item = fs::read_to_string(dir.join(&href)).with_context(|| format!("failed to read {} item", href))?;
+        let item_dir = Path::new(&href).parent().unwrap_or_else(|| Path::new(""));
+
+        match parse_item(&item, item_dir) {
+            Ok(mut test_form) => {
+                // The key of the metadata is preferred to the identifier it's sanitized into
+                if let Some(key) = key {
+                    test_form.key = key;
+                }
+                test_forms.push(test_form)
+            }
+            Err(err) => warn!("{} item is skipped: {:#}", href, err),
+        }
+    }
+
+    Ok(test_forms)
+}
+
+fn item_resources(manifest: &str) -> anyhow::Result<Vec<ItemResource>> {
+    let mut reader = Reader::from_str(manifest);
+    let mut resources = Vec::new();
+    // Whether the open `resource` and `testKey` elements belong to an item
+    let (mut in_item, mut in_key) = (false, false);
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) | Event::Empty(element) if xml::local_name(&element) == "resource" => {
+                let resource_type = xml::attribute(&element, "type")?.unwrap_or_default();
+                in_item = false;
+                if resource_type.starts_with("imsqti_item") {
+                    if let Some(href) = xml::attribute(&element, "href")? {
+                        resources.push(ItemResource { href, key: None });
+                        in_item = true;
+                    }
+                }
+            }
+            Event::Start(element) if in_item && xml::local_name(&element) == "testKey" => in_key = true,
+            Event::Empty(element) if in_item && xml::local_name(&element) == "testKey" => {
+                if let Some(resource) = resources.last_mut() {
+                    resource.key = Some(None);
+                }
+            }
+            Event::Text(text) if in_key => {
+                if let Some(resource) = resources.last_mut() {
+                    resource.key = Some(Some(xml::text(&text)?));
+                }
+            }
+            Event::End(element) => match element.local_name().as_ref() {
+                b"resource" => in_item = false,
+                b"testKey" => in_key = false,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(resources)
+}
+
+#[derive(Default)]
+struct ItemParser {
+    /// Names of the open elements
+    elements: Vec<String>,
+    key: Option<String>,
+    category: Option<String>,
+    correct: Option<String>,
+    description: String,
+    choices: Vec<(String, String)>,
+    choice: Option<(String, String)>,
+    image_path: Option<String>,
+    explanation: String,
+}
+
+impl ItemParser {
+    fn inside(&self, name: &str) -> bool {
+        self.elements.iter().any(|element| element == name)
+    }
+
+    fn open(&mut self, name: &str, element: &quick_xml::events::BytesStart, item_dir: &Path) -> anyhow::Result<()> {
+        match name {
+            "assessmentItem" => {
+                self.key = xml::attribute(element, "identifier")?;
+                self.category = xml::attribute(element, "label")?;
+            }
+            "responseDeclaration" if xml::attribute(element, "identifier")?.as_deref() == Some(RESPONSE) => {
+                let cardinality = xml::attribute(element, "cardinality")?.unwrap_or_default();
+                if cardinality != "single" {
+                    return Err(anyhow!(
+                        "{} cardinality isn't supported, only single choice is",
+                        cardinality
+                    ));
+                }
+            }
+            "choiceInteraction" => {
+                let max_choices = xml::attribute(element, "maxChoices")?.unwrap_or_else(|| "1".to_owned());
+                if max_choices != "1" {
+                    return Err(anyhow!("only single choice interactions are supported"));
+                }
+            }
+            "simpleChoice" => {
+                let identifier = xml::attribute(element, "identifier")?
+                    .ok_or_else(|| anyhow!("a simpleChoice has no identifier"))?;
+                self.choice = Some((identifier, String::new()));
+            }
+            "img" if self.inside("itemBody") && !self.inside("simpleChoice") && self.image_path.is_none() => {
+                self.image_path =
+                    xml::attribute(element, "src")?.map(|src| item_dir.join(src).to_string_lossy().replace('\\', "/"));
+            }
+            interaction if interaction.ends_with("Interaction") => {
+                return Err(anyhow!("{} isn't supported, only choiceInteraction is", interaction));
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn close(&mut self, name: &str) {
+        match name {
+            "simpleChoice" => self.choices.extend(self.choice.take()),
+            // Block elements end a line
+            "p" | "div" | "prompt" | "li" | "br" => {
+                if self.inside("modalFeedback") || self.inside("feedbackBlock") {
+                    self.explanation.push('\n');
+                } else {
+                    self.description.push('\n');
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        // Line breaks of the markup aren't line breaks of the text
+        let text = text.replace(['\n', '\r', '\t'], " ");
+
+        if self.elements.last().map(String::as_str) == Some("value") && self.inside("correctResponse") {
+            self.correct = Some(text.trim().to_owned());
+        } else if let Some((_, choice)) = self.choice.as_mut() {
+            choice.push_str(&text);
+        } else if self.inside("modalFeedback") || self.inside("feedbackBlock") {
+            self.explanation.push_str(&text);
+        } else if self.inside("itemBody") && (!self.inside("choiceInteraction") || self.inside("prompt")) {
+            self.description.push_str(&text);
+        }
+    }
+
+    fn into_test_form(self) -> TestForm {
+        let correct = self.correct;
+        let right_answer_id = self
+            .choices
+            .iter()
+            .position(|(identifier, _)| Some(identifier) == correct.as_ref())
+            // `0` is left for the validation to report
+            .map_or(0, |index| index as i32 + 1);
+        let explanation = normalize_text(&self.explanation);
+
+        TestForm {
+            key: self.key,
+            description: normalize_text(&self.description),
+            answers: self.choices.iter().map(|(_, text)| normalize_text(text)).collect(),
+            right_answer_id,
+            image_path: self.image_path,
+            image_data: None,
+            category: self.category.filter(|category| !category.trim().is_empty()),
+            explanation: Some(explanation).filter(|explanation| !explanation.is_empty()),
+        }
+    }
+}
+
+/// Parses a single choice `assessmentItem`, relative image paths are resolved against `item_dir`
+fn parse_item(item: &str, item_dir: &Path) -> anyhow::Result<TestForm> {
+    let mut reader = Reader::from_str(item);
+    let mut parser = ItemParser::default();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => {
+                let name = xml::local_name(&element);
+                parser.open(&name, &element, item_dir)?;
+                parser.elements.push(name);
+            }
+            Event::Empty(element) => {
+                let name = xml::local_name(&element);
+                parser.open(&name, &element, item_dir)?;
+                parser.close(&name);
+            }
+            Event::End(_) => {
+                if let Some(name) = parser.elements.pop() {
+                    parser.close(&name);
+                }
+            }
+            Event::Text(text) => parser.text(&xml::text(&text)?),
+            Event::CData(cdata) => parser.text(&xml::cdata(cdata)?),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if parser.key.is_none() {
+        return Err(anyhow!("it isn't an assessmentItem"));
+    }
+    if parser.choices.is_empty() {
+        return Err(anyhow!("it has no choiceInteraction"));
+    }
+
+    Ok(parser.into_test_form())
+}
+
+/// Writes the bank as a QTI 2.1 package to a directory or a `.zip` archive
+pub fn write_package(bank: &ExportBank, path: &Path) -> anyhow::Result<ExportSummary> {
+    let mut bundle = BundleWriter::create(path)?;
+    let images = bank.write_images(&mut bundle)?;
+
+    let mut identifiers = HashSet::new();
+    let mut resources = String::new();
+
+    for test in bank.tests.iter() {
+        let mut identifier = item_identifier(test);
+        if !identifiers.insert(identifier.clone()) {
+            identifier = format!("{}_{}", identifier, test.id);
+            identifiers.insert(identifier.clone());
+        }
+
+        let href = format!("items/{}.xml", identifier);
+        let image = bank.image(test)?.map(ExportBank::image_file_name);
+        bundle.add_file(&href, item_xml(test, &identifier, image.as_deref())?.as_bytes())?;
+
+        // The identifier is a sanitized key, so the key itself goes to the metadata to be imported back
+        let key = match test.key.as_ref() {
+            Some(key) => format!(
+                "<dt:testKey xmlns:dt=\"{}\">{}</dt:testKey>",
+                KEY_NAMESPACE,
+                escape(key)
+            ),
+            None => format!("<dt:testKey xmlns:dt=\"{}\"/>", KEY_NAMESPACE),
+        };
+        resources.push_str(&format!(
+            "    <resource identifier=\"resource_{id}\" type=\"{resource_type}\" href=\"{href}\">\n      <metadata>{key}</metadata>\n      <file href=\"{href}\"/>\n",
+            id = escape(&identifier),
+            resource_type = ITEM_RESOURCE_TYPE,
+            href = escape(&href),
+            key = key,
+        ));
+        if let Some(image) = image {
+            resources.push_str(&format!("      <file href=\"{}\"/>\n", escape(&image)));
+        }
+        resources.push_str("    </resource>\n");
+    }
+
+    let manifest = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest xmlns="http://www.imsglobal.org/xsd/imscp_v1p1" identifier="drive-tests">
+  <metadata>
+    <schema>QTIv2.1 Package</schema>
+    <schemaversion>1.0.0</schemaversion>
+  </metadata>
+  <organizations/>
+  <resources>
+{}  </resources>
+</manifest>
+"#,
+        resources
+    );
+    bundle.add_file(MANIFEST_FILE, manifest.as_bytes())?;
+    bundle.finish()?;
+
+    Ok(ExportSummary {
+        tests: bank.tests.len(),
+        images,
+    })
+}
+
+/// Item identifiers are XML names, keys which aren't ones are sanitized and the keyless tests are named by their id.
+/// The keys themselves are kept in the manifest
+fn item_identifier(test: &Test) -> String {
+    let key = match test.key.as_ref() {
+        Some(key) => key,
+        None => return format!("test_{}", test.id),
+    };
+
+    let identifier = key
+        .chars()
+        .map(
+            |c| match c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' {
+                true => c,
+                false => '_',
+            },
+        )
+        .collect::<String>();
+    match identifier.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        true => identifier,
+        false => format!("_{}", identifier),
+    }
+}
+
+fn paragraphs(text: &str, indent: &str) -> String {
+    text.lines()
+        .map(|line| format!("{}<p>{}</p>\n", indent, escape(line)))
+        .collect()
+}
+
+fn item_xml(test: &Test, identifier: &str, image: Option<&str>) -> anyhow::Result<String> {
+    let answers = ExportBank::answers(test)?;
+
+    let label = test
+        .category
+        .as_ref()
+        .map(|category| format!(" label=\"{}\"", escape(category)))
+        .unwrap_or_default();
+    let image = image
+        .map(|image| format!("    <p><img src=\"../{}\" alt=\"\"/></p>\n", escape(image)))
+        .unwrap_or_default();
+    let choices = answers
+        .iter()
+        .enumerate()
+        .map(|(index, answer)| {
+            format!(
+                "      <simpleChoice identifier=\"choice_{}\">{}</simpleChoice>\n",
+                index + 1,
+                escape(answer)
+            )
+        })
+        .collect::<String>();
+
+    // The explanation is shown whatever the answer is
+    let (feedback_declaration, response_processing, feedback) = match test.explanation.as_ref() {
+        Some(explanation) => (
+            "  <outcomeDeclaration identifier=\"FEEDBACK\" cardinality=\"single\" baseType=\"identifier\"/>\n".to_owned(),
+            format!(
+                r#"  <responseProcessing>
+    <responseCondition>
+      <responseIf>
+        <match><variable identifier="RESPONSE"/><correct identifier="RESPONSE"/></match>
+        <setOutcomeValue identifier="SCORE"><baseValue baseType="float">1</baseValue></setOutcomeValue>
+      </responseIf>
+      <responseElse>
+        <setOutcomeValue identifier="SCORE"><baseValue baseType="float">0</baseValue></setOutcomeValue>
+      </responseElse>
+    </responseCondition>
+    <setOutcomeValue identifier="FEEDBACK"><baseValue baseType="identifier">{}</baseValue></setOutcomeValue>
+  </responseProcessing>
+"#,
+                EXPLANATION
+            ),
+            format!(
+                "  <modalFeedback outcomeIdentifier=\"FEEDBACK\" identifier=\"{}\" showHide=\"show\">\n{}  </modalFeedback>\n",
+                EXPLANATION,
+                paragraphs(explanation, "    ")
+            ),
+        ),
+        None => (
+            String::new(),
+            "  <responseProcessing template=\"http://www.imsglobal.org/question/qti_v2p1/rptemplates/match_correct\"/>\n"
+                .to_owned(),
+            String::new(),
+        ),
+    };
+
+    Ok(format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<assessmentItem xmlns="http://www.imsglobal.org/xsd/imsqti_v2p1" identifier="{identifier}" title="{title}"{label} adaptive="false" timeDependent="false">
+  <responseDeclaration identifier="{response}" cardinality="single" baseType="identifier">
+    <correctResponse>
+      <value>choice_{right_answer_id}</value>
+    </correctResponse>
+  </responseDeclaration>
+  <outcomeDeclaration identifier="SCORE" cardinality="single" baseType="float">
+    <defaultValue>
+      <value>0</value>
+    </defaultValue>
+  </outcomeDeclaration>
+{feedback_declaration}  <itemBody>
+{description}{image}    <choiceInteraction responseIdentifier="{response}" shuffle="false" maxChoices="1">
+{choices}    </choiceInteraction>
+  </itemBody>
+{response_processing}{feedback}</assessmentItem>
+"#,
+        identifier = escape(identifier),
+        title = escape(test.description.lines().next().unwrap_or_default()),
+        label = label,
+        response = RESPONSE,
+        right_answer_id = test.right_answer_id,
+        feedback_declaration = feedback_declaration,
+        description = paragraphs(&test.description, "    "),
+        image = image,
+        choices = choices,
+        response_processing = response_processing,
+        feedback = feedback,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::QuestionCache,
+        db::{export_tests, get_tests, insert_tests_to_db, testing::*, ExportSummary, ImportOptions},
+        formats::ExportFormat,
+        image_pipeline::{ImagePipeline, ImagePipelineConfig},
+    };
+
+    fn test(key: Option<&str>, explanation: Option<&str>) -> Test {
+        Test {
+            id: 7,
+            description: "Who goes first?\nLook at the picture".to_owned(),
+            answers: r#"["The tram","The car & the bus"]"#.to_owned(),
+            right_answer_id: 2,
+            image_hash: None,
+            thumbnail_hash: None,
+            retina_hash: None,
+            key: key.map(str::to_owned),
+            retired: false,
+            category: Some("Crossroads".to_owned()),
+            explanation: explanation.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn item_xml_is_read_back_as_the_same_test() {
+        let test = test(Some("q1"), Some("Trams go first"));
+        let xml = item_xml(&test, "q1", Some("images/sign.png")).unwrap();
+
+        let test_form = parse_item(&xml, Path::new("items")).unwrap();
+
+        assert_eq!(test_form.key.as_deref(), Some("q1"));
+        assert_eq!(test_form.description, test.description);
+        assert_eq!(test_form.answers, vec!["The tram", "The car & the bus"]);
+        assert_eq!(test_form.right_answer_id, 2);
+        assert_eq!(test_form.image_path.as_deref(), Some("items/../images/sign.png"));
+        assert_eq!(test_form.category.as_deref(), Some("Crossroads"));
+        assert_eq!(test_form.explanation.as_deref(), Some("Trams go first"));
+    }
+
+    #[test]
+    fn parse_item_reads_items_of_other_authoring_tools() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <qti:assessmentItem xmlns:qti="http://www.imsglobal.org/xsd/imsqti_v2p1" identifier="item-42" title="Sign">
+              <qti:responseDeclaration identifier="RESPONSE" cardinality="single" baseType="identifier">
+                <qti:correctResponse><qti:value>B</qti:value></qti:correctResponse>
+              </qti:responseDeclaration>
+              <qti:itemBody>
+                <qti:choiceInteraction responseIdentifier="RESPONSE" shuffle="true" maxChoices="1">
+                  <qti:prompt>What does the
+                    <qti:b>sign</qti:b> mean?</qti:prompt>
+                  <qti:simpleChoice identifier="A">Stop</qti:simpleChoice>
+                  <qti:simpleChoice identifier="B"><![CDATA[Give way]]></qti:simpleChoice>
+                </qti:choiceInteraction>
+              </qti:itemBody>
+            </qti:assessmentItem>"#;
+
+        let test_form = parse_item(xml, Path::new("")).unwrap();
+
+        assert_eq!(test_form.key.as_deref(), Some("item-42"));
+        assert_eq!(test_form.description, "What does the sign mean?");
+        assert_eq!(test_form.answers, vec!["Stop", "Give way"]);
+        assert_eq!(test_form.right_answer_id, 2);
+        assert_eq!(test_form.explanation, None);
+    }
+
+    #[test]
+    fn parse_item_rejects_other_interactions() {
+        let xml = r#"<assessmentItem identifier="q1">
+              <responseDeclaration identifier="RESPONSE" cardinality="single" baseType="string"/>
+              <itemBody><textEntryInteraction responseIdentifier="RESPONSE"/></itemBody>
+            </assessmentItem>"#;
+
+        assert!(parse_item(xml, Path::new("")).is_err());
+    }
+
+    #[test]
+    fn item_identifier_sanitizes_keys() {
+        assert_eq!(item_identifier(&test(None, None)), "test_7");
+        assert_eq!(item_identifier(&test(Some("signs/12 a"), None)), "signs_12_a");
+        assert_eq!(item_identifier(&test(Some("12"), None)), "_12");
+    }
+
+    #[test]
+    fn export_tests_writes_qti_packages_which_import_back() {
+        let (pool, path) = temp_db();
+        let (other_pool, other_path) = temp_db();
+        let dir = crate::bundle::TempDir::new("drive-tests-qti-test").unwrap();
+        let pipeline = ImagePipeline::new(ImagePipelineConfig::default());
+        import_bank_with_image(&pool, dir.path());
+        // Both keys are sanitized into the same identifier
+        let sanitized = vec![
+            test_form(Some("signs/12 a"), "Third", 1),
+            test_form(Some("signs_12 a"), "Fourth", 2),
+        ];
+        import(sanitized, &pool, false).unwrap();
+
+        let package = dir.path().join("bank-qti.zip");
+        let exported = export_tests(&pool, &package, &format_options(ExportFormat::Qti)).unwrap();
+        let options = ImportOptions::default();
+        let cache = QuestionCache::new();
+        let elsewhere = insert_tests_to_db(&package, &other_pool, &cache, &pipeline, options).unwrap();
+        let reimported = insert_tests_to_db(&package, &pool, &cache, &pipeline, options).unwrap();
+        let other_tests = get_tests(&other_pool).unwrap();
+
+        remove_temp_db(pool, path);
+        remove_temp_db(other_pool, other_path);
+        assert_eq!(exported, ExportSummary { tests: 4, images: 1 });
+        assert_eq!(elsewhere.summary.created, 4);
+        assert_eq!(
+            (
+                reimported.summary.created,
+                reimported.summary.unchanged,
+                reimported.summary.updated
+            ),
+            (0, 4, 0)
+        );
+        assert_eq!(
+            other_tests.iter().map(|test| test.key.as_deref()).collect::<Vec<_>>(),
+            vec![Some("q1"), None, Some("signs/12 a"), Some("signs_12 a")]
+        );
+        assert_eq!(other_tests[0].explanation.as_deref(), Some("Because"));
+        assert!(other_tests[0].image_hash.is_some());
+        assert_eq!(other_tests[1].right_answer_id, 2);
+    }
+}
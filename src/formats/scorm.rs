@@ -0,0 +1,202 @@
+//! SCORM 1.2 and 2004 packages: a single SCO with a self-contained HTML/JS player of the exported tests.
+//! The player reports the score, completion and whether the learner passed through the SCORM runtime API of the LMS
+use serde_json::json;
+use std::path::Path;
+
+use super::xml::escape;
+use crate::{
+    bundle::BundleWriter,
+    db::{ExportBank, ExportSummary},
+};
+
+const MANIFEST_FILE: &str = "imsmanifest.xml";
+const PLAYER_HTML: &str = include_str!("scorm/index.html");
+const PLAYER_JS: &str = include_str!("scorm/player.js");
+/// Percent of right answers the learner needs to pass
+const PASS_MARK: u32 = 80;
+const TITLE: &str = "Drive tests";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScormVersion {
+    Scorm12,
+    Scorm2004,
+}
+
+impl ScormVersion {
+    /// `SCORM_VERSION` of the player
+    fn player_name(self) -> &'static str {
+        match self {
+            ScormVersion::Scorm12 => "1.2",
+            ScormVersion::Scorm2004 => "2004",
+        }
+    }
+}
+
+/// The tests in the shape `get_test` serves them with what the player needs to check the answers offline
+fn questions_js(bank: &ExportBank, version: ScormVersion) -> anyhow::Result<String> {
+    let mut questions = Vec::with_capacity(bank.tests.len());
+    for test in bank.tests.iter() {
+        questions.push(json!({
+            "id": test.id,
+            "description": test.description,
+            "answers": ExportBank::answers(test)?,
+            "image_url": bank.image(test)?.map(ExportBank::image_file_name),
+            "right_answer_id": test.right_answer_id,
+            "explanation": test.explanation,
+        }));
+    }
+
+    Ok(format!(
+        "var SCORM_VERSION = \"{}\";\nvar PASS_MARK = {};\nvar QUESTIONS = {};\n",
+        version.player_name(),
+        PASS_MARK,
+        serde_json::to_string_pretty(&questions)?
+    ))
+}
+
+fn manifest(version: ScormVersion, files: &[String]) -> String {
+    let files = files
+        .iter()
+        .map(|file| format!("      <file href=\"{}\"/>\n", escape(file)))
+        .collect::<String>();
+
+    match version {
+        ScormVersion::Scorm12 => format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest identifier="drive-tests" version="1.0"
+    xmlns="http://www.imsproject.org/xsd/imscp_rootv1p1p2"
+    xmlns:adlcp="http://www.adlnet.org/xsd/adlcp_rootv1p2">
+  <metadata>
+    <schema>ADL SCORM</schema>
+    <schemaversion>1.2</schemaversion>
+  </metadata>
+  <organizations default="drive-tests-organization">
+    <organization identifier="drive-tests-organization">
+      <title>{title}</title>
+      <item identifier="drive-tests-item" identifierref="drive-tests-player">
+        <title>{title}</title>
+        <adlcp:masteryscore>{pass_mark}</adlcp:masteryscore>
+      </item>
+    </organization>
+  </organizations>
+  <resources>
+    <resource identifier="drive-tests-player" type="webcontent" adlcp:scormtype="sco" href="index.html">
+{files}    </resource>
+  </resources>
+</manifest>
+"#,
+            title = TITLE,
+            pass_mark = PASS_MARK,
+            files = files,
+        ),
+        ScormVersion::Scorm2004 => format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest identifier="drive-tests" version="1.0"
+    xmlns="http://www.imsglobal.org/xsd/imscp_v1p1"
+    xmlns:adlcp="http://www.adlnet.org/xsd/adlcp_v1p3"
+    xmlns:imsss="http://www.imsglobal.org/xsd/imsss">
+  <metadata>
+    <schema>ADL SCORM</schema>
+    <schemaversion>2004 4th Edition</schemaversion>
+  </metadata>
+  <organizations default="drive-tests-organization">
+    <organization identifier="drive-tests-organization">
+      <title>{title}</title>
+      <item identifier="drive-tests-item" identifierref="drive-tests-player">
+        <title>{title}</title>
+        <imsss:sequencing>
+          <imsss:objectives>
+            <imsss:primaryObjective objectiveID="drive-tests-passed" satisfiedByMeasure="true">
+              <imsss:minNormalizedMeasure>{pass_mark}</imsss:minNormalizedMeasure>
+            </imsss:primaryObjective>
+          </imsss:objectives>
+        </imsss:sequencing>
+      </item>
+    </organization>
+  </organizations>
+  <resources>
+    <resource identifier="drive-tests-player" type="webcontent" adlcp:scormType="sco" href="index.html">
+{files}    </resource>
+  </resources>
+</manifest>
+"#,
+            title = TITLE,
+            pass_mark = PASS_MARK as f64 / 100.0,
+            files = files,
+        ),
+    }
+}
+
+/// Writes the bank as a SCORM package to a directory or a `.zip` archive
+pub fn write_package(bank: &ExportBank, path: &Path, version: ScormVersion) -> anyhow::Result<ExportSummary> {
+    let mut package = BundleWriter::create(path)?;
+    let images = bank.write_images(&mut package)?;
+
+    package.add_file("index.html", PLAYER_HTML.as_bytes())?;
+    package.add_file("player.js", PLAYER_JS.as_bytes())?;
+    package.add_file("questions.js", questions_js(bank, version)?.as_bytes())?;
+
+    let mut files = vec![
+        "index.html".to_owned(),
+        "player.js".to_owned(),
+        "questions.js".to_owned(),
+    ];
+    files.extend(bank.images.values().map(ExportBank::image_file_name));
+    package.add_file(MANIFEST_FILE, manifest(version, &files).as_bytes())?;
+    package.finish()?;
+
+    Ok(ExportSummary {
+        tests: bank.tests.len(),
+        images,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{export_tests, get_tests, testing::*, ExportOptions, ExportSummary},
+        formats::ExportFormat,
+    };
+
+    #[test]
+    fn manifest_lists_the_player_files_for_both_versions() {
+        let files = vec!["index.html".to_owned(), "images/a&b.png".to_owned()];
+
+        let scorm12 = manifest(ScormVersion::Scorm12, &files);
+        let scorm2004 = manifest(ScormVersion::Scorm2004, &files);
+
+        assert!(scorm12.contains("<schemaversion>1.2</schemaversion>"));
+        assert!(scorm12.contains("<adlcp:masteryscore>80</adlcp:masteryscore>"));
+        assert!(scorm2004.contains("<schemaversion>2004 4th Edition</schemaversion>"));
+        assert!(scorm2004.contains("<imsss:minNormalizedMeasure>0.8</imsss:minNormalizedMeasure>"));
+        for manifest in [scorm12, scorm2004] {
+            assert!(manifest.contains(r#"<file href="images/a&amp;b.png"/>"#));
+        }
+    }
+
+    #[test]
+    fn export_tests_writes_scorm_packages_of_the_chosen_tests() {
+        let (pool, path) = temp_db();
+        let dir = crate::bundle::TempDir::new("drive-tests-scorm-test").unwrap();
+        import_bank_with_image(&pool, dir.path());
+        let first = get_tests(&pool).unwrap()[0].id;
+
+        let package = dir.path().join("scorm");
+        let options = ExportOptions {
+            format: ExportFormat::Scorm12,
+            test_ids: vec![first],
+            ..ExportOptions::default()
+        };
+        let exported = export_tests(&pool, &package, &options).unwrap();
+        let questions = std::fs::read_to_string(package.join("questions.js")).unwrap();
+        let manifest = std::fs::read_to_string(package.join("imsmanifest.xml")).unwrap();
+
+        remove_temp_db(pool, path);
+        assert_eq!(exported, ExportSummary { tests: 1, images: 1 });
+        assert!(questions.contains("\"description\": \"First\""));
+        assert!(!questions.contains("Second"));
+        assert!(package.join("index.html").is_file());
+        assert!(manifest.contains("adlcp:scormtype=\"sco\" href=\"index.html\""));
+    }
+}
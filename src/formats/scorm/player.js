@@ -0,0 +1,144 @@
+// Shows QUESTIONS one by one and reports the score through the SCORM runtime API of SCORM_VERSION
+(function () {
+  "use strict";
+
+  var is2004 = SCORM_VERSION === "2004";
+  var api = findApi(window);
+  var index = 0;
+  var right = 0;
+  var finished = false;
+
+  // The API is provided by a parent frame or the window which opened the package
+  function findApi(win) {
+    var name = is2004 ? "API_1484_11" : "API";
+    for (var depth = 0; win && depth < 10; depth++) {
+      if (win[name]) {
+        return win[name];
+      }
+      if (win.parent && win.parent !== win) {
+        win = win.parent;
+      } else {
+        win = win.opener;
+      }
+    }
+    return null;
+  }
+
+  function call(method12, method2004) {
+    if (!api) {
+      return "";
+    }
+    var args = Array.prototype.slice.call(arguments, 2);
+    return api[is2004 ? method2004 : method12].apply(api, args);
+  }
+
+  function setValue(element12, element2004, value) {
+    call("LMSSetValue", "SetValue", is2004 ? element2004 : element12, String(value));
+  }
+
+  function text(id, value) {
+    document.getElementById(id).textContent = value;
+  }
+
+  function show(id, visible) {
+    document.getElementById(id).hidden = !visible;
+  }
+
+  function showQuestion() {
+    var question = QUESTIONS[index];
+    var answers = document.getElementById("answers");
+    var image = document.getElementById("image");
+
+    text("progress", (index + 1) + " / " + QUESTIONS.length);
+    text("description", question.description);
+    if (question.image_url) {
+      image.src = question.image_url;
+    }
+    show("image", !!question.image_url);
+    show("explanation", false);
+    show("next", false);
+
+    answers.innerHTML = "";
+    question.answers.forEach(function (answer, answerIndex) {
+      var button = document.createElement("button");
+      button.textContent = answer;
+      button.onclick = function () {
+        choose(question, answerIndex + 1);
+      };
+      answers.appendChild(button);
+    });
+  }
+
+  function choose(question, answerId) {
+    var buttons = document.getElementById("answers").getElementsByTagName("button");
+    for (var i = 0; i < buttons.length; i++) {
+      buttons[i].disabled = true;
+    }
+    buttons[question.right_answer_id - 1].className = "right";
+    if (answerId === question.right_answer_id) {
+      right++;
+    } else {
+      buttons[answerId - 1].className = "wrong";
+    }
+
+    if (question.explanation) {
+      text("explanation", question.explanation);
+      show("explanation", true);
+    }
+    show("next", true);
+  }
+
+  function finish() {
+    var score = QUESTIONS.length ? Math.round((right * 100) / QUESTIONS.length) : 100;
+    var passed = score >= PASS_MARK;
+
+    setValue("cmi.core.score.min", "cmi.score.min", 0);
+    setValue("cmi.core.score.max", "cmi.score.max", 100);
+    setValue("cmi.core.score.raw", "cmi.score.raw", score);
+    if (is2004) {
+      setValue(null, "cmi.score.scaled", score / 100);
+      setValue(null, "cmi.completion_status", "completed");
+      setValue(null, "cmi.success_status", passed ? "passed" : "failed");
+    } else {
+      setValue("cmi.core.lesson_status", null, passed ? "passed" : "failed");
+    }
+    call("LMSCommit", "Commit", "");
+    terminate();
+
+    show("question", false);
+    show("result", true);
+    text("progress", "");
+    text("score", right + " / " + QUESTIONS.length + " (" + score + "%)");
+    text("status", passed ? "Passed" : "Failed");
+  }
+
+  function terminate() {
+    if (!finished) {
+      finished = true;
+      call("LMSFinish", "Terminate", "");
+    }
+  }
+
+  document.getElementById("next").onclick = function () {
+    index++;
+    if (index < QUESTIONS.length) {
+      showQuestion();
+    } else {
+      finish();
+    }
+  };
+  window.addEventListener("unload", terminate);
+
+  call("LMSInitialize", "Initialize", "");
+  if (is2004) {
+    setValue(null, "cmi.completion_status", "incomplete");
+  } else {
+    setValue("cmi.core.lesson_status", null, "incomplete");
+  }
+
+  if (QUESTIONS.length) {
+    showQuestion();
+  } else {
+    finish();
+  }
+})();
@@ -0,0 +1,372 @@
+use anyhow::Context;
+use calamine::{open_workbook_auto, Reader};
+use std::{collections::HashMap, path::Path};
+
+use crate::db::{model::TestForm, validation::SheetSource};
+
+/// Positions of the known columns, the header row names them in any order and case
+struct Columns {
+    key: Option<usize>,
+    description: usize,
+    /// Either `answer 1`, `answer 2`... columns or a single `answers` column with an answer per line
+    answers: Vec<usize>,
+    right_answer: usize,
+    image: Option<usize>,
+    category: Option<usize>,
+    explanation: Option<usize>,
+}
+
+impl Columns {
+    fn from_header(header: &[String]) -> anyhow::Result<Self> {
+        let names = header.iter().map(|name| normalize(name)).collect::<Vec<_>>();
+        let find = |aliases: &[&str]| names.iter().position(|name| aliases.contains(&name.as_str()));
+
+        let mut numbered_answers = names
+            .iter()
+            .enumerate()
+            .filter_map(|(index, name)| {
+                let number = name.strip_prefix("answer_")?.parse::<usize>().ok()?;
+                Some((number, index))
+            })
+            .collect::<Vec<_>>();
+        numbered_answers.sort_unstable();
+        let answers = match find(&["answers"]) {
+            Some(index) if numbered_answers.is_empty() => vec![index],
+            _ => numbered_answers.into_iter().map(|(_, index)| index).collect(),
+        };
+        if answers.is_empty() {
+            return Err(anyhow!("there is neither an `answers` nor `answer 1` column"));
+        }
+
+        Ok(Columns {
+            key: find(&["key"]),
+            description: find(&["description", "question"])
+                .ok_or_else(|| anyhow!("there is no `description` column"))?,
+            answers,
+            right_answer: find(&["right_answer", "correct_answer", "right_answer_id"])
+                .ok_or_else(|| anyhow!("there is no `correct answer` column"))?,
+            image: find(&["image", "image_file", "image_path"]),
+            category: find(&["category"]),
+            explanation: find(&["explanation"]),
+        })
+    }
+
+    /// The headers of the columns the fields are read from
+    fn headers(&self, header: &[String]) -> HashMap<String, String> {
+        let name = |index: usize| header[index].trim().to_owned();
+        let mut headers = [
+            ("key", self.key),
+            ("description", Some(self.description)),
+            ("right_answer_id", Some(self.right_answer)),
+            ("image_path", self.image),
+            ("category", self.category),
+            ("explanation", self.explanation),
+        ]
+        .iter()
+        .filter_map(|(field, index)| Some((field.to_string(), name((*index)?))))
+        .collect::<HashMap<_, _>>();
+        match self.answers.as_slice() {
+            [single] => {
+                headers.insert("answers".to_owned(), name(*single));
+            }
+            columns => headers.extend(
+                columns
+                    .iter()
+                    .enumerate()
+                    .map(|(answer, &index)| (format!("answers/{}", answer), name(index))),
+            ),
+        }
+
+        headers
+    }
+
+    /// The test comes with the answers its correct answer cell is ambiguous between, if it is
+    fn test_form(&self, row: &[String]) -> (TestForm, Option<(i32, i32)>) {
+        let cell = |index: usize| row.get(index).map(|cell| cell.trim()).unwrap_or_default();
+        let optional = |index: Option<usize>| index.map(cell).filter(|cell| !cell.is_empty()).map(str::to_owned);
+
+        let answers = match self.answers.as_slice() {
+            [single] => cell(*single)
+                .lines()
+                .map(str::trim)
+                .filter(|answer| !answer.is_empty())
+                .map(str::to_owned)
+                .collect::<Vec<_>>(),
+            columns => {
+                let mut answers = columns.iter().map(|&index| cell(index).to_owned()).collect::<Vec<_>>();
+                // Tests with fewer answers leave the last columns empty
+                while answers.last().is_some_and(String::is_empty) {
+                    answers.pop();
+                }
+                answers
+            }
+        };
+
+        let (right_answer_id, ambiguous) = right_answer_id(cell(self.right_answer), &answers);
+        let test_form = TestForm {
+            key: optional(self.key),
+            description: cell(self.description).to_owned(),
+            right_answer_id,
+            answers,
+            image_path: optional(self.image),
+            image_data: None,
+            category: optional(self.category),
+            explanation: optional(self.explanation),
+        };
+
+        (test_form, ambiguous)
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+/// The correct answer is either its text or its number starting from 1, the text is matched first,
+/// so the answers can be numbers too. `0` is left for the validation to report.
+/// A cell which is the text of an answer and the number of another one comes with both of them
+fn right_answer_id(cell: &str, answers: &[String]) -> (i32, Option<(i32, i32)>) {
+    let number = cell
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.fract() == 0.0)
+        .map(|number| number as i32);
+
+    match answers.iter().position(|answer| answer == cell) {
+        Some(index) => {
+            let text = index as i32 + 1;
+            match number {
+                Some(number) if number != text && (1..=answers.len() as i32).contains(&number) => {
+                    (text, Some((number, text)))
+                }
+                _ => (text, None),
+            }
+        }
+        None => (number.unwrap_or(0), None),
+    }
+}
+
+/// The first row is the header, blank rows are skipped. Every row comes with its 1-based number in the sheet
+fn test_forms(mut rows: impl Iterator<Item = (usize, Vec<String>)>) -> anyhow::Result<(Vec<TestForm>, SheetSource)> {
+    let (_, header) = rows.next().ok_or_else(|| anyhow!("there is no header row"))?;
+    let columns = Columns::from_header(&header)?;
+    let mut source = SheetSource {
+        columns: columns.headers(&header),
+        ..SheetSource::default()
+    };
+
+    let test_forms = rows
+        .filter(|(_, row)| row.iter().any(|cell| !cell.trim().is_empty()))
+        .enumerate()
+        .map(|(test, (number, row))| {
+            source.rows.push(number);
+            let (test_form, ambiguous) = columns.test_form(&row);
+            if let Some(ambiguous) = ambiguous {
+                source.ambiguous_right_answers.insert(test, ambiguous);
+            }
+            test_form
+        })
+        .collect();
+
+    Ok((test_forms, source))
+}
+
+pub fn read_csv(path: &Path) -> anyhow::Result<(Vec<TestForm>, SheetSource)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    let rows = reader
+        .records()
+        .map(|record| {
+            // A quoted cell can span lines, so a record is numbered by the line it starts on
+            record.map(|record| {
+                let line = record.position().map_or(0, |position| position.line() as usize);
+                (line, record.iter().map(str::to_owned).collect::<Vec<_>>())
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    test_forms(rows.into_iter()).with_context(|| format!("failed to read {}", path.display()))
+}
+
+/// Reads the first sheet of an XLSX, XLS or ODS workbook
+pub fn read_spreadsheet(path: &Path) -> anyhow::Result<(Vec<TestForm>, SheetSource)> {
+    let mut workbook = open_workbook_auto(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| anyhow!("{} has no sheets", path.display()))??;
+    // The range starts at the first used cell, which isn't always the first row
+    let first_row = range.start().map_or(0, |(row, _)| row as usize) + 1;
+    let rows = range.rows().enumerate().map(|(index, row)| {
+        (
+            first_row + index,
+            row.iter().map(|cell| cell.to_string()).collect::<Vec<_>>(),
+        )
+    });
+
+    test_forms(rows).with_context(|| format!("failed to read {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cache::QuestionCache,
+        db::{get_tests, insert_tests_to_db, testing::*, ImportOptions},
+        image_pipeline::{ImagePipeline, ImagePipelineConfig},
+    };
+
+    fn rows(rows: &[&[&str]]) -> impl Iterator<Item = (usize, Vec<String>)> {
+        rows.iter()
+            .enumerate()
+            .map(|(index, row)| (index + 1, row.iter().map(|cell| cell.to_string()).collect::<Vec<_>>()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn test_forms_reads_numbered_answer_columns_in_any_order() {
+        let (forms, source) = test_forms(rows(&[
+            &[
+                "Answer 2",
+                "Description",
+                "Answer 1",
+                "Answer 3",
+                "Correct answer",
+                "Category",
+            ],
+            &["No", "Is it?", "Yes", "", "Yes", "Signs"],
+            &["", "", "", "", "", ""],
+            &["B", "Which?", "A", "C", "3", ""],
+        ]))
+        .unwrap();
+
+        assert_eq!(forms.len(), 2);
+        assert_eq!(forms[0].answers, vec!["Yes", "No"]);
+        assert_eq!(forms[0].right_answer_id, 1);
+        assert_eq!(forms[0].category.as_deref(), Some("Signs"));
+        assert_eq!(forms[1].answers, vec!["A", "B", "C"]);
+        assert_eq!(forms[1].right_answer_id, 3);
+        assert_eq!(forms[1].category, None);
+        assert_eq!(source.rows, vec![2, 4]);
+        assert_eq!(source.columns["answers/0"], "Answer 1");
+        assert_eq!(source.columns["right_answer_id"], "Correct answer");
+        assert_eq!(source.columns.get("key"), None);
+    }
+
+    #[test]
+    fn test_forms_splits_a_single_answers_column_by_lines() {
+        let (forms, source) = test_forms(rows(&[
+            &["key", "description", "answers", "right_answer", "explanation"],
+            &["q1", "Is it?", "Yes\nNo\n", "Maybe", "Because"],
+        ]))
+        .unwrap();
+
+        assert_eq!(forms[0].key.as_deref(), Some("q1"));
+        assert_eq!(forms[0].answers, vec!["Yes", "No"]);
+        // An unknown answer is left for the validation to report
+        assert_eq!(forms[0].right_answer_id, 0);
+        assert_eq!(forms[0].explanation.as_deref(), Some("Because"));
+        assert_eq!(source.columns["answers"], "answers");
+    }
+
+    #[test]
+    fn test_forms_matches_numeric_answers_by_their_text_first() {
+        let (forms, source) = test_forms(rows(&[
+            &["description", "answers", "right_answer"],
+            &["Speed limit?", "30\n50\n70", "50"],
+            &["Lanes?", "3\n1\n2", "2"],
+            &["Exits?", "4\n5", "2"],
+        ]))
+        .unwrap();
+
+        assert_eq!(forms[0].right_answer_id, 2);
+        assert_eq!(forms[1].right_answer_id, 3);
+        assert_eq!(forms[2].right_answer_id, 2);
+        assert_eq!(source.ambiguous_right_answers.get(&0), None);
+        // `2` is the text of the third answer and the number of the second one
+        assert_eq!(source.ambiguous_right_answers.get(&1), Some(&(2, 3)));
+        assert_eq!(source.ambiguous_right_answers.get(&2), None);
+    }
+
+    #[test]
+    fn test_forms_requires_the_description_and_answer_columns() {
+        assert!(test_forms(rows(&[&["description", "right_answer"]])).is_err());
+        assert!(test_forms(rows(&[&["answers", "right_answer"]])).is_err());
+    }
+
+    #[test]
+    fn insert_tests_to_db_reads_csv_files() {
+        let (pool, path) = temp_db();
+        let dir = crate::bundle::TempDir::new("drive-tests-csv-test").unwrap();
+        let csv_path = dir.path().join("tests.csv");
+        std::fs::write(
+            &csv_path,
+            "key,description,answer 1,answer 2,correct answer,category,explanation\n\
+             q1,\"Is it, really?\",Yes,No,2,Signs,Because\n",
+        )
+        .unwrap();
+
+        let pipeline = ImagePipeline::new(ImagePipelineConfig::default());
+        let report = insert_tests_to_db(
+            &csv_path,
+            &pool,
+            &QuestionCache::new(),
+            &pipeline,
+            ImportOptions::default(),
+        )
+        .unwrap();
+        let imported = get_tests(&pool).unwrap();
+
+        remove_temp_db(pool, path);
+        assert_eq!(report.summary.created, 1);
+        assert_eq!(imported[0].description, "Is it, really?");
+        assert_eq!(imported[0].right_answer_id, 2);
+        assert_eq!(imported[0].category.as_deref(), Some("Signs"));
+        assert_eq!(imported[0].explanation.as_deref(), Some("Because"));
+    }
+
+    #[test]
+    fn insert_tests_to_db_reports_the_rows_and_columns_of_csv_files() {
+        let (pool, path) = temp_db();
+        let dir = crate::bundle::TempDir::new("drive-tests-csv-issues-test").unwrap();
+        let csv_path = dir.path().join("tests.csv");
+        std::fs::write(
+            &csv_path,
+            "Description,Answer 1,Answer 2,Correct answer\n\
+             \"Is it,\nreally?\",Yes,No,3\n\
+             ,,,\n\
+             Is it?,Yes,Yes,1\n\
+             Lanes?,2,1,1\n",
+        )
+        .unwrap();
+
+        let pipeline = ImagePipeline::new(ImagePipelineConfig::default());
+        let report = insert_tests_to_db(
+            &csv_path,
+            &pool,
+            &QuestionCache::new(),
+            &pipeline,
+            ImportOptions::default(),
+        )
+        .unwrap();
+
+        remove_temp_db(pool, path);
+        let issues = report
+            .validation
+            .issues
+            .iter()
+            .map(|issue| issue.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            issues,
+            vec![
+                "error at row 2, column `Correct answer`: 3 isn't within the 2 answers, it starts from 1",
+                "error at row 5, column `Answer 2`: the answer repeats the answer 1",
+                "error at row 6, column `Correct answer`: 1 is the number of the answer 1 and the text of the answer 2",
+            ]
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use quick_xml::events::{BytesCData, BytesStart, BytesText};
+
+pub fn local_name(element: &BytesStart) -> String {
+    String::from_utf8_lossy(element.local_name().as_ref()).into_owned()
+}
+
+pub fn attribute(element: &BytesStart, name: &str) -> anyhow::Result<Option<String>> {
+    match element.try_get_attribute(name)? {
+        // `unescape_value` is missing when the `encoding` feature is on, the files are UTF-8 anyway
+        Some(attribute) => {
+            let value = std::str::from_utf8(&attribute.value)?;
+            Ok(Some(quick_xml::escape::unescape(value)?.into_owned()))
+        }
+        None => Ok(None),
+    }
+}
+
+pub fn text(text: &BytesText) -> anyhow::Result<String> {
+    Ok(text.unescape()?.into_owned())
+}
+
+pub fn cdata(cdata: BytesCData) -> anyhow::Result<String> {
+    Ok(String::from_utf8(cdata.into_inner().into_owned())?)
+}
+
+/// Escapes text and attribute values
+pub fn escape(text: &str) -> String {
+    quick_xml::escape::escape(text).into_owned()
+}
+
+/// Collapses the whitespace inside every line and drops the blank lines, markup indentation isn't part of the text
+pub fn normalize_text(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,320 @@
+use anyhow::Context;
+use image::{
+    imageops::FilterType, io::Reader as ImageReader, DynamicImage, GenericImageView, ImageFormat, ImageOutputFormat,
+};
+use log::debug;
+use std::{env, fmt, io::Cursor, str::FromStr};
+
+/// The largest side of an image WebP can encode
+const MAX_IMAGE_SIDE: u32 = 16_383;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// JPEG sources stay JPEG, everything else becomes PNG
+    Auto,
+    Jpeg,
+    Png,
+    WebP,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(format: &str) -> anyhow::Result<Self> {
+        match format.to_lowercase().as_str() {
+            "auto" => Ok(OutputFormat::Auto),
+            "jpeg" | "jpg" => Ok(OutputFormat::Jpeg),
+            "png" => Ok(OutputFormat::Png),
+            "webp" => Ok(OutputFormat::WebP),
+            _ => Err(anyhow!("{} isn't a supported image format", format)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ImagePipelineConfig {
+    pub format: OutputFormat,
+    /// Quality of the lossy formats, from 1 to 100
+    pub quality: u8,
+    pub max_width: u32,
+    pub max_height: u32,
+    pub thumbnail_width: u32,
+    pub thumbnail_height: u32,
+    /// Whether to store a variant with the doubled max dimensions for high density screens
+    pub retina: bool,
+}
+
+impl Default for ImagePipelineConfig {
+    fn default() -> Self {
+        ImagePipelineConfig {
+            format: OutputFormat::Auto,
+            quality: 75,
+            max_width: 800,
+            max_height: 600,
+            thumbnail_width: 200,
+            thumbnail_height: 150,
+            retina: true,
+        }
+    }
+}
+
+impl ImagePipelineConfig {
+    /// Reads the `IMAGE_*` environment variables, the unset ones keep their default values
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut config = ImagePipelineConfig::default();
+
+        if let Ok(format) = env::var("IMAGE_FORMAT") {
+            config.format = format.parse()?;
+        }
+        if let Ok(quality) = env::var("IMAGE_QUALITY") {
+            config.quality = quality.parse().with_context(|| "IMAGE_QUALITY must be a number")?;
+            if !(1..=100).contains(&config.quality) {
+                return Err(anyhow!("IMAGE_QUALITY must be from 1 to 100"));
+            }
+        }
+        if let Ok(size) = env::var("IMAGE_MAX_SIZE") {
+            let (width, height) = parse_size(&size).with_context(|| "IMAGE_MAX_SIZE must look like 800x600")?;
+            config.max_width = width;
+            config.max_height = height;
+        }
+        if let Ok(size) = env::var("IMAGE_THUMBNAIL_SIZE") {
+            let (width, height) = parse_size(&size).with_context(|| "IMAGE_THUMBNAIL_SIZE must look like 200x150")?;
+            config.thumbnail_width = width;
+            config.thumbnail_height = height;
+        }
+        if let Ok(retina) = env::var("IMAGE_RETINA") {
+            config.retina = retina.parse().with_context(|| "IMAGE_RETINA must be true or false")?;
+        }
+
+        Ok(config)
+    }
+}
+
+/// Both sides are from 1 to `MAX_IMAGE_SIDE`
+fn parse_size(size: &str) -> anyhow::Result<(u32, u32)> {
+    let side = |side: &str| -> anyhow::Result<u32> {
+        match side.trim().parse()? {
+            side @ 1..=MAX_IMAGE_SIDE => Ok(side),
+            side => Err(anyhow!("{} isn't from 1 to {}", side, MAX_IMAGE_SIDE)),
+        }
+    };
+
+    let mut parts = size.split('x');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(width), Some(height), None) => Ok((side(width)?, side(height)?)),
+        _ => Err(anyhow!("{} isn't a size", size)),
+    }
+}
+
+/// An image ready to be stored with all its variants
+pub struct ProcessedImage {
+    pub image: Vec<u8>,
+    pub thumbnail: Vec<u8>,
+    /// `None` when the source is too small to have a bigger variant than `image`
+    pub retina: Option<Vec<u8>>,
+    pub source_size: usize,
+}
+
+/// Sizes of the processed images, logged after an import
+#[derive(Default, Debug)]
+pub struct PipelineReport {
+    pub images: usize,
+    pub source_bytes: usize,
+    pub image_bytes: usize,
+    pub variants_bytes: usize,
+}
+
+impl PipelineReport {
+    pub fn add(&mut self, image: &ProcessedImage) {
+        self.images += 1;
+        self.source_bytes += image.source_size;
+        self.image_bytes += image.image.len();
+        self.variants_bytes += image.thumbnail.len() + image.retina.as_ref().map_or(0, Vec::len);
+    }
+
+    pub fn bytes_saved(&self) -> i64 {
+        self.source_bytes as i64 - self.image_bytes as i64
+    }
+}
+
+impl fmt::Display for PipelineReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} images processed: {} source bytes stored as {} bytes ({} bytes saved) plus {} bytes of variants",
+            self.images,
+            self.source_bytes,
+            self.image_bytes,
+            self.bytes_saved(),
+            self.variants_bytes
+        )
+    }
+}
+
+pub struct ImagePipeline {
+    config: ImagePipelineConfig,
+}
+
+impl ImagePipeline {
+    pub fn new(config: ImagePipelineConfig) -> Self {
+        ImagePipeline { config }
+    }
+
+    pub fn config(&self) -> &ImagePipelineConfig {
+        &self.config
+    }
+
+    /// Decodes the image whatever its extension is, resizes it and re-encodes it. Re-encoding drops EXIF and
+    /// any other metadata, so the EXIF orientation is applied to the pixels beforehand
+    pub fn process(&self, data: &[u8]) -> anyhow::Result<ProcessedImage> {
+        let reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+        let source_format = reader
+            .format()
+            .ok_or_else(|| anyhow!("the image format isn't recognized"))?;
+        let source = apply_orientation(reader.decode()?, exif_orientation(data));
+
+        let output_format = match self.config.format {
+            OutputFormat::Auto if source_format == ImageFormat::Jpeg => OutputFormat::Jpeg,
+            OutputFormat::Auto => OutputFormat::Png,
+            format => format,
+        };
+        debug!(
+            "Processing {:?} {}x{} image into {:?}",
+            source_format,
+            source.width(),
+            source.height(),
+            output_format
+        );
+
+        let image = fit(&source, self.config.max_width, self.config.max_height);
+        let thumbnail = fit(&source, self.config.thumbnail_width, self.config.thumbnail_height);
+        let retina = if self.config.retina && (source.width() > image.width() || source.height() > image.height()) {
+            let retina = fit(
+                &source,
+                self.config.max_width.saturating_mul(2),
+                self.config.max_height.saturating_mul(2),
+            );
+            Some(self.encode(&retina, output_format)?)
+        } else {
+            None
+        };
+
+        Ok(ProcessedImage {
+            image: self.encode(&image, output_format)?,
+            thumbnail: self.encode(&thumbnail, output_format)?,
+            retina,
+            source_size: data.len(),
+        })
+    }
+
+    fn encode(&self, image: &DynamicImage, format: OutputFormat) -> anyhow::Result<Vec<u8>> {
+        let mut buffer: Vec<u8> = Vec::new();
+
+        match format {
+            OutputFormat::Jpeg => {
+                // JPEG doesn't support the alpha channel
+                DynamicImage::ImageRgb8(image.to_rgb8())
+                    .write_to(&mut buffer, ImageOutputFormat::Jpeg(self.config.quality))?;
+            }
+            OutputFormat::Png | OutputFormat::Auto => image.write_to(&mut buffer, ImageOutputFormat::Png)?,
+            OutputFormat::WebP => {
+                let rgba = image.to_rgba8();
+                let encoded =
+                    webp::Encoder::from_rgba(&rgba, image.width(), image.height()).encode(self.config.quality as f32);
+                buffer.extend_from_slice(&encoded);
+            }
+        }
+
+        Ok(buffer)
+    }
+}
+
+/// Downscales the image to fit into the bounds keeping its aspect ratio, images are never upscaled
+fn fit(image: &DynamicImage, max_width: u32, max_height: u32) -> DynamicImage {
+    if image.width() <= max_width && image.height() <= max_height {
+        return image.clone();
+    }
+
+    image.resize(max_width, max_height, FilterType::Lanczos3)
+}
+
+fn exif_orientation(data: &[u8]) -> u32 {
+    exif::Reader::new()
+        .read_from_container(&mut Cursor::new(data))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .unwrap_or(1)
+}
+
+fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbImage;
+
+    fn encoded_image(width: u32, height: u32, format: ImageOutputFormat) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(width, height))
+            .write_to(&mut buffer, format)
+            .unwrap();
+        buffer
+    }
+
+    #[test]
+    fn process_detects_the_format_by_content_and_resizes() {
+        let pipeline = ImagePipeline::new(ImagePipelineConfig::default());
+        let processed = pipeline
+            .process(&encoded_image(2000, 1000, ImageOutputFormat::Jpeg(90)))
+            .unwrap();
+
+        let image = image::load_from_memory(&processed.image).unwrap();
+        assert_eq!(image::guess_format(&processed.image).unwrap(), ImageFormat::Jpeg);
+        assert_eq!((image.width(), image.height()), (800, 400));
+
+        let thumbnail = image::load_from_memory(&processed.thumbnail).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (200, 100));
+
+        let retina = image::load_from_memory(processed.retina.as_ref().unwrap()).unwrap();
+        assert_eq!((retina.width(), retina.height()), (1600, 800));
+    }
+
+    #[test]
+    fn process_keeps_small_images_and_skips_the_retina_variant() {
+        let pipeline = ImagePipeline::new(ImagePipelineConfig {
+            format: OutputFormat::WebP,
+            ..ImagePipelineConfig::default()
+        });
+        let processed = pipeline
+            .process(&encoded_image(100, 50, ImageOutputFormat::Png))
+            .unwrap();
+
+        assert_eq!(image::guess_format(&processed.image).unwrap(), ImageFormat::WebP);
+        assert!(processed.retina.is_none());
+    }
+
+    #[test]
+    fn parse_size_requires_width_and_height() {
+        assert_eq!(parse_size("800x600").unwrap(), (800, 600));
+        assert!(parse_size("800").is_err());
+        assert!(parse_size("800x600x1").is_err());
+        assert!(parse_size("0x0").is_err());
+        assert!(parse_size("800x0").is_err());
+        assert!(parse_size("5000000000x600").is_err());
+        assert!(parse_size("100000x600").is_err());
+    }
+}
@@ -0,0 +1,329 @@
+//! Item response theory for the adaptive mode: the 1PL/2PL logistic models, the ability estimate of a learner
+//! and the calibration of the tests parameters from the recorded answers
+use std::{env, str::FromStr, time::Duration};
+
+use crate::lti::PASS_MARK;
+
+const DEFAULT_TARGET_SE: f64 = 0.3;
+const DEFAULT_CALIBRATION_SECS: u64 = 24 * 60 * 60;
+const DEFAULT_MIN_ANSWERS: usize = 20;
+/// Abilities and difficulties are kept within it
+const SCALE_LIMIT: f64 = 4.0;
+/// Points the posterior of an ability is evaluated at over the scale
+const GRID_POINTS: usize = 81;
+const CALIBRATION_ROUNDS: usize = 20;
+const NEWTON_STEPS: usize = 5;
+/// Standard deviations of the priors keeping the parameters of the tests with few or one-sided answers finite
+const DIFFICULTY_PRIOR_SD: f64 = 2.0;
+const DISCRIMINATION_PRIOR_SD: f64 = 0.5;
+const MIN_DISCRIMINATION: f64 = 0.2;
+const MAX_DISCRIMINATION: f64 = 4.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IrtModel {
+    /// Rasch, every test discriminates the same
+    OnePl,
+    TwoPl,
+}
+
+impl FromStr for IrtModel {
+    type Err = anyhow::Error;
+
+    fn from_str(model: &str) -> anyhow::Result<Self> {
+        match model {
+            "1pl" => Ok(IrtModel::OnePl),
+            "2pl" => Ok(IrtModel::TwoPl),
+            model => Err(anyhow!("Unknown IRT model {}, expected 1pl or 2pl", model)),
+        }
+    }
+}
+
+/// The adaptive mode settings, read from the `IRT_*` environment variables
+#[derive(Clone, Debug)]
+pub struct IrtConfig {
+    pub model: IrtModel,
+    /// The adaptive mode stops once the standard error of the ability is at most it
+    pub target_se: f64,
+    /// How often the calibration job runs, it doesn't run in the background when it's zero
+    pub calibration_interval: Duration,
+    /// Answers a test needs to be calibrated, the others keep the default parameters
+    pub min_answers: usize,
+}
+
+impl Default for IrtConfig {
+    fn default() -> Self {
+        IrtConfig {
+            model: IrtModel::TwoPl,
+            target_se: DEFAULT_TARGET_SE,
+            calibration_interval: Duration::from_secs(DEFAULT_CALIBRATION_SECS),
+            min_answers: DEFAULT_MIN_ANSWERS,
+        }
+    }
+}
+
+impl IrtConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        fn parse<T: FromStr>(name: &str, default: T) -> anyhow::Result<T> {
+            match env::var(name) {
+                Ok(value) => value
+                    .parse::<T>()
+                    .map_err(|_| anyhow!("{} isn't valid: {}", name, value)),
+                Err(_) => Ok(default),
+            }
+        }
+        let default = IrtConfig::default();
+
+        Ok(IrtConfig {
+            model: parse("IRT_MODEL", default.model)?,
+            target_se: parse("IRT_TARGET_SE", default.target_se)?,
+            calibration_interval: Duration::from_secs(parse(
+                "IRT_CALIBRATION_SECS",
+                default.calibration_interval.as_secs(),
+            )?),
+            min_answers: parse("IRT_MIN_ANSWERS", default.min_answers)?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ItemParams {
+    pub difficulty: f64,
+    pub discrimination: f64,
+}
+
+impl Default for ItemParams {
+    /// An average test, used until the test is calibrated
+    fn default() -> Self {
+        ItemParams {
+            difficulty: 0.0,
+            discrimination: 1.0,
+        }
+    }
+}
+
+impl ItemParams {
+    /// The probability of a right answer at the ability
+    pub fn probability(&self, ability: f64) -> f64 {
+        1.0 / (1.0 + (-self.discrimination * (ability - self.difficulty)).exp())
+    }
+
+    /// Fisher information of the test at the ability, the adaptive mode serves the most informative test
+    pub fn information(&self, ability: f64) -> f64 {
+        let probability = self.probability(ability);
+        self.discrimination.powi(2) * probability * (1.0 - probability)
+    }
+}
+
+/// The posterior of an ability over a grid of the scale with a standard normal prior
+#[derive(Clone, Debug)]
+pub struct AbilityEstimate {
+    weights: Vec<f64>,
+}
+
+fn grid() -> impl Iterator<Item = f64> {
+    (0..GRID_POINTS).map(|point| -SCALE_LIMIT + 2.0 * SCALE_LIMIT * point as f64 / (GRID_POINTS - 1) as f64)
+}
+
+impl AbilityEstimate {
+    /// The estimate from the answers to the tests, it's the prior without answers
+    pub fn new<'a>(answers: impl IntoIterator<Item = (&'a ItemParams, bool)> + Clone) -> Self {
+        let weights = grid()
+            .map(|ability| {
+                let likelihood = answers
+                    .clone()
+                    .into_iter()
+                    .map(|(params, correct)| {
+                        let probability = params.probability(ability);
+                        match correct {
+                            true => probability.ln(),
+                            false => (1.0 - probability).ln(),
+                        }
+                    })
+                    .sum::<f64>();
+                likelihood - ability.powi(2) / 2.0
+            })
+            .collect::<Vec<_>>();
+        // Normalized in the log space first, so a long run of answers doesn't underflow
+        let max = weights.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let weights = weights.iter().map(|weight| (weight - max).exp()).collect::<Vec<_>>();
+        let total = weights.iter().sum::<f64>();
+
+        AbilityEstimate {
+            weights: weights.iter().map(|weight| weight / total).collect(),
+        }
+    }
+
+    /// The expected a posteriori ability
+    pub fn ability(&self) -> f64 {
+        grid()
+            .zip(self.weights.iter())
+            .map(|(ability, weight)| ability * weight)
+            .sum()
+    }
+
+    pub fn standard_error(&self) -> f64 {
+        let mean = self.ability();
+        grid()
+            .zip(self.weights.iter())
+            .map(|(ability, weight)| (ability - mean).powi(2) * weight)
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// The posterior probability that the ability is at least `ability`
+    pub fn probability_above(&self, ability: f64) -> f64 {
+        grid()
+            .zip(self.weights.iter())
+            .filter(|(point, _)| *point >= ability)
+            .map(|(_, weight)| weight)
+            .sum()
+    }
+}
+
+/// The ability expected to answer right the pass mark of the tests, `None` without tests
+pub fn pass_ability(items: &[ItemParams]) -> Option<f64> {
+    if items.is_empty() {
+        return None;
+    }
+    let expected_score = |ability: f64| items.iter().map(|params| params.probability(ability)).sum::<f64>();
+    let pass_score = items.len() as f64 * PASS_MARK as f64 / 100.0;
+
+    // The expected score grows with the ability
+    let (mut low, mut high) = (-2.0 * SCALE_LIMIT, 2.0 * SCALE_LIMIT);
+    for _ in 0..50 {
+        let middle = (low + high) / 2.0;
+        match expected_score(middle) < pass_score {
+            true => low = middle,
+            false => high = middle,
+        }
+    }
+    Some((low + high) / 2.0)
+}
+
+/// An answer of a learner to a test, both given as indexes
+#[derive(Clone, Copy, Debug)]
+pub struct Response {
+    pub learner: usize,
+    pub item: usize,
+    pub correct: bool,
+}
+
+/// Fits the parameters of `items` tests by marginal maximum likelihood: the expected answers at every ability
+/// over the posteriors of the learners alternate with Newton steps on the parameters of every test
+pub fn calibrate(responses: &[Response], learners: usize, items: usize, model: IrtModel) -> Vec<ItemParams> {
+    let mut by_learner = vec![Vec::new(); learners];
+    let mut by_item = vec![Vec::new(); items];
+    for response in responses {
+        by_learner[response.learner].push((response.item, response.correct));
+        by_item[response.item].push(response.correct);
+    }
+
+    // Starts from the difficulties implied by the share of the right answers
+    let mut params = by_item
+        .iter()
+        .map(|answers| {
+            let right = answers.iter().filter(|correct| **correct).count() as f64;
+            let share = (right + 0.5) / (answers.len() as f64 + 1.0);
+            ItemParams {
+                difficulty: (-(share / (1.0 - share)).ln()).clamp(-SCALE_LIMIT, SCALE_LIMIT),
+                discrimination: 1.0,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    for _ in 0..CALIBRATION_ROUNDS {
+        // Expected answers and right answers of every test at every point of the grid
+        let mut expected = vec![vec![(0.0, 0.0); GRID_POINTS]; items];
+        for answers in by_learner.iter() {
+            let estimate = AbilityEstimate::new(answers.iter().map(|(item, correct)| (&params[*item], *correct)));
+            for (item, correct) in answers {
+                for (point, weight) in estimate.weights.iter().enumerate() {
+                    expected[*item][point].0 += weight;
+                    expected[*item][point].1 += weight * *correct as u8 as f64;
+                }
+            }
+        }
+
+        for (item_params, expected) in params.iter_mut().zip(expected.iter()) {
+            for _ in 0..NEWTON_STEPS {
+                let ItemParams {
+                    difficulty,
+                    discrimination,
+                } = *item_params;
+                let (mut difficulty_gradient, mut difficulty_curvature) = (
+                    -difficulty / DIFFICULTY_PRIOR_SD.powi(2),
+                    -1.0 / DIFFICULTY_PRIOR_SD.powi(2),
+                );
+                let (mut discrimination_gradient, mut discrimination_curvature) = (
+                    -(discrimination - 1.0) / DISCRIMINATION_PRIOR_SD.powi(2),
+                    -1.0 / DISCRIMINATION_PRIOR_SD.powi(2),
+                );
+                for (ability, (answers, right)) in grid().zip(expected.iter()) {
+                    let probability = item_params.probability(ability);
+                    let residual = right - answers * probability;
+                    let variance = answers * probability * (1.0 - probability);
+                    difficulty_gradient -= discrimination * residual;
+                    difficulty_curvature -= discrimination.powi(2) * variance;
+                    discrimination_gradient += (ability - difficulty) * residual;
+                    discrimination_curvature -= (ability - difficulty).powi(2) * variance;
+                }
+
+                item_params.difficulty =
+                    (difficulty - difficulty_gradient / difficulty_curvature).clamp(-SCALE_LIMIT, SCALE_LIMIT);
+                if model == IrtModel::TwoPl {
+                    item_params.discrimination = (discrimination - discrimination_gradient / discrimination_curvature)
+                        .clamp(MIN_DISCRIMINATION, MAX_DISCRIMINATION);
+                }
+            }
+        }
+    }
+
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn calibration_recovers_the_order_of_the_difficulties_and_the_ability_converges() {
+        let truth = [-2.0, -1.0, 0.0, 1.0, 2.0]
+            .iter()
+            .map(|difficulty| ItemParams {
+                difficulty: *difficulty,
+                discrimination: 1.0,
+            })
+            .collect::<Vec<_>>();
+        let mut rng = StdRng::seed_from_u64(7);
+        let learners = 400;
+        let mut responses = Vec::new();
+        for learner in 0..learners {
+            let ability = rng.gen_range(-2.0, 2.0);
+            for (item, params) in truth.iter().enumerate() {
+                let correct = rng.gen::<f64>() < params.probability(ability);
+                responses.push(Response { learner, item, correct });
+            }
+        }
+
+        for model in [IrtModel::OnePl, IrtModel::TwoPl] {
+            let fitted = calibrate(&responses, learners, truth.len(), model);
+            assert!(fitted.windows(2).all(|pair| pair[0].difficulty < pair[1].difficulty));
+            for (fitted, truth) in fitted.iter().zip(truth.iter()) {
+                assert!((fitted.difficulty - truth.difficulty).abs() < 0.6, "{:?}", fitted);
+            }
+        }
+
+        let prior = AbilityEstimate::new(std::iter::empty());
+        let strong = AbilityEstimate::new(truth.iter().cycle().take(40).map(|params| (params, true)));
+        assert!(prior.ability().abs() < 1e-9);
+        assert!((prior.standard_error() - 1.0).abs() < 0.01);
+        assert!(strong.ability() > 2.0);
+        assert!(strong.standard_error() < prior.standard_error());
+
+        let pass_ability = pass_ability(&truth).unwrap();
+        assert!((truth.iter().map(|params| params.probability(pass_ability)).sum::<f64>() - 4.0).abs() < 1e-6);
+        assert!(strong.probability_above(pass_ability) > 0.9);
+        assert!(prior.probability_above(pass_ability) < 0.5);
+    }
+}
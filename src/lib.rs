@@ -1,12 +1,27 @@
+// diesel 1.4 derives and `table!` expand into impl blocks that newer compilers flag as non-local
+#![allow(non_local_definitions)]
+
 #[macro_use]
 extern crate diesel;
 #[macro_use]
+extern crate diesel_migrations;
+#[macro_use]
 extern crate anyhow;
 
+pub mod achievements;
+pub mod bundle;
+pub mod cache;
 pub mod db;
+pub mod formats;
+pub mod image_pipeline;
+pub mod irt;
+pub mod lti;
+pub mod server;
 pub mod utils;
 pub mod web;
+pub mod xapi;
 
+pub use server::{build_app, AppConfig, Server};
 pub use web::*;
 
 pub use db::model;
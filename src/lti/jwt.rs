@@ -0,0 +1,163 @@
+//! RS256 JSON Web Tokens and Key Sets, the only kind LTI 1.3 uses
+use openssl::{
+    base64,
+    bn::BigNum,
+    hash::MessageDigest,
+    pkey::{HasPublic, PKey, PKeyRef, Private, Public},
+    rsa::Rsa,
+    sha,
+    sign::{Signer, Verifier},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+pub fn base64url_encode(data: &[u8]) -> String {
+    base64::encode_block(data)
+        .trim_end_matches('=')
+        .replace('+', "-")
+        .replace('/', "_")
+}
+
+pub fn base64url_decode(text: &str) -> anyhow::Result<Vec<u8>> {
+    let mut standard = text.replace('-', "+").replace('_', "/");
+    while !standard.len().is_multiple_of(4) {
+        standard.push('=');
+    }
+
+    Ok(base64::decode_block(&standard)?)
+}
+
+#[derive(Deserialize)]
+pub struct Header {
+    pub alg: String,
+    pub kid: Option<String>,
+}
+
+/// An RSA public key of a key set
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Jwk {
+    pub kty: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alg: Option<String>,
+    #[serde(rename = "use", default, skip_serializing_if = "Option::is_none")]
+    pub key_use: Option<String>,
+    pub n: String,
+    pub e: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+impl Jwk {
+    /// The public part of the key, identified by `kid`
+    pub fn from_key<T: HasPublic>(key: &PKeyRef<T>, kid: &str) -> anyhow::Result<Self> {
+        let rsa = key.rsa()?;
+        Ok(Jwk {
+            kty: "RSA".to_owned(),
+            kid: Some(kid.to_owned()),
+            alg: Some("RS256".to_owned()),
+            key_use: Some("sig".to_owned()),
+            n: base64url_encode(&rsa.n().to_vec()),
+            e: base64url_encode(&rsa.e().to_vec()),
+        })
+    }
+
+    pub fn public_key(&self) -> anyhow::Result<PKey<Public>> {
+        if self.kty != "RSA" {
+            return Err(anyhow!("{} keys aren't supported", self.kty));
+        }
+
+        let n = BigNum::from_slice(&base64url_decode(&self.n)?)?;
+        let e = BigNum::from_slice(&base64url_decode(&self.e)?)?;
+        Ok(PKey::from_rsa(Rsa::from_public_components(n, e)?)?)
+    }
+}
+
+impl JwkSet {
+    /// The key with the `kid`, the only key of the set when the token doesn't name one
+    pub fn find(&self, kid: Option<&str>) -> Option<&Jwk> {
+        match kid {
+            Some(kid) => self.keys.iter().find(|key| key.kid.as_deref() == Some(kid)),
+            None if self.keys.len() == 1 => self.keys.first(),
+            None => None,
+        }
+    }
+}
+
+/// Key id derived from the key itself, so it changes only when the key does
+pub fn key_id<T: HasPublic>(key: &PKeyRef<T>) -> anyhow::Result<String> {
+    Ok(hex::encode(&sha::sha256(&key.public_key_to_der()?)[..8]))
+}
+
+/// Decodes the header without checking anything, it tells which key verifies the token
+pub fn header(token: &str) -> anyhow::Result<Header> {
+    let header = token.split('.').next().unwrap_or_default();
+    Ok(serde_json::from_slice(&base64url_decode(header)?)?)
+}
+
+pub fn sign(claims: &Value, key: &PKey<Private>, kid: &str) -> anyhow::Result<String> {
+    let header = json!({ "typ": "JWT", "alg": "RS256", "kid": kid });
+    let message = format!(
+        "{}.{}",
+        base64url_encode(header.to_string().as_bytes()),
+        base64url_encode(claims.to_string().as_bytes())
+    );
+
+    let mut signer = Signer::new(MessageDigest::sha256(), key)?;
+    signer.update(message.as_bytes())?;
+    Ok(format!("{}.{}", message, base64url_encode(&signer.sign_to_vec()?)))
+}
+
+/// Checks the RS256 signature and returns the claims, validating them is up to the caller
+pub fn verify(token: &str, key: &PKey<Public>) -> anyhow::Result<Value> {
+    let mut parts = token.split('.');
+    let (header, claims, signature) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(header), Some(claims), Some(signature), None) => (header, claims, signature),
+        _ => return Err(anyhow!("the token isn't a signed JWT")),
+    };
+
+    let alg = self::header(token)?.alg;
+    if alg != "RS256" {
+        return Err(anyhow!("{} tokens aren't supported, only RS256 ones are", alg));
+    }
+
+    let mut verifier = Verifier::new(MessageDigest::sha256(), key)?;
+    verifier.update(format!("{}.{}", header, claims).as_bytes())?;
+    if !verifier.verify(&base64url_decode(signature)?)? {
+        return Err(anyhow!("the token signature is invalid"));
+    }
+
+    Ok(serde_json::from_slice(&base64url_decode(claims)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_tokens_are_verified_by_the_key_set_key_only() {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let other_key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let kid = key_id(&key).unwrap();
+        let key_set = JwkSet {
+            keys: vec![Jwk::from_key(&key, &kid).unwrap()],
+        };
+        let claims = json!({ "sub": "learner", "aud": ["tool"] });
+
+        let token = sign(&claims, &key, &kid).unwrap();
+        let public_key = key_set
+            .find(header(&token).unwrap().kid.as_deref())
+            .unwrap()
+            .public_key()
+            .unwrap();
+        let forged = sign(&claims, &other_key, &kid).unwrap();
+
+        assert_eq!(verify(&token, &public_key).unwrap(), claims);
+        assert!(verify(&forged, &public_key).is_err());
+        assert!(verify(&token[..token.len() - 4], &public_key).is_err());
+    }
+}
@@ -0,0 +1,679 @@
+//! LTI 1.3 tool: OIDC launches from an LMS, practice and exam sessions of its users
+//! and their scores sent back through the Assignment and Grade Services
+use actix_web::client::Client;
+use anyhow::Context;
+use openssl::pkey::{PKey, Private, Public};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::{
+    collections::HashMap,
+    env, fs,
+    sync::{Mutex, RwLock},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{model::User, utils};
+use jwt::JwkSet;
+
+pub mod jwt;
+
+/// How long a login waits for its launch
+const LOGIN_TTL: Duration = Duration::from_secs(10 * 60);
+const SESSION_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+/// Seconds the clocks of the platform and the tool may differ by
+const CLOCK_LEEWAY: u64 = 60;
+/// The key set is fetched again for an unknown key at most this often, so forged tokens can't flood the platform
+const KEYS_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+const DEFAULT_QUESTIONS: usize = 20;
+const SCORE_SCOPE: &str = "https://purl.imsglobal.org/spec/lti-ags/scope/score";
+const RESOURCE_LINK_REQUEST: &str = "LtiResourceLinkRequest";
+/// Percent of right answers an exam is passed with, the same as in the SCORM packages
+pub const PASS_MARK: usize = 80;
+
+/// The LMS platform the tool is registered with, read from the `LTI_*` environment variables
+#[derive(Clone)]
+pub struct LtiConfig {
+    /// `iss` of the platform id tokens
+    pub issuer: String,
+    /// Client id the platform gave the tool
+    pub client_id: String,
+    /// Only the launches of this deployment are accepted, the ones of any deployment are when it's `None`
+    pub deployment_id: Option<String>,
+    /// OIDC authorization endpoint of the platform
+    pub auth_url: String,
+    /// `/lti/launch` of the tool as registered in the platform, the logins for other targets are rejected
+    pub launch_url: String,
+    /// Key set the platform signs the id tokens with
+    pub jwks_url: String,
+    /// OAuth 2 token endpoint of the platform, the AGS access tokens come from it
+    pub token_url: String,
+    /// Key the tool signs its token requests with, its public part is served by `/lti/jwks`
+    pub key: PKey<Private>,
+    /// Questions of a session unless the launch asks for another number with the `questions` custom parameter
+    pub questions: usize,
+}
+
+impl LtiConfig {
+    /// `None` when `LTI_ISSUER` isn't set, the tool is off then
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let issuer = match env::var("LTI_ISSUER") {
+            Ok(issuer) => issuer,
+            Err(_) => return Ok(None),
+        };
+        let var = |name: &str| env::var(name).with_context(|| format!("{} has to be set for LTI", name));
+
+        let key_file = match env::var("LTI_KEY_FILE") {
+            Ok(key_file) => key_file,
+            Err(_) => utils::get_keys_paths()?.1,
+        };
+        let key = fs::read(&key_file).with_context(|| format!("failed to read {} LTI key", key_file))?;
+        let questions = match env::var("LTI_QUESTIONS") {
+            Ok(questions) => questions
+                .parse()
+                .with_context(|| format!("LTI_QUESTIONS isn't a number: {}", questions))?,
+            Err(_) => DEFAULT_QUESTIONS,
+        };
+
+        Ok(Some(LtiConfig {
+            issuer,
+            client_id: var("LTI_CLIENT_ID")?,
+            deployment_id: env::var("LTI_DEPLOYMENT_ID").ok(),
+            auth_url: var("LTI_AUTH_URL")?,
+            launch_url: var("LTI_LAUNCH_URL")?,
+            jwks_url: var("LTI_JWKS_URL")?,
+            token_url: var("LTI_TOKEN_URL")?,
+            key: PKey::private_key_from_pem(&key).with_context(|| format!("{} isn't a PEM private key", key_file))?,
+            questions,
+        }))
+    }
+}
+
+/// Third party initiated login the platform starts a launch with
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    pub iss: String,
+    pub login_hint: String,
+    pub target_link_uri: String,
+    pub lti_message_hint: Option<String>,
+    pub client_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct ResourceLink {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct AgsEndpoint {
+    lineitem: Option<String>,
+    #[serde(default)]
+    scope: Vec<String>,
+}
+
+/// Claims of a resource link launch id token
+#[derive(Deserialize)]
+pub struct LaunchClaims {
+    iss: String,
+    aud: Audience,
+    azp: Option<String>,
+    exp: u64,
+    iat: u64,
+    nonce: String,
+    /// Id of the user in the LMS
+    pub sub: String,
+    given_name: Option<String>,
+    family_name: Option<String>,
+    name: Option<String>,
+    #[serde(rename = "https://purl.imsglobal.org/spec/lti/claim/message_type")]
+    message_type: String,
+    #[serde(rename = "https://purl.imsglobal.org/spec/lti/claim/version")]
+    version: String,
+    #[serde(rename = "https://purl.imsglobal.org/spec/lti/claim/deployment_id")]
+    deployment_id: String,
+    #[serde(rename = "https://purl.imsglobal.org/spec/lti/claim/custom", default)]
+    custom: Map<String, Value>,
+    #[serde(rename = "https://purl.imsglobal.org/spec/lti/claim/resource_link")]
+    resource_link: Option<ResourceLink>,
+    #[serde(rename = "https://purl.imsglobal.org/spec/lti-ags/claim/endpoint")]
+    ags: Option<AgsEndpoint>,
+}
+
+impl LaunchClaims {
+    fn validate(&self, config: &LtiConfig, nonce: &str, now: u64) -> anyhow::Result<()> {
+        let audience = match &self.aud {
+            Audience::One(audience) => vec![audience.as_str()],
+            Audience::Many(audience) => audience.iter().map(String::as_str).collect(),
+        };
+
+        if self.iss != config.issuer {
+            return Err(anyhow!("{} isn't the registered platform", self.iss));
+        }
+        if !audience.contains(&config.client_id.as_str()) {
+            return Err(anyhow!("the token isn't issued for {} client", config.client_id));
+        }
+        if audience.len() > 1 && self.azp.as_deref() != Some(config.client_id.as_str()) {
+            return Err(anyhow!(
+                "the token has several audiences and isn't authorized for the tool"
+            ));
+        }
+        if self.exp + CLOCK_LEEWAY < now || self.iat > now + CLOCK_LEEWAY {
+            return Err(anyhow!("the token is expired or issued in the future"));
+        }
+        if self.nonce != nonce {
+            return Err(anyhow!("the token nonce isn't the one of the login"));
+        }
+        if config
+            .deployment_id
+            .as_ref()
+            .is_some_and(|id| *id != self.deployment_id)
+        {
+            return Err(anyhow!("{} deployment isn't the registered one", self.deployment_id));
+        }
+        if self.message_type != RESOURCE_LINK_REQUEST {
+            return Err(anyhow!("{} messages aren't supported", self.message_type));
+        }
+        if self.version != "1.3.0" {
+            return Err(anyhow!("LTI {} isn't supported", self.version));
+        }
+        if self.sub.is_empty() {
+            return Err(anyhow!("the launch is anonymous"));
+        }
+
+        Ok(())
+    }
+
+    fn custom(&self, name: &str) -> Option<String> {
+        match self.custom.get(name)? {
+            Value::String(value) => Some(value.clone()),
+            value => Some(value.to_string()),
+        }
+    }
+
+    /// Chosen by the `mode` custom parameter of the resource link, practice by default
+    pub fn mode(&self) -> SessionMode {
+        match self.custom("mode").as_deref() {
+            Some("exam") => SessionMode::Exam,
+            _ => SessionMode::Practice,
+        }
+    }
+
+    pub fn questions(&self, config: &LtiConfig) -> usize {
+        self.custom("questions")
+            .and_then(|questions| questions.parse().ok())
+            .filter(|questions| *questions > 0)
+            .unwrap_or(config.questions)
+    }
+
+    /// The line item to send the score to, when the platform lets the tool send scores
+    pub fn line_item(&self) -> Option<String> {
+        self.ags
+            .as_ref()
+            .filter(|ags| ags.scope.iter().any(|scope| scope == SCORE_SCOPE))
+            .and_then(|ags| ags.lineitem.clone())
+    }
+
+    /// The given and the family name of the user, the full name is split when the platform doesn't send them
+    pub fn names(&self) -> (String, String) {
+        if let (Some(given_name), Some(family_name)) = (self.given_name.as_ref(), self.family_name.as_ref()) {
+            return (given_name.clone(), family_name.clone());
+        }
+
+        match self.name.as_deref().map(str::trim).filter(|name| !name.is_empty()) {
+            Some(name) => match name.split_once(' ') {
+                Some((given_name, family_name)) => (given_name.to_owned(), family_name.trim().to_owned()),
+                None => (name.to_owned(), self.sub.clone()),
+            },
+            None => ("LTI".to_owned(), self.sub.clone()),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionMode {
+    /// The right answer and the explanation are shown after every answer
+    Practice,
+    /// Nothing but the final score is shown
+    Exam,
+}
+
+pub struct LtiSession {
+    pub user_id: i32,
+    pub user_name: String,
+    /// The LMS user the score is sent for
+    pub subject: String,
+    pub mode: SessionMode,
+    pub test_ids: Vec<i32>,
+    /// Whether the answered tests were answered right, in the order of `test_ids`
+    pub results: Vec<bool>,
+    pub line_item: Option<String>,
+    /// The resource link the session was launched from, sessions of the same link are the same activity
+    pub resource_link_id: Option<String>,
+    started_at: Instant,
+    /// The answer to the current test is being recorded
+    answering: bool,
+}
+
+impl LtiSession {
+    pub fn new(user: &User, claims: &LaunchClaims, test_ids: Vec<i32>) -> Self {
+        LtiSession {
+            user_id: user.id,
+            user_name: format!("{} {}", user.name, user.second_name),
+            subject: claims.sub.clone(),
+            mode: claims.mode(),
+            test_ids,
+            results: Vec::new(),
+            line_item: claims.line_item(),
+            resource_link_id: claims.resource_link.as_ref().map(|link| link.id.clone()),
+            started_at: Instant::now(),
+            answering: false,
+        }
+    }
+
+    /// Takes an answer to the current test, `false` for another test or while another answer is being recorded
+    pub fn begin_answer(&mut self, test_id: i32) -> bool {
+        if self.answering || self.current_test() != Some(test_id) {
+            return false;
+        }
+
+        self.answering = true;
+        true
+    }
+
+    /// Moves to the next test once the answer is recorded, `None` keeps the current test when recording it failed
+    pub fn end_answer(&mut self, correct: Option<bool>) {
+        self.answering = false;
+        self.results.extend(correct);
+    }
+
+    pub fn current_test(&self) -> Option<i32> {
+        self.test_ids.get(self.results.len()).copied()
+    }
+
+    pub fn right_answers(&self) -> usize {
+        self.results.iter().filter(|right| **right).count()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.results.len() >= self.test_ids.len()
+    }
+
+    pub fn is_passed(&self) -> bool {
+        self.right_answers() * 100 >= self.test_ids.len() * PASS_MARK
+    }
+}
+
+/// The tool with its pending logins and running sessions, they live in memory only
+pub struct Lti {
+    pub config: LtiConfig,
+    /// Nonces of the logins waiting for their launches by their state
+    logins: Mutex<HashMap<String, (String, Instant)>>,
+    sessions: Mutex<HashMap<String, LtiSession>>,
+    platform_keys: RwLock<JwkSet>,
+    platform_keys_fetched_at: Mutex<Option<Instant>>,
+}
+
+fn random_token() -> String {
+    hex::encode(rand::random::<[u8; 16]>())
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl Lti {
+    pub fn new(config: LtiConfig) -> Self {
+        Lti {
+            config,
+            logins: Mutex::new(HashMap::new()),
+            sessions: Mutex::new(HashMap::new()),
+            platform_keys: RwLock::new(JwkSet::default()),
+            platform_keys_fetched_at: Mutex::new(None),
+        }
+    }
+
+    /// Starts the OIDC login of a launch and returns the platform URL to redirect the browser to
+    pub fn login(&self, request: &LoginRequest) -> anyhow::Result<String> {
+        if request.iss != self.config.issuer {
+            return Err(anyhow!("{} isn't the registered platform", request.iss));
+        }
+        if request
+            .client_id
+            .as_ref()
+            .is_some_and(|id| *id != self.config.client_id)
+        {
+            return Err(anyhow!("the login isn't for {} client", self.config.client_id));
+        }
+        // The id token is posted to the target, so it can't be anything but the launch URL of the tool
+        if request.target_link_uri != self.config.launch_url {
+            return Err(anyhow!("{} isn't the launch URL of the tool", request.target_link_uri));
+        }
+
+        let (state, nonce) = (random_token(), random_token());
+        {
+            let mut logins = self.logins.lock().unwrap();
+            logins.retain(|_, (_, started_at)| started_at.elapsed() < LOGIN_TTL);
+            logins.insert(state.clone(), (nonce.clone(), Instant::now()));
+        }
+
+        let mut query = vec![
+            ("scope", "openid"),
+            ("response_type", "id_token"),
+            ("response_mode", "form_post"),
+            ("prompt", "none"),
+            ("client_id", self.config.client_id.as_str()),
+            ("redirect_uri", self.config.launch_url.as_str()),
+            ("login_hint", request.login_hint.as_str()),
+            ("state", state.as_str()),
+            ("nonce", nonce.as_str()),
+        ];
+        if let Some(hint) = request.lti_message_hint.as_ref() {
+            query.push(("lti_message_hint", hint));
+        }
+
+        let separator = match self.config.auth_url.contains('?') {
+            true => '&',
+            false => '?',
+        };
+        Ok(format!(
+            "{}{}{}",
+            self.config.auth_url,
+            separator,
+            serde_urlencoded::to_string(query)?
+        ))
+    }
+
+    /// Verifies the id token the platform posts back to the launch URL, every login launches once
+    pub async fn launch(&self, id_token: &str, state: &str) -> anyhow::Result<LaunchClaims> {
+        let nonce = match self.logins.lock().unwrap().remove(state) {
+            Some((nonce, started_at)) if started_at.elapsed() < LOGIN_TTL => nonce,
+            _ => return Err(anyhow!("there is no login with this state or it has expired")),
+        };
+
+        let kid = jwt::header(id_token)?.kid;
+        let key = self.platform_key(kid.as_deref()).await?;
+        let claims = serde_json::from_value::<LaunchClaims>(jwt::verify(id_token, &key)?)
+            .map_err(|err| anyhow!("the id token isn't an LTI launch - {}", err))?;
+        claims.validate(&self.config, &nonce, unix_time())?;
+
+        Ok(claims)
+    }
+
+    /// Keys are cached, the key set is fetched again when the platform signs with a new key
+    async fn platform_key(&self, kid: Option<&str>) -> anyhow::Result<PKey<Public>> {
+        if let Some(key) = self.platform_keys.read().unwrap().find(kid) {
+            return key.public_key();
+        }
+        if !self.may_fetch_platform_keys(Instant::now()) {
+            return Err(anyhow!(
+                "the platform has no {} key, the keys were fetched a moment ago",
+                kid.unwrap_or_default()
+            ));
+        }
+
+        let mut response = Client::default()
+            .get(&self.config.jwks_url)
+            .send()
+            .await
+            .map_err(|err| anyhow!("Failed to get the platform keys - {}", err))?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Failed to get the platform keys - {}", response.status()));
+        }
+        let key_set = response
+            .json::<JwkSet>()
+            .await
+            .map_err(|err| anyhow!("The platform keys aren't a key set - {}", err))?;
+
+        let mut platform_keys = self.platform_keys.write().unwrap();
+        *platform_keys = key_set;
+        platform_keys
+            .find(kid)
+            .ok_or_else(|| anyhow!("the platform has no {} key", kid.unwrap_or_default()))?
+            .public_key()
+    }
+
+    /// Remembers the fetch when it's allowed, the failed ones count too
+    fn may_fetch_platform_keys(&self, now: Instant) -> bool {
+        let mut fetched_at = self.platform_keys_fetched_at.lock().unwrap();
+        if fetched_at.is_some_and(|fetched_at| now.saturating_duration_since(fetched_at) < KEYS_REFETCH_INTERVAL) {
+            return false;
+        }
+
+        *fetched_at = Some(now);
+        true
+    }
+
+    /// Returns the token the session is known by
+    pub fn start_session(&self, session: LtiSession) -> String {
+        let token = random_token();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| session.started_at.elapsed() < SESSION_TTL);
+        sessions.insert(token.clone(), session);
+
+        token
+    }
+
+    pub fn with_session<T>(&self, token: &str, f: impl FnOnce(&mut LtiSession) -> T) -> Option<T> {
+        self.sessions.lock().unwrap().get_mut(token).map(f)
+    }
+
+    pub fn key_set(&self) -> anyhow::Result<JwkSet> {
+        Ok(JwkSet {
+            keys: vec![jwt::Jwk::from_key(&self.config.key, &jwt::key_id(&self.config.key)?)?],
+        })
+    }
+
+    /// Client credentials grant with a signed JWT, the way LTI services are authorized
+    async fn access_token(&self, scope: &str) -> anyhow::Result<String> {
+        let now = unix_time();
+        let assertion = jwt::sign(
+            &json!({
+                "iss": self.config.client_id,
+                "sub": self.config.client_id,
+                "aud": self.config.token_url,
+                "iat": now,
+                "exp": now + 300,
+                "jti": random_token(),
+            }),
+            &self.config.key,
+            &jwt::key_id(&self.config.key)?,
+        )?;
+
+        let mut response = Client::default()
+            .post(&self.config.token_url)
+            .send_form(&[
+                ("grant_type", "client_credentials"),
+                (
+                    "client_assertion_type",
+                    "urn:ietf:params:oauth:client-assertion-type:jwt-bearer",
+                ),
+                ("client_assertion", assertion.as_str()),
+                ("scope", scope),
+            ])
+            .await
+            .map_err(|err| anyhow!("Failed to get an access token - {}", err))?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Failed to get an access token - {}", response.status()));
+        }
+        let token = response
+            .json::<Value>()
+            .await
+            .map_err(|err| anyhow!("Failed to read the access token - {}", err))?;
+
+        token["access_token"]
+            .as_str()
+            .map(str::to_owned)
+            .ok_or_else(|| anyhow!("the token response has no access_token"))
+    }
+
+    /// Sends the final score of a session to its line item
+    pub async fn send_score(&self, line_item: &str, subject: &str, right: usize, total: usize) -> anyhow::Result<()> {
+        let token = self.access_token(SCORE_SCOPE).await?;
+        let score = json!({
+            "userId": subject,
+            "scoreGiven": right,
+            "scoreMaximum": total,
+            "activityProgress": "Completed",
+            "gradingProgress": "FullyGraded",
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+        });
+
+        let response = Client::default()
+            .post(scores_url(line_item))
+            .header("Authorization", format!("Bearer {}", token))
+            .content_type("application/vnd.ims.lis.v1.score+json")
+            .send_body(score.to_string())
+            .await
+            .map_err(|err| anyhow!("Failed to send the score - {}", err))?;
+        if !response.status().is_success() {
+            return Err(anyhow!("Failed to send the score - {}", response.status()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Scores are posted to the `/scores` path of the line item, its query stays
+fn scores_url(line_item: &str) -> String {
+    match line_item.split_once('?') {
+        Some((path, query)) => format!("{}/scores?{}", path.trim_end_matches('/'), query),
+        None => format!("{}/scores", line_item.trim_end_matches('/')),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::rsa::Rsa;
+
+    fn config() -> LtiConfig {
+        LtiConfig {
+            issuer: "https://lms.example".to_owned(),
+            client_id: "tool".to_owned(),
+            deployment_id: Some("1".to_owned()),
+            auth_url: "https://lms.example/auth".to_owned(),
+            launch_url: "https://tool.example/lti/launch".to_owned(),
+            jwks_url: "https://lms.example/jwks".to_owned(),
+            token_url: "https://lms.example/token".to_owned(),
+            key: PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap(),
+            questions: 20,
+        }
+    }
+
+    fn claims(changes: Value) -> LaunchClaims {
+        let mut claims = json!({
+            "iss": "https://lms.example",
+            "aud": ["tool"],
+            "azp": "tool",
+            "exp": 2000,
+            "iat": 1000,
+            "nonce": "nonce",
+            "sub": "learner",
+            "name": "Ann Lee",
+            "https://purl.imsglobal.org/spec/lti/claim/message_type": "LtiResourceLinkRequest",
+            "https://purl.imsglobal.org/spec/lti/claim/version": "1.3.0",
+            "https://purl.imsglobal.org/spec/lti/claim/deployment_id": "1",
+            "https://purl.imsglobal.org/spec/lti/claim/custom": { "mode": "exam", "questions": "5" },
+        });
+        for (name, value) in changes.as_object().unwrap() {
+            claims[name] = value.clone();
+        }
+
+        serde_json::from_value(claims).unwrap()
+    }
+
+    #[test]
+    fn launch_claims_are_validated_against_the_registration() {
+        let config = config();
+
+        assert!(claims(json!({})).validate(&config, "nonce", 1500).is_ok());
+        assert!(claims(json!({})).validate(&config, "other nonce", 1500).is_err());
+        assert!(claims(json!({})).validate(&config, "nonce", 3000).is_err());
+        assert!(claims(json!({ "aud": "other tool" }))
+            .validate(&config, "nonce", 1500)
+            .is_err());
+        assert!(claims(json!({ "iss": "https://other.example" }))
+            .validate(&config, "nonce", 1500)
+            .is_err());
+        let deployment = json!({ "https://purl.imsglobal.org/spec/lti/claim/deployment_id": "2" });
+        assert!(claims(deployment).validate(&config, "nonce", 1500).is_err());
+    }
+
+    #[test]
+    fn launch_claims_choose_the_session() {
+        let config = config();
+        let claims = claims(json!({}));
+
+        assert_eq!(claims.mode(), SessionMode::Exam);
+        assert_eq!(claims.questions(&config), 5);
+        assert_eq!(claims.names(), ("Ann".to_owned(), "Lee".to_owned()));
+        assert_eq!(claims.line_item(), None);
+        assert_eq!(
+            scores_url("https://lms.example/lineitems/7?type=1"),
+            "https://lms.example/lineitems/7/scores?type=1"
+        );
+    }
+
+    #[test]
+    fn logins_only_target_the_launch_url() {
+        let lti = Lti::new(config());
+        let login = |target_link_uri: &str| LoginRequest {
+            iss: "https://lms.example".to_owned(),
+            login_hint: "learner".to_owned(),
+            target_link_uri: target_link_uri.to_owned(),
+            lti_message_hint: None,
+            client_id: None,
+        };
+
+        assert!(lti
+            .login(&login("https://tool.example/lti/launch"))
+            .unwrap()
+            .contains("redirect_uri=https%3A%2F%2Ftool.example%2Flti%2Flaunch"));
+        assert!(lti.login(&login("https://attacker.example/lti/launch")).is_err());
+    }
+
+    #[test]
+    fn platform_keys_are_fetched_again_once_a_minute() {
+        let lti = Lti::new(config());
+        let now = Instant::now();
+
+        assert!(lti.may_fetch_platform_keys(now));
+        assert!(!lti.may_fetch_platform_keys(now + Duration::from_secs(30)));
+        assert!(lti.may_fetch_platform_keys(now + KEYS_REFETCH_INTERVAL));
+    }
+
+    #[test]
+    fn sessions_move_on_once_the_answer_is_recorded() {
+        let user = User {
+            id: 1,
+            name: "Ann".to_owned(),
+            second_name: "Lee".to_owned(),
+            password: String::new(),
+            scores: 0,
+            display_name: None,
+            leaderboard_hidden: false,
+        };
+        let mut session = LtiSession::new(&user, &claims(json!({})), vec![7, 8]);
+
+        assert!(!session.begin_answer(8));
+        assert!(session.begin_answer(7));
+        // A second answer to the same test waits for the first one
+        assert!(!session.begin_answer(7));
+        session.end_answer(None);
+        assert_eq!(session.current_test(), Some(7));
+        assert!(session.begin_answer(7));
+        session.end_answer(Some(true));
+        assert_eq!(session.current_test(), Some(8));
+        assert_eq!(session.right_answers(), 1);
+    }
+}
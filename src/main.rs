@@ -1,15 +1,17 @@
-use actix_cors::Cors;
-use actix_web::{
-    http,
-    middleware::{self, Logger},
-    App, HttpServer,
-};
+use chrono::Utc;
 use log::{error, info};
 use std::{env, path::Path};
 
 use lib::{
-    db::{establish_connection, insert_tests_to_db},
-    utils,
+    db::{
+        calibrate_tests, establish_connection, export_tests, insert_tests_to_db, spawn_calibration, ExportOptions,
+        ImportOptions,
+    },
+    image_pipeline::{ImagePipeline, ImagePipelineConfig},
+    irt::IrtConfig,
+    lti::LtiConfig,
+    xapi::XapiConfig,
+    AdminConfig, AppConfig, Server,
 };
 
 const DEFAULT_IP_ADDR: &str = "127.0.0.1:5050";
@@ -19,40 +21,90 @@ async fn main() -> anyhow::Result<()> {
     env_logger::init();
 
     let server_addr = env::var("SERVER_IP_ADDR").unwrap_or_else(|_| DEFAULT_IP_ADDR.to_string());
+    let path_prefix = env::var("SERVER_PATH_PREFIX").unwrap_or_default();
 
-    info!("Running server on {}", server_addr);
-
-    let connection_pool = establish_connection();
-    if env::args().nth(1).is_some() {
-        let path_to_tests = env::args().nth(1).unwrap();
+    let irt_config = IrtConfig::from_env()?;
+    let mut config = AppConfig::new(establish_connection())
+        .with_prefix(&path_prefix)
+        .with_irt(irt_config.clone());
+    let (flags, args): (Vec<String>, Vec<String>) = env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let flag_value = |name: &str| flags.iter().find_map(|flag| flag.strip_prefix(name)?.strip_prefix('='));
+    if let (Some("export"), Some(path)) = (args.first().map(String::as_str), args.get(1)) {
+        let options = ExportOptions {
+            format: flag_value("--format").unwrap_or("bundle").parse()?,
+            categories: flag_value("--category")
+                .map(|categories| {
+                    categories
+                        .split(',')
+                        .map(|category| category.trim().to_owned())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            test_ids: flag_value("--tests")
+                .map(|ids| ids.split(',').map(|id| id.trim().parse()).collect::<Result<_, _>>())
+                .transpose()?
+                .unwrap_or_default(),
+        };
+        export_tests(&config.pool, Path::new(path), &options)?;
+        return Ok(());
+    }
+    if let Some("calibrate") = args.first().map(String::as_str) {
+        let model = flag_value("--model")
+            .map(str::parse)
+            .transpose()?
+            .unwrap_or(irt_config.model);
+        let summary = calibrate_tests(&config.pool, model, irt_config.min_answers, Utc::now().timestamp())?;
+        println!("{}", summary);
+        return Ok(());
+    }
+    if let Some(path_to_tests) = args.first() {
+        let pipeline = ImagePipeline::new(ImagePipelineConfig::from_env()?);
+        let has_flag = |name: &str| flags.iter().any(|flag| flag == name);
+        let options = ImportOptions {
+            retire_missing: has_flag("--retire-missing"),
+            dry_run: has_flag("--dry-run"),
+            strict: has_flag("--strict"),
+        };
         let path = Path::new(path_to_tests.as_str());
 
         match path.exists() {
-            true => {
-                let _ = insert_tests_to_db(&path, &connection_pool)
-                    .map_err(|err| error!("Insert values to the DB failed due to: {}", err));
-            }
+            true => match insert_tests_to_db(path, &config.pool, &config.cache, &pipeline, options) {
+                Ok(report) if options.dry_run => {
+                    println!("{}\n{}", report.validation, report.summary);
+                    return Ok(());
+                }
+                Ok(_) => {}
+                Err(err) => error!("Insert values to the DB failed due to: {}", err),
+            },
             false => error!("{} path to the tests isn't valid", path_to_tests),
         }
-    }
 
-    let tls_builder = utils::tls_builder()?;
+        // A dry run only checks the tests
+        if options.dry_run {
+            return Err(anyhow::anyhow!("The dry run import failed"));
+        }
+    }
 
     info!(
         "Successfully connected to the DB on {}",
         env::var("DATABASE_URL").unwrap()
     );
 
-    HttpServer::new(move || {
-        App::new()
-            .wrap(Cors::permissive())
-            .wrap(Logger::new("%a %t %r %b %s %T"))
-            .wrap(middleware::Compress::new(http::ContentEncoding::Identity))
-            .data(connection_pool.clone())
-            .configure(utils::services_config)
-    })
-    .bind_openssl(server_addr, tls_builder)?
-    .run()
-    .await
-    .map_err(|err| err.into())
+    config.cache.refresh(&config.pool)?;
+    if let Some(lti_config) = LtiConfig::from_env()? {
+        info!("Serving LTI launches of {}", lti_config.issuer);
+        config = config.with_lti(lti_config);
+    }
+    if let Some(xapi_config) = XapiConfig::from_env()? {
+        info!("Sending xAPI statements to {}", xapi_config.endpoint);
+        config = config.with_xapi(xapi_config);
+    }
+    if let Some(admin_config) = AdminConfig::from_env() {
+        info!("Serving the admin services");
+        config = config.with_admin(admin_config);
+    }
+
+    spawn_calibration(config.pool.clone(), irt_config);
+
+    Server::new(config, &server_addr).run().await
 }
@@ -0,0 +1,160 @@
+use actix_cors::Cors;
+use actix_service::ServiceFactory;
+use actix_web::{
+    body::{Body, ResponseBody},
+    dev::{Service, ServiceRequest, ServiceResponse},
+    http,
+    middleware::{self, Logger},
+    web::{self, Data, ServiceConfig},
+    App, Error, HttpServer,
+};
+use log::info;
+
+use crate::{
+    cache::QuestionCache,
+    db::DbPool,
+    irt::IrtConfig,
+    lti::{Lti, LtiConfig},
+    utils,
+    web::AdminConfig,
+    xapi::{Xapi, XapiConfig},
+};
+
+const LOG_FORMAT: &str = "%a %t %r %b %s %T";
+
+/// Everything the Drive-tests services need to be mounted into an actix app
+#[derive(Clone)]
+pub struct AppConfig {
+    pub pool: DbPool,
+    /// Path prefix the services are mounted under, e.g. `/drive-tests`. An empty prefix mounts them at the root
+    pub prefix: String,
+    pub cache: Data<QuestionCache>,
+    /// The LTI tool services are mounted only when it's configured
+    pub lti: Option<Data<Lti>>,
+    /// Statements are sent to the LRS only when it's configured
+    pub xapi: Option<Data<Xapi>>,
+    pub irt: Data<IrtConfig>,
+    /// The admin services are mounted only when it's configured
+    pub admin: Option<Data<AdminConfig>>,
+}
+
+impl AppConfig {
+    pub fn new(pool: DbPool) -> Self {
+        AppConfig {
+            pool,
+            prefix: String::new(),
+            cache: Data::new(QuestionCache::new()),
+            lti: None,
+            xapi: None,
+            irt: Data::new(IrtConfig::default()),
+            admin: None,
+        }
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.trim_end_matches('/').to_owned();
+        self
+    }
+
+    pub fn with_lti(mut self, config: LtiConfig) -> Self {
+        self.lti = Some(Data::new(Lti::new(config)));
+        self
+    }
+
+    pub fn with_xapi(mut self, config: XapiConfig) -> Self {
+        self.xapi = Some(Data::new(Xapi::new(config)));
+        self
+    }
+
+    pub fn with_irt(mut self, config: IrtConfig) -> Self {
+        self.irt = Data::new(config);
+        self
+    }
+
+    pub fn with_admin(mut self, config: AdminConfig) -> Self {
+        self.admin = Some(Data::new(config));
+        self
+    }
+
+    /// Registers the services together with their shared data under `prefix`.
+    /// Pass it to `App::configure` to embed Drive-tests into a larger actix app:
+    /// ```no_run
+    /// # let pool = lib::db::establish_connection();
+    /// let config = lib::AppConfig::new(pool).with_prefix("/drive-tests");
+    /// let app = actix_web::App::new().configure(|cfg| config.configure(cfg));
+    /// ```
+    pub fn configure(&self, cfg: &mut ServiceConfig) {
+        let mut scope = web::scope(&self.prefix)
+            .data(self.pool.clone())
+            .app_data(self.cache.clone())
+            .app_data(self.irt.clone())
+            .configure(utils::services_config);
+        if let Some(xapi) = self.xapi.as_ref() {
+            scope = scope.app_data(xapi.clone());
+        }
+        if let Some(lti) = self.lti.as_ref() {
+            scope = scope.app_data(lti.clone()).configure(utils::lti_services_config);
+        }
+        if let Some(admin) = self.admin.as_ref() {
+            scope = scope.app_data(admin.clone()).configure(utils::admin_services_config);
+        }
+
+        cfg.service(scope);
+    }
+}
+
+/// Builds the fully configured application: CORS, logging, compression, shared data and all the services
+pub fn build_app(
+    config: AppConfig,
+) -> App<
+    impl ServiceFactory<
+        Config = (),
+        Request = ServiceRequest,
+        Response = ServiceResponse<Body>,
+        Error = Error,
+        InitError = (),
+    >,
+    Body,
+> {
+    App::new()
+        .wrap(Cors::permissive())
+        .wrap(Logger::new(LOG_FORMAT))
+        .wrap(middleware::Compress::new(http::ContentEncoding::Identity))
+        // The middlewares above change the body type into private ones, boxing it keeps the app type nameable
+        .wrap_fn(|request, service| {
+            let response = service.call(request);
+            async move {
+                let response = response.await?;
+                Ok(response.map_body(|_, body| ResponseBody::Other(Body::from_message(body))))
+            }
+        })
+        .configure(|cfg| config.configure(cfg))
+}
+
+/// The Drive-tests HTTPS server
+pub struct Server {
+    config: AppConfig,
+    addr: String,
+}
+
+impl Server {
+    pub fn new(config: AppConfig, addr: &str) -> Self {
+        Server {
+            config,
+            addr: addr.to_owned(),
+        }
+    }
+
+    pub async fn run(self) -> anyhow::Result<()> {
+        let tls_builder = utils::tls_builder()?;
+        let config = self.config;
+
+        info!("Running server on {}{}", self.addr, config.prefix);
+
+        HttpServer::new(move || build_app(config.clone()))
+            .bind_openssl(self.addr, tls_builder)?
+            .run()
+            .await
+            .map_err(|err| err.into())
+    }
+}
@@ -1,4 +1,11 @@
-use crate::web::{check_answer, check_answer_with_user, get_test, healthy, sing_in, sing_up};
+use crate::web::{
+    add_bookmark, add_to_collection, answer_daily, cache_stats, check_answer, check_answer_with_user,
+    create_collection, delete_collection, dismiss_notifications, end_marathon, get_achievements, get_bookmarks,
+    get_collections, get_daily, get_daily_leaderboard, get_image, get_leaderboard, get_marathon, get_notifications,
+    get_progress, get_test, healthy, lti_answer, lti_jwks, lti_launch, lti_login, lti_login_form, lti_session,
+    question_quality, remove_bookmark, remove_from_collection, set_privacy, set_time_zone, sing_in, sing_up,
+    start_marathon,
+};
 use anyhow::Context;
 use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod};
 use std::{env, path::Path};
@@ -7,11 +14,49 @@ pub fn services_config(cfg: &mut actix_web::web::ServiceConfig) {
     cfg.service(sing_up)
         .service(sing_in)
         .service(get_test)
+        .service(get_image)
         .service(check_answer_with_user)
         .service(check_answer)
+        .service(cache_stats)
+        .service(get_leaderboard)
+        .service(set_privacy)
+        .service(get_progress)
+        .service(start_marathon)
+        .service(get_marathon)
+        .service(end_marathon)
+        .service(get_bookmarks)
+        .service(add_bookmark)
+        .service(remove_bookmark)
+        .service(get_collections)
+        .service(create_collection)
+        .service(delete_collection)
+        .service(add_to_collection)
+        .service(remove_from_collection)
+        .service(get_daily)
+        .service(answer_daily)
+        .service(get_daily_leaderboard)
+        .service(set_time_zone)
+        .service(get_achievements)
+        .service(get_notifications)
+        .service(dismiss_notifications)
         .service(healthy);
 }
 
+/// The LTI tool services, they need `Lti` in the app data
+pub fn lti_services_config(cfg: &mut actix_web::web::ServiceConfig) {
+    cfg.service(lti_login)
+        .service(lti_login_form)
+        .service(lti_launch)
+        .service(lti_session)
+        .service(lti_answer)
+        .service(lti_jwks);
+}
+
+/// The admin services, they need `AdminConfig` in the app data
+pub fn admin_services_config(cfg: &mut actix_web::web::ServiceConfig) {
+    cfg.service(question_quality);
+}
+
 pub fn tls_builder() -> anyhow::Result<SslAcceptorBuilder> {
     let (key, cert) = get_certs_paths()?;
 
@@ -0,0 +1,77 @@
+use actix_web::{
+    delete, get,
+    web::{block, Data, Json},
+    HttpResponse, Result,
+};
+use log::error;
+use serde::Deserialize;
+
+use super::authenticate;
+use crate::{
+    db::{self, DbPool},
+    model::UserForm,
+};
+
+#[derive(Deserialize)]
+pub struct DismissNotificationsForm {
+    pub user: UserForm,
+    /// The ids of the shown badges, the ones earned since then stay notified
+    pub achievements: Vec<String>,
+}
+
+/// Every badge with the progress of the user towards it
+#[get("/user/achievements")]
+pub async fn get_achievements(user: Json<UserForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let user = match authenticate(user.into_inner(), pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let achievements = block(move || db::achievements(&pool, user.id)).await.map_err(|err| {
+        error!("{}:{} Loading the achievements failed - {:?}", file!(), line!(), err);
+        HttpResponse::InternalServerError().finish()
+    })?;
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(achievements))
+}
+
+/// The badges earned since the notifications were dismissed the last time
+#[get("/user/notifications")]
+pub async fn get_notifications(user: Json<UserForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let user = match authenticate(user.into_inner(), pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let earned = block(move || db::unseen_achievements(&pool, user.id))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Loading the notifications failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(earned))
+}
+
+#[delete("/user/notifications")]
+pub async fn dismiss_notifications(form: Json<DismissNotificationsForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let DismissNotificationsForm { user, achievements } = form.into_inner();
+    let user = match authenticate(user, pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    block(move || db::mark_achievements_seen(&pool, user.id, &achievements))
+        .await
+        .map_err(|err| {
+            error!(
+                "{}:{} Dismissing the notifications failed - {:?}",
+                file!(),
+                line!(),
+                err
+            );
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    Ok(HttpResponse::Ok().finish())
+}
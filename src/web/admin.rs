@@ -0,0 +1,77 @@
+use actix_web::{
+    get,
+    http::header,
+    web::{block, Data, Query},
+    HttpRequest, HttpResponse, Result,
+};
+use log::error;
+use openssl::memcmp;
+use serde::Deserialize;
+use std::env;
+
+use crate::db::{self, DbPool};
+
+/// The admin services, mounted only when `ADMIN_TOKEN` is set
+#[derive(Clone)]
+pub struct AdminConfig {
+    /// Sent by the admins as `Authorization: Bearer <token>`
+    pub token: String,
+}
+
+impl AdminConfig {
+    pub fn from_env() -> Option<Self> {
+        env::var("ADMIN_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty())
+            .map(|token| AdminConfig { token })
+    }
+
+    fn authorizes(&self, request: &HttpRequest) -> bool {
+        let token = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        match token {
+            Some(token) if token.len() == self.token.len() => memcmp::eq(token.as_bytes(), self.token.as_bytes()),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct QualityQuery {
+    /// Only the tests with a quality flag
+    #[serde(default)]
+    pub flagged: bool,
+}
+
+#[get("/admin/questions/quality")]
+pub async fn question_quality(
+    request: HttpRequest,
+    query: Query<QualityQuery>,
+    admin: Data<AdminConfig>,
+    pool: Data<DbPool>,
+) -> Result<HttpResponse> {
+    if !admin.authorizes(&request) {
+        return Ok(HttpResponse::Unauthorized()
+            .header(header::WWW_AUTHENTICATE, "Bearer")
+            .finish());
+    }
+
+    let mut report = block(move || db::question_quality(&pool)).await.map_err(|err| {
+        error!(
+            "{}:{} Computing the question quality failed - {:?}",
+            file!(),
+            line!(),
+            err
+        );
+        HttpResponse::InternalServerError().finish()
+    })?;
+    if query.flagged {
+        report.retain(|quality| !quality.flags.is_empty());
+    }
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(report))
+}
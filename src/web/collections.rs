@@ -0,0 +1,199 @@
+use actix_web::{
+    delete, get, post, put,
+    web::{block, Data, Json, Path},
+    HttpResponse, Result,
+};
+use chrono::Utc;
+use log::error;
+use serde::Deserialize;
+
+use super::authenticate;
+use crate::{
+    db::{self, DbPool},
+    model::UserForm,
+};
+
+const MAX_COLLECTION_NAME_LEN: usize = 50;
+
+#[derive(Deserialize)]
+pub struct CollectionForm {
+    pub user: UserForm,
+    pub name: String,
+}
+
+fn found(found: bool) -> HttpResponse {
+    match found {
+        true => HttpResponse::Ok().finish(),
+        false => HttpResponse::NotFound().finish(),
+    }
+}
+
+#[get("/bookmarks")]
+pub async fn get_bookmarks(user: Json<UserForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let user = match authenticate(user.into_inner(), pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let bookmarks = block(move || db::bookmarks(&pool, user.id)).await.map_err(|err| {
+        error!("{}:{} Loading the bookmarks failed - {:?}", file!(), line!(), err);
+        HttpResponse::InternalServerError().finish()
+    })?;
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(bookmarks))
+}
+
+#[put("/bookmarks/{test_id}")]
+pub async fn add_bookmark(path: Path<i32>, user: Json<UserForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let user = match authenticate(user.into_inner(), pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let test_id = path.into_inner();
+    let bookmarked = block(move || db::bookmark(&pool, user.id, test_id, Utc::now().timestamp()))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Bookmarking a test failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    Ok(found(bookmarked))
+}
+
+/// Removes the test from the collections of the user too
+#[delete("/bookmarks/{test_id}")]
+pub async fn remove_bookmark(path: Path<i32>, user: Json<UserForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let user = match authenticate(user.into_inner(), pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let test_id = path.into_inner();
+    let removed = block(move || db::remove_bookmark(&pool, user.id, test_id))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Removing a bookmark failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    Ok(found(removed))
+}
+
+#[get("/collections")]
+pub async fn get_collections(user: Json<UserForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let user = match authenticate(user.into_inner(), pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let collections = block(move || db::collections(&pool, user.id)).await.map_err(|err| {
+        error!("{}:{} Loading the collections failed - {:?}", file!(), line!(), err);
+        HttpResponse::InternalServerError().finish()
+    })?;
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(collections))
+}
+
+#[post("/collections")]
+pub async fn create_collection(form: Json<CollectionForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let CollectionForm { user, name } = form.into_inner();
+    let name = name.trim().to_owned();
+    if name.is_empty() || name.chars().count() > MAX_COLLECTION_NAME_LEN {
+        return Ok(HttpResponse::BadRequest().finish());
+    }
+
+    let user = match authenticate(user, pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let collection = block(move || db::create_collection(&pool, user.id, &name, Utc::now().timestamp()))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Creating a collection failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    Ok(match collection {
+        Some(collection) => HttpResponse::Created()
+            .content_type("application/json")
+            .json(collection),
+        None => HttpResponse::Conflict().finish(),
+    })
+}
+
+/// The bookmarks of the collection are kept
+#[delete("/collections/{collection_id}")]
+pub async fn delete_collection(path: Path<i32>, user: Json<UserForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let user = match authenticate(user.into_inner(), pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let collection_id = path.into_inner();
+    let deleted = block(move || db::delete_collection(&pool, user.id, collection_id))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Deleting a collection failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    Ok(found(deleted))
+}
+
+/// Bookmarks the test too
+#[put("/collections/{collection_id}/tests/{test_id}")]
+pub async fn add_to_collection(
+    path: Path<(i32, i32)>,
+    user: Json<UserForm>,
+    pool: Data<DbPool>,
+) -> Result<HttpResponse> {
+    let user = match authenticate(user.into_inner(), pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let (collection_id, test_id) = path.into_inner();
+    let added = block(move || db::add_to_collection(&pool, user.id, collection_id, test_id, Utc::now().timestamp()))
+        .await
+        .map_err(|err| {
+            error!(
+                "{}:{} Adding a test to a collection failed - {:?}",
+                file!(),
+                line!(),
+                err
+            );
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    Ok(found(added))
+}
+
+/// The test stays bookmarked
+#[delete("/collections/{collection_id}/tests/{test_id}")]
+pub async fn remove_from_collection(
+    path: Path<(i32, i32)>,
+    user: Json<UserForm>,
+    pool: Data<DbPool>,
+) -> Result<HttpResponse> {
+    let user = match authenticate(user.into_inner(), pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let (collection_id, test_id) = path.into_inner();
+    let removed = block(move || db::remove_from_collection(&pool, user.id, collection_id, test_id))
+        .await
+        .map_err(|err| {
+            error!(
+                "{}:{} Removing a test from a collection failed - {:?}",
+                file!(),
+                line!(),
+                err
+            );
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    Ok(found(removed))
+}
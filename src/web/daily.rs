@@ -0,0 +1,243 @@
+use actix_web::{
+    get, post, put,
+    web::{block, Data, Json, Query},
+    HttpRequest, HttpResponse, Result,
+};
+use chrono::{NaiveDate, Utc};
+use chrono_tz::Tz;
+use log::error;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::{
+    authenticate, cached_bank,
+    leaderboard::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE},
+    test_json, AnswerForm, AnswerWithUserForm, ImageEncoding, SCORES_FOR_RIGHT_ANSWER,
+};
+use crate::{
+    cache::QuestionCache,
+    db::{self, DailyAnswerOutcome, DbPool},
+    model::{NewUserAnswer, UserForm},
+    xapi::{Actor, Xapi},
+};
+
+#[derive(Deserialize)]
+pub struct DailyQuery {
+    pub image: Option<ImageEncoding>,
+}
+
+#[derive(Deserialize)]
+pub struct DailyLeaderboardQuery {
+    /// YYYY-MM-DD, the current day of the caller or of UTC by default
+    pub day: Option<String>,
+    /// 1-based
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct TimeZoneForm {
+    pub user: UserForm,
+    /// IANA name like `Europe/Kyiv`, UTC when it's missing
+    pub time_zone: Option<String>,
+}
+
+/// The questions of the day of the user with their answers so far and their streak
+#[get("/daily")]
+pub async fn get_daily(
+    request: HttpRequest,
+    query: Query<DailyQuery>,
+    user: Json<UserForm>,
+    pool: Data<DbPool>,
+    cache: Data<QuestionCache>,
+) -> Result<HttpResponse> {
+    let user = match authenticate(user.into_inner(), pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let bank = cached_bank(pool.clone(), cache).await?;
+    let challenge = block(move || db::daily_challenge(&pool, &user, Utc::now()))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Loading the daily challenge failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    let questions = challenge
+        .tests
+        .iter()
+        .filter_map(|test_id| bank.test(*test_id))
+        .map(|test| test_json(&request, &test, query.image))
+        .collect::<Vec<_>>();
+    let mut challenge = json!(challenge);
+    if let Value::Object(challenge) = &mut challenge {
+        challenge.insert("questions".to_owned(), json!(questions));
+    }
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(challenge))
+}
+
+/// Scores the answer like `POST /check_test` does, every question of the day is answered only once
+#[post("/daily/answer")]
+pub async fn answer_daily(
+    form: Json<AnswerWithUserForm>,
+    pool: Data<DbPool>,
+    xapi: Option<Data<Xapi>>,
+) -> Result<HttpResponse> {
+    let AnswerWithUserForm {
+        user,
+        answer,
+        response_time_ms,
+    } = form.into_inner();
+    let AnswerForm { test_id, answer_id } = answer;
+
+    let user = match authenticate(user, pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let pool_clone = pool.clone();
+    let user_id = user.id;
+    let (time_zone, day, tests) = block(move || -> anyhow::Result<_> {
+        let time_zone = db::time_zone(&pool_clone, user_id)?;
+        let day = db::local_day(time_zone.as_deref(), Utc::now());
+        let tests = db::daily_set(&pool_clone, &day.to_string())?;
+        Ok((time_zone, day, tests))
+    })
+    .await
+    .map_err(|err| {
+        error!("{}:{} Loading the daily challenge failed - {:?}", file!(), line!(), err);
+        HttpResponse::InternalServerError().finish()
+    })?;
+    if !tests.contains(&(test_id as i32)) {
+        return Ok(HttpResponse::BadRequest().finish());
+    }
+
+    let pool_clone = pool.clone();
+    let check_result = block(move || db::check_test_answer(test_id, answer_id, &pool_clone))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Checking a test answer failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    let scores = match check_result {
+        true => SCORES_FOR_RIGHT_ANSWER,
+        false => 0,
+    };
+    let new_answer = NewUserAnswer {
+        response_ms: response_time_ms.map(|response_ms| response_ms.min(i32::MAX as u32) as i32),
+        ..NewUserAnswer::new(user.id, test_id as i32, answer_id as i32, check_result, scores as i32)
+    };
+    let outcome = block(move || db::record_daily_answer(&pool, day, time_zone.as_deref(), tests.len(), &new_answer))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Recording a daily answer failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+    let finished = match outcome {
+        DailyAnswerOutcome::Recorded { finished } => finished,
+        DailyAnswerOutcome::Answered => return Ok(HttpResponse::Conflict().finish()),
+        DailyAnswerOutcome::Overlapping { day } => {
+            return Ok(HttpResponse::Conflict().content_type("application/json").json(json!({
+                "description": format!("The day overlaps {} started in another time zone", day),
+            })))
+        }
+    };
+
+    if let Some(xapi) = xapi {
+        let actor = Actor {
+            user_id: user.id,
+            name: format!("{} {}", user.name, user.second_name),
+        };
+        xapi.record(vec![xapi.answered(&actor, test_id as i32, answer_id, check_result)]);
+    }
+
+    let description = match check_result {
+        true => "The answer is correct",
+        false => "The answer is incorrect",
+    };
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(json!({
+        "description": description,
+        "scores": scores,
+        "finished": finished,
+    })))
+}
+
+/// The day of the caller is taken when the request has the user in its body like `GET /user`
+#[get("/daily/leaderboard")]
+pub async fn get_daily_leaderboard(
+    query: Query<DailyLeaderboardQuery>,
+    user: Option<Json<UserForm>>,
+    pool: Data<DbPool>,
+) -> Result<HttpResponse> {
+    let caller = match user {
+        Some(user) => match authenticate(user.into_inner(), pool.clone()).await? {
+            Ok(user) => Some(user),
+            Err(response) => return Ok(response),
+        },
+        None => None,
+    };
+
+    let day = match (&query.day, caller) {
+        (Some(day), _) => match day.parse::<NaiveDate>() {
+            Ok(day) => day,
+            Err(_) => return Ok(HttpResponse::BadRequest().finish()),
+        },
+        (None, Some(caller)) => {
+            let pool = pool.clone();
+            let time_zone = block(move || db::time_zone(&pool, caller.id)).await.map_err(|err| {
+                error!("{}:{} Loading the user time zone failed - {:?}", file!(), line!(), err);
+                HttpResponse::InternalServerError().finish()
+            })?;
+            db::local_day(time_zone.as_deref(), Utc::now())
+        }
+        (None, None) => db::local_day(None, Utc::now()),
+    };
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let leaderboard = block(move || db::daily_leaderboard(&pool, &day.to_string(), page, per_page))
+        .await
+        .map_err(|err| {
+            error!(
+                "{}:{} Loading the daily leaderboard failed - {:?}",
+                file!(),
+                line!(),
+                err
+            );
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(leaderboard))
+}
+
+/// The daily challenges and streaks of the user follow the calendar of the time zone
+#[put("/user/time_zone")]
+pub async fn set_time_zone(form: Json<TimeZoneForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let TimeZoneForm { user, time_zone } = form.into_inner();
+    let time_zone = match time_zone.map(|time_zone| time_zone.trim().parse::<Tz>()) {
+        Some(Ok(time_zone)) => Some(time_zone.name().to_owned()),
+        Some(Err(_)) => return Ok(HttpResponse::BadRequest().finish()),
+        None => None,
+    };
+
+    let user = match authenticate(user, pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let time_zone_clone = time_zone.clone();
+    block(move || db::set_time_zone(&pool, user.id, time_zone_clone.as_deref()))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Changing the user time zone failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(json!({
+        "time_zone": time_zone,
+        "day": db::local_day(time_zone.as_deref(), Utc::now()).to_string(),
+    })))
+}
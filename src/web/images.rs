@@ -0,0 +1,182 @@
+use actix_web::{
+    get,
+    http::header::{
+        self, CacheControl, CacheDirective, ContentRange, ContentRangeSpec, ETag, EntityTag, Header, IfNoneMatch,
+        IfRange,
+    },
+    web::{Data, Path, Query},
+    HttpRequest, HttpResponse, Result,
+};
+use log::debug;
+use serde::Deserialize;
+
+use super::cached_bank;
+use crate::{cache::QuestionCache, db::DbPool};
+
+const IMAGE_MAX_AGE: u32 = 24 * 60 * 60;
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageVariant {
+    Original,
+    Thumbnail,
+    Retina,
+}
+
+#[derive(Deserialize)]
+pub struct ImageQuery {
+    pub variant: Option<ImageVariant>,
+}
+
+#[get("/images/{id}")]
+pub async fn get_image(
+    request: HttpRequest,
+    id: Path<i32>,
+    query: Query<ImageQuery>,
+    pool: Data<DbPool>,
+    cache: Data<QuestionCache>,
+) -> Result<HttpResponse> {
+    let bank = cached_bank(pool, cache).await?;
+
+    let test = match bank.test(id.into_inner()) {
+        Some(test) => test,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+    let image = match query.variant.unwrap_or(ImageVariant::Original) {
+        ImageVariant::Original => test.image.as_ref(),
+        ImageVariant::Thumbnail => test.thumbnail.as_ref(),
+        ImageVariant::Retina => test.retina.as_ref(),
+    };
+    let image = match image {
+        Some(image) => image,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    // The hash of the content, so it only changes with the image, unlike the time the bank was loaded at
+    let etag = EntityTag::strong(image.etag.clone());
+
+    if is_not_modified(&request, &etag) {
+        debug!("The image of {} test isn't modified", test.id);
+        return Ok(HttpResponse::NotModified().set(ETag(etag)).finish());
+    }
+
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type(image.content_type.as_str())
+        .set(ETag(etag.clone()))
+        .set(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(IMAGE_MAX_AGE),
+        ]))
+        .header(header::ACCEPT_RANGES, "bytes");
+
+    let length = image.data.len() as u64;
+    let range = match request.headers().get(header::RANGE) {
+        Some(range) if if_range_matches(&request, &etag) => range.to_str().ok().map(parse_range),
+        _ => None,
+    };
+
+    match range {
+        Some(Some(spec)) => match spec.satisfiable_range(length) {
+            Some((start, end)) => Ok(response
+                .status(actix_web::http::StatusCode::PARTIAL_CONTENT)
+                .set(ContentRange(ContentRangeSpec::Bytes {
+                    range: Some((start, end)),
+                    instance_length: Some(length),
+                }))
+                .body(image.data[start as usize..=end as usize].to_vec())),
+            None => Ok(HttpResponse::RangeNotSatisfiable()
+                .set(ContentRange(ContentRangeSpec::Bytes {
+                    range: None,
+                    instance_length: Some(length),
+                }))
+                .finish()),
+        },
+        // Unsupported ranges (multiple or not in bytes) are ignored and the whole image is sent
+        _ => Ok(response.body(image.data.clone())),
+    }
+}
+
+fn is_not_modified(request: &HttpRequest, etag: &EntityTag) -> bool {
+    if !request.headers().contains_key(header::IF_NONE_MATCH) {
+        return false;
+    }
+
+    match IfNoneMatch::parse(request) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        Err(_) => false,
+    }
+}
+
+fn if_range_matches(request: &HttpRequest, etag: &EntityTag) -> bool {
+    if !request.headers().contains_key(header::IF_RANGE) {
+        return true;
+    }
+
+    match IfRange::parse(request) {
+        Ok(IfRange::EntityTag(tag)) => tag.strong_eq(etag),
+        // There is no `Last-Modified` to compare the date with, so the whole image is sent
+        Ok(IfRange::Date(_)) | Err(_) => false,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum RangeSpec {
+    FromTo(u64, u64),
+    AllFrom(u64),
+    Last(u64),
+}
+
+impl RangeSpec {
+    /// Returns inclusive bounds of the range or `None` if it is outside of the data
+    fn satisfiable_range(&self, length: u64) -> Option<(u64, u64)> {
+        match *self {
+            RangeSpec::FromTo(from, to) if from < length && from <= to => Some((from, to.min(length - 1))),
+            RangeSpec::AllFrom(from) if from < length => Some((from, length - 1)),
+            RangeSpec::Last(last) if last > 0 && length > 0 => Some((length.saturating_sub(last), length - 1)),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a single range of the `Range: bytes=...` header, multiple ranges aren't supported
+fn parse_range(header: &str) -> Option<RangeSpec> {
+    let spec = header.trim().strip_prefix("bytes=")?.trim();
+    if spec.contains(',') {
+        return None;
+    }
+
+    let (from, to) = spec.split_at(spec.find('-')?);
+    let to = &to[1..];
+
+    match (from.trim(), to.trim()) {
+        ("", "") => None,
+        ("", last) => last.parse().ok().map(RangeSpec::Last),
+        (from, "") => from.parse().ok().map(RangeSpec::AllFrom),
+        (from, to) => Some(RangeSpec::FromTo(from.parse().ok()?, to.parse().ok()?)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_range_supports_all_the_single_range_forms() {
+        assert_eq!(parse_range("bytes=0-99"), Some(RangeSpec::FromTo(0, 99)));
+        assert_eq!(parse_range("bytes=100-"), Some(RangeSpec::AllFrom(100)));
+        assert_eq!(parse_range("bytes=-50"), Some(RangeSpec::Last(50)));
+        assert_eq!(parse_range("bytes=0-1, 5-6"), None);
+        assert_eq!(parse_range("items=0-1"), None);
+        assert_eq!(parse_range("bytes=-"), None);
+    }
+
+    #[test]
+    fn satisfiable_range_clamps_to_the_data_length() {
+        assert_eq!(RangeSpec::FromTo(10, 1000).satisfiable_range(100), Some((10, 99)));
+        assert_eq!(RangeSpec::Last(1000).satisfiable_range(100), Some((0, 99)));
+        assert_eq!(RangeSpec::AllFrom(100).satisfiable_range(100), None);
+        assert_eq!(RangeSpec::FromTo(5, 1).satisfiable_range(100), None);
+    }
+}
@@ -0,0 +1,101 @@
+use actix_web::{
+    get, put,
+    web::{block, Data, Json, Query},
+    HttpResponse, Result,
+};
+use chrono::Utc;
+use log::error;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::authenticate;
+use crate::{
+    db::{self, DbPool, LeaderboardWindow},
+    model::UserForm,
+};
+
+pub(super) const DEFAULT_PAGE_SIZE: usize = 20;
+pub(super) const MAX_PAGE_SIZE: usize = 100;
+const MAX_DISPLAY_NAME_LEN: usize = 50;
+
+#[derive(Deserialize)]
+pub struct LeaderboardQuery {
+    pub window: Option<LeaderboardWindow>,
+    /// 1-based
+    pub page: Option<usize>,
+    pub per_page: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct PrivacyForm {
+    pub user: UserForm,
+    /// Shown on the leaderboard instead of the real name, the real name is shown when it's missing
+    pub display_name: Option<String>,
+    /// Leaves the leaderboard entirely
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+/// The caller's own rank is returned too when the request has the user in its body like `GET /user`
+#[get("/leaderboard")]
+pub async fn get_leaderboard(
+    query: Query<LeaderboardQuery>,
+    user: Option<Json<UserForm>>,
+    pool: Data<DbPool>,
+) -> Result<HttpResponse> {
+    let caller = match user {
+        Some(user) => match authenticate(user.into_inner(), pool.clone()).await? {
+            Ok(user) => Some(user),
+            Err(response) => return Ok(response),
+        },
+        None => None,
+    };
+
+    let window = query.window.unwrap_or_default();
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let leaderboard = block(move || db::leaderboard(&pool, window, page, per_page, caller.as_ref(), Utc::now()))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Loading the leaderboard failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(leaderboard))
+}
+
+#[put("/user/privacy")]
+pub async fn set_privacy(form: Json<PrivacyForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let PrivacyForm {
+        user,
+        display_name,
+        hidden,
+    } = form.into_inner();
+    let display_name = display_name
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty());
+    if display_name
+        .as_ref()
+        .is_some_and(|name| name.chars().count() > MAX_DISPLAY_NAME_LEN)
+    {
+        return Ok(HttpResponse::BadRequest().finish());
+    }
+
+    let user = match authenticate(user, pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let display_name_clone = display_name.clone();
+    block(move || db::set_leaderboard_privacy(user.id, display_name_clone.as_deref(), hidden, &pool))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Changing the user privacy failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(json!({
+        "display_name": display_name,
+        "hidden": hidden,
+    })))
+}
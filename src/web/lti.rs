@@ -0,0 +1,268 @@
+use actix_web::{
+    get, post,
+    web::{block, Data, Form, Json, Path, Query},
+    HttpRequest, HttpResponse, Result,
+};
+use log::{debug, error, info};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{cached_bank, test_json, AnswerForm, SCORES_FOR_RIGHT_ANSWER};
+use crate::{
+    cache::QuestionCache,
+    db::{self, DbPool, ExamSource},
+    lti::{LoginRequest, Lti, LtiSession, SessionMode},
+    model::NewUserAnswer,
+    xapi::{Actor, Xapi},
+};
+
+/// Page the launch opens in the LMS frame, it plays the session through the `/lti/sessions` services
+const PLAYER: &str = include_str!("lti/player.html");
+
+#[derive(Deserialize)]
+pub struct LaunchForm {
+    pub id_token: String,
+    pub state: String,
+}
+
+fn login(request: &LoginRequest, lti: &Lti) -> HttpResponse {
+    match lti.login(request) {
+        Ok(url) => HttpResponse::Found().header("Location", url).finish(),
+        Err(err) => {
+            debug!("The LTI login was rejected - {}", err);
+            HttpResponse::BadRequest().finish()
+        }
+    }
+}
+
+/// Platforms start the login with either a GET or a form POST
+#[get("/lti/login")]
+pub async fn lti_login(request: Query<LoginRequest>, lti: Data<Lti>) -> HttpResponse {
+    login(&request, &lti)
+}
+
+#[post("/lti/login")]
+pub async fn lti_login_form(request: Form<LoginRequest>, lti: Data<Lti>) -> HttpResponse {
+    login(&request, &lti)
+}
+
+#[post("/lti/launch")]
+pub async fn lti_launch(
+    form: Form<LaunchForm>,
+    lti: Data<Lti>,
+    pool: Data<DbPool>,
+    cache: Data<QuestionCache>,
+) -> Result<HttpResponse> {
+    let claims = match lti.launch(&form.id_token, &form.state).await {
+        Ok(claims) => claims,
+        Err(err) => {
+            debug!("The LTI launch was rejected - {}", err);
+            return Ok(HttpResponse::Unauthorized().finish());
+        }
+    };
+
+    let (given_name, family_name) = claims.names();
+    let issuer = lti.config.issuer.clone();
+    let subject = claims.sub.clone();
+    let pool_clone = pool.clone();
+    let user = block(move || db::lti_user(&pool_clone, &issuer, &subject, &given_name, &family_name))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Mapping an LTI user failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    let bank = cached_bank(pool, cache).await?;
+    let test_ids = bank
+        .random_tests(claims.questions(&lti.config))
+        .iter()
+        .map(|test| test.id)
+        .collect::<Vec<_>>();
+    if test_ids.is_empty() {
+        error!("{}:{} There are no tests to serve", file!(), line!());
+        return Ok(HttpResponse::InternalServerError().finish());
+    }
+
+    let session = LtiSession::new(&user, &claims, test_ids);
+    info!(
+        "The {} {} user launched a {:?} session of {} tests",
+        user.name,
+        user.second_name,
+        session.mode,
+        session.test_ids.len()
+    );
+    let token = lti.start_session(session);
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(PLAYER.replace("{{session}}", &token)))
+}
+
+#[get("/lti/sessions/{token}")]
+pub async fn lti_session(
+    request: HttpRequest,
+    token: Path<String>,
+    lti: Data<Lti>,
+    pool: Data<DbPool>,
+    cache: Data<QuestionCache>,
+) -> Result<HttpResponse> {
+    let session = lti.with_session(&token, |session| {
+        (
+            session.mode,
+            session.current_test(),
+            session.results.len(),
+            session.test_ids.len(),
+            session.right_answers(),
+        )
+    });
+    let (mode, current_test, answered, total, right_answers) = match session {
+        Some(session) => session,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let bank = cached_bank(pool, cache).await?;
+    let test = current_test.and_then(|id| bank.test(id));
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(json!({
+        "mode": mode,
+        "answered": answered,
+        "total": total,
+        "right_answers": right_answers,
+        "finished": current_test.is_none(),
+        "test": test.map(|test| test_json(&request, &test, None)),
+    })))
+}
+
+/// Answers the current test of the session, the score is sent to the LMS after the last one
+#[post("/lti/sessions/{token}/answers")]
+pub async fn lti_answer(
+    token: Path<String>,
+    answer: Json<AnswerForm>,
+    lti: Data<Lti>,
+    pool: Data<DbPool>,
+    cache: Data<QuestionCache>,
+    xapi: Option<Data<Xapi>>,
+) -> Result<HttpResponse> {
+    let AnswerForm { test_id, answer_id } = answer.into_inner();
+    let bank = cached_bank(pool.clone(), cache).await?;
+    let test = match bank.test(test_id as i32) {
+        Some(test) => test,
+        None => return Ok(HttpResponse::BadRequest().finish()),
+    };
+
+    let correct = test.right_answer_id == answer_id as i32;
+    let outcome = lti.with_session(&token, |session| {
+        if !session.begin_answer(test.id) {
+            return None;
+        }
+
+        let total = session.test_ids.len();
+        Some((
+            session.mode,
+            session.user_id,
+            session.results.len() + 1 == total,
+            session.right_answers() + correct as usize,
+            total,
+            session.line_item.clone(),
+            session.subject.clone(),
+        ))
+    });
+    let (mode, user_id, finished, right_answers, total, line_item, subject) = match outcome {
+        Some(Some(outcome)) => outcome,
+        // Tests are answered one by one in the session order
+        Some(None) => return Ok(HttpResponse::Conflict().finish()),
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let scores = match correct {
+        true => SCORES_FOR_RIGHT_ANSWER,
+        false => 0,
+    };
+    let answer = NewUserAnswer::new(user_id, test.id, answer_id as i32, correct, scores as i32);
+    // The session only moves on once the answer is in the DB, so a failed write can be answered again
+    let recorded = block(move || {
+        db::record_answer(&answer, &pool)?;
+        match (finished, mode) {
+            (true, SessionMode::Exam) => db::record_exam(
+                &pool,
+                user_id,
+                ExamSource::Lti,
+                total,
+                right_answers,
+                answer.answered_at,
+            ),
+            _ => Ok(()),
+        }
+    })
+    .await;
+    if let Err(err) = recorded {
+        lti.with_session(&token, |session| session.end_answer(None));
+        error!("{}:{} Failed to add new scores - {:?}", file!(), line!(), err);
+        return Ok(HttpResponse::InternalServerError().finish());
+    }
+
+    lti.with_session(&token, |session| {
+        session.end_answer(Some(correct));
+
+        if let Some(xapi) = xapi.as_ref() {
+            let actor = Actor {
+                user_id: session.user_id,
+                name: session.user_name.clone(),
+            };
+            let mut statements = vec![xapi.answered(&actor, test.id, answer_id, correct)];
+            if session.is_finished() {
+                let exam = format!("lti/{}", session.resource_link_id.as_deref().unwrap_or("session"));
+                let passed = match session.mode {
+                    SessionMode::Exam => Some(session.is_passed()),
+                    SessionMode::Practice => None,
+                };
+                statements.extend(xapi.exam_results(
+                    &actor,
+                    &exam,
+                    session.right_answers(),
+                    session.test_ids.len(),
+                    passed,
+                ));
+            }
+            xapi.record(statements);
+        }
+    });
+
+    let score_sent = match (finished, line_item) {
+        (true, Some(line_item)) => match lti.send_score(&line_item, &subject, right_answers, total).await {
+            Ok(()) => true,
+            Err(err) => {
+                error!("{}:{} Sending an LTI score failed - {:?}", file!(), line!(), err);
+                false
+            }
+        },
+        _ => false,
+    };
+
+    let mut response = json!({
+        "finished": finished,
+        "score_sent": score_sent,
+    });
+    if mode == SessionMode::Practice {
+        response["correct"] = json!(correct);
+        response["right_answer_id"] = json!(test.right_answer_id);
+        response["explanation"] = json!(test.explanation);
+    }
+    if finished {
+        response["right_answers"] = json!(right_answers);
+        response["total"] = json!(total);
+    }
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(response))
+}
+
+/// Key set the platform verifies the tool token requests with
+#[get("/lti/jwks")]
+pub async fn lti_jwks(lti: Data<Lti>) -> Result<HttpResponse> {
+    let key_set = lti.key_set().map_err(|err| {
+        error!("{}:{} Building the LTI key set failed - {:?}", file!(), line!(), err);
+        HttpResponse::InternalServerError().finish()
+    })?;
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(key_set))
+}
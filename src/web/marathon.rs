@@ -0,0 +1,86 @@
+use actix_web::{
+    delete, get, post,
+    web::{block, Data, Json},
+    HttpResponse, Result,
+};
+use chrono::Utc;
+use log::error;
+use serde::Deserialize;
+
+use super::authenticate;
+use crate::{
+    db::{self, DbPool},
+    model::UserForm,
+};
+
+#[derive(Deserialize)]
+pub struct MarathonForm {
+    pub user: UserForm,
+    /// Walks through the tests of the category only
+    pub category: Option<String>,
+}
+
+/// Starts the marathon from the beginning, the tests are then served by `GET /test?mode=marathon`
+#[post("/marathon")]
+pub async fn start_marathon(form: Json<MarathonForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let MarathonForm { user, category } = form.into_inner();
+    let user = match authenticate(user, pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let progress = block(
+        move || match db::start_marathon(&pool, user.id, category, Utc::now().timestamp())? {
+            true => db::marathon(&pool, user.id),
+            // A category without tests has nothing to walk through
+            false => Ok(None),
+        },
+    )
+    .await
+    .map_err(|err| {
+        error!("{}:{} Starting a marathon failed - {:?}", file!(), line!(), err);
+        HttpResponse::InternalServerError().finish()
+    })?;
+
+    Ok(match progress {
+        Some(progress) => HttpResponse::Ok().content_type("application/json").json(progress),
+        None => HttpResponse::BadRequest().finish(),
+    })
+}
+
+#[get("/marathon")]
+pub async fn get_marathon(user: Json<UserForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let user = match authenticate(user.into_inner(), pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let progress = block(move || db::marathon(&pool, user.id)).await.map_err(|err| {
+        error!("{}:{} Loading the marathon failed - {:?}", file!(), line!(), err);
+        HttpResponse::InternalServerError().finish()
+    })?;
+
+    Ok(match progress {
+        Some(progress) => HttpResponse::Ok().content_type("application/json").json(progress),
+        None => HttpResponse::NotFound().finish(),
+    })
+}
+
+/// Drops the marathon, the next `GET /test?mode=marathon` starts a new one over the whole bank
+#[delete("/marathon")]
+pub async fn end_marathon(user: Json<UserForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let user = match authenticate(user.into_inner(), pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let ended = block(move || db::end_marathon(&pool, user.id)).await.map_err(|err| {
+        error!("{}:{} Ending the marathon failed - {:?}", file!(), line!(), err);
+        HttpResponse::InternalServerError().finish()
+    })?;
+
+    Ok(match ended {
+        true => HttpResponse::Ok().finish(),
+        false => HttpResponse::NotFound().finish(),
+    })
+}
@@ -0,0 +1,529 @@
+use actix_web::{
+    get, post,
+    web::{block, Data, Json, Query},
+    HttpRequest, HttpResponse, Result,
+};
+use chrono::{DateTime, Utc};
+use log::{debug, error, info};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+
+use crate::{
+    cache::{CachedBank, CachedTest, QuestionCache},
+    db::{self, DbPool},
+    irt::IrtConfig,
+    model::{NewUserAnswer, User, UserForm},
+    xapi::{Actor, Xapi},
+};
+
+mod achievements;
+mod admin;
+mod collections;
+mod daily;
+mod images;
+mod leaderboard;
+mod lti;
+mod marathon;
+mod progress;
+pub use achievements::{dismiss_notifications, get_achievements, get_notifications};
+pub use admin::{question_quality, AdminConfig};
+pub use collections::{
+    add_bookmark, add_to_collection, create_collection, delete_collection, get_bookmarks, get_collections,
+    remove_bookmark, remove_from_collection,
+};
+pub use daily::{answer_daily, get_daily, get_daily_leaderboard, set_time_zone};
+pub use images::get_image;
+pub use leaderboard::{get_leaderboard, set_privacy};
+pub use lti::{lti_answer, lti_jwks, lti_launch, lti_login, lti_login_form, lti_session};
+pub use marathon::{end_marathon, get_marathon, start_marathon};
+pub use progress::get_progress;
+
+pub(crate) const SCORES_FOR_RIGHT_ANSWER: u32 = 5;
+
+#[derive(Deserialize, Serialize)]
+pub struct AnswerForm {
+    pub test_id: u32,
+    pub answer_id: u32,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AnswerWithUserForm {
+    pub answer: AnswerForm,
+    pub user: UserForm,
+    /// Milliseconds the user took to answer, it's recorded for the question quality report
+    #[serde(default)]
+    pub response_time_ms: Option<u32>,
+}
+
+#[post("/user")]
+pub async fn sing_up(user: Json<UserForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let user = user.into_inner();
+    info!(
+        "There is a new user:[name - {}, second name - {}]",
+        user.name, user.second_name
+    );
+
+    let user_clone = user.clone();
+    let pool_clone = pool.clone();
+    let check_passed = block(move || db::check_if_user_exists(user_clone, pool_clone))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Checking if a user exists error - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    if check_passed {
+        debug!(
+            "The [{} {}] user already present in the DB",
+            user.name, user.second_name
+        );
+        return Ok(HttpResponse::Conflict().finish());
+    }
+
+    let user_clone = user.clone();
+    block(move || db::registry_new_user(user_clone, pool))
+        .await
+        .map_err(|err| {
+            error!(
+                "{}:{} An error occurred while registering a new user - {:?}",
+                file!(),
+                line!(),
+                err
+            );
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    debug!("Successfully registry {} {} user", user.name, user.second_name);
+
+    let UserForm { name, second_name, .. } = user;
+
+    let http_response = HttpResponse::Created().content_type("application/json").json(json!({
+        "name" : name,
+        "second_name" : second_name,
+        "scores" : 0
+    }));
+
+    Ok(http_response)
+}
+
+#[get("/user")]
+pub async fn sing_in(user: Json<UserForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let user = user.into_inner();
+    debug!(
+        "Validation of user:[name - {}, second name - {}]",
+        user.name, user.second_name
+    );
+
+    let user_clone = user.clone();
+    let pool_clone = pool.clone();
+    let check_passed = block(move || db::check_if_user_exists(user_clone, pool_clone))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Checking if a user exists error - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    if !check_passed {
+        debug!("The user doesn't present in the DB");
+        return Ok(HttpResponse::BadRequest().finish());
+    }
+
+    debug!("The {} {} user exists in the DB", user.name, user.second_name);
+    let user_clone = user.clone();
+    let pool_clone = pool.clone();
+    let verify_password_passed = block(move || db::verify_password(user_clone, pool_clone))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Verifying an user password failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    if !verify_password_passed {
+        debug!("The user hasn't passed password verify");
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+    debug!("The user passed password verifying");
+
+    let user_clone = user.clone();
+    let pool_clone = pool.clone();
+    let scores = block(move || db::get_scores(&user_clone, &pool_clone))
+        .await
+        .map_err(|err| {
+            error!(
+                "{}:{} An error occurred while getting a user scores - {:?}",
+                file!(),
+                line!(),
+                err
+            );
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    let UserForm { name, second_name, .. } = user;
+
+    let http_response = HttpResponse::Found().content_type("application/json").json(json!({
+       "name"  : name,
+       "second_name": second_name,
+       "scores"     : scores,
+    }));
+
+    Ok(http_response)
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageEncoding {
+    Url,
+    Base64,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TestMode {
+    /// A uniformly random test
+    #[default]
+    Random,
+    /// The test most due in the spaced repetition schedule of the user
+    Review,
+    /// A test the user answered wrong and hasn't cleared by answering it right since
+    Mistakes,
+    /// The test most informative at the IRT estimate of the ability of the user
+    Adaptive,
+    /// The next test of the marathon of the user, a marathon over the whole bank is started when there is none
+    Marathon,
+}
+
+#[derive(Deserialize)]
+pub struct TestQuery {
+    /// Old clients can still ask for the image inlined as base64 with `?image=base64`
+    pub image: Option<ImageEncoding>,
+    #[serde(default)]
+    pub mode: TestMode,
+    /// Right answers in a row that clear a test from the mistakes, 1 by default
+    pub clear_after: Option<usize>,
+    /// Practices the tests of a collection of the user, only in the random mode
+    pub collection: Option<i32>,
+}
+
+pub(crate) async fn cached_bank(pool: Data<DbPool>, cache: Data<QuestionCache>) -> Result<Arc<CachedBank>> {
+    match cache.bank() {
+        Some(bank) => Ok(bank),
+        None => block(move || cache.load_bank(&pool)).await.map_err(|err| {
+            error!("{}:{} Loading the tests cache failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish().into()
+        }),
+    }
+}
+
+/// The JSON a test is served as, its images are linked to `get_image`
+pub(crate) fn test_json(request: &HttpRequest, test: &CachedTest, encoding: Option<ImageEncoding>) -> Value {
+    let image_url = |variant: Option<&str>| {
+        let url = request
+            .url_for("get_image", &[test.id.to_string()])
+            .map(|url| url.path().to_owned())
+            // `get_image` isn't registered when `get_test` is mounted on its own
+            .unwrap_or_else(|_| format!("/images/{}", test.id));
+
+        match variant {
+            Some(variant) => format!("{}?variant={}", url, variant),
+            None => url,
+        }
+    };
+    let image = match encoding {
+        Some(ImageEncoding::Base64) => test.image.as_ref().map(|image| image.base64()),
+        _ => None,
+    };
+
+    json!({
+        "id": test.id,
+        "description": test.description,
+        "answers"    : test.answers,
+        "image_url": test.image.as_ref().map(|_| image_url(None)),
+        "thumbnail_url": test.thumbnail.as_ref().map(|_| image_url(Some("thumbnail"))),
+        "retina_image_url": test.retina.as_ref().map(|_| image_url(Some("retina"))),
+        "image": image,
+    })
+}
+
+/// The modes other than `random` pick the test for the user, who is passed in the body like in `GET /user`
+#[get("/test")]
+pub async fn get_test(
+    request: HttpRequest,
+    query: Query<TestQuery>,
+    user: Option<Json<UserForm>>,
+    pool: Data<DbPool>,
+    cache: Data<QuestionCache>,
+    irt: Option<Data<IrtConfig>>,
+) -> Result<HttpResponse> {
+    let user = match (query.mode, user) {
+        (TestMode::Random, _) if query.collection.is_none() => None,
+        (_, _) if query.collection.is_some() && query.mode != TestMode::Random => {
+            return Ok(HttpResponse::BadRequest().finish())
+        }
+        (_, Some(user)) => match authenticate(user.into_inner(), pool.clone()).await? {
+            Ok(user) => Some(user),
+            Err(response) => return Ok(response),
+        },
+        (_, None) => return Ok(HttpResponse::BadRequest().finish()),
+    };
+
+    let clear_after = query.clear_after.unwrap_or(1);
+    if clear_after == 0 {
+        return Ok(HttpResponse::BadRequest().finish());
+    }
+
+    let bank = cached_bank(pool.clone(), cache).await?;
+    // The fields the mode adds to the test
+    let mut extra = Map::new();
+    let test = match (query.mode, user) {
+        (TestMode::Review, Some(user)) => {
+            let pick = block(move || db::review_test(&pool, user.id, Utc::now().timestamp()))
+                .await
+                .map_err(|err| {
+                    error!("{}:{} Picking a test to review failed - {:?}", file!(), line!(), err);
+                    HttpResponse::InternalServerError().finish()
+                })?;
+            // `null` for a test the user sees for the first time
+            let due_at = pick
+                .as_ref()
+                .and_then(|pick| pick.due_at)
+                .and_then(|due_at| DateTime::from_timestamp(due_at, 0))
+                .map(|due_at| due_at.to_rfc3339());
+            extra.insert("due_at".to_owned(), json!(due_at));
+            pick.and_then(|pick| bank.test(pick.test_id))
+        }
+        (TestMode::Mistakes, Some(user)) => {
+            let mistakes = block(move || db::mistakes(&pool, user.id, clear_after))
+                .await
+                .map_err(|err| {
+                    error!("{}:{} Loading the mistakes failed - {:?}", file!(), line!(), err);
+                    HttpResponse::InternalServerError().finish()
+                })?;
+            let test_id = match mistakes.choose(&mut rand::thread_rng()) {
+                Some(test_id) => *test_id,
+                None => {
+                    return Ok(HttpResponse::NotFound()
+                        .content_type("application/json")
+                        .json(json!({"description": "There are no mistakes to review"})))
+                }
+            };
+            // The served test included
+            extra.insert("mistakes_left".to_owned(), json!(mistakes.len()));
+            bank.test(test_id)
+        }
+        (TestMode::Adaptive, Some(user)) => {
+            let target_se = irt
+                .map(|irt| irt.target_se)
+                .unwrap_or_else(|| IrtConfig::default().target_se);
+            let pick = block(move || db::adaptive_test(&pool, user.id, target_se))
+                .await
+                .map_err(|err| {
+                    error!("{}:{} Picking an adaptive test failed - {:?}", file!(), line!(), err);
+                    HttpResponse::InternalServerError().finish()
+                })?;
+            // The ability is measured, there is no test to serve anymore
+            let test_id = match pick.test_id {
+                Some(test_id) => test_id,
+                None => {
+                    return Ok(HttpResponse::Ok()
+                        .content_type("application/json")
+                        .json(json!({ "adaptive": pick })))
+                }
+            };
+            extra.insert("adaptive".to_owned(), json!(pick));
+            bank.test(test_id)
+        }
+        (TestMode::Marathon, Some(user)) => {
+            let progress = block(move || match db::marathon(&pool, user.id)? {
+                Some(progress) => Ok(progress),
+                None => match db::start_marathon(&pool, user.id, None, Utc::now().timestamp())? {
+                    true => db::marathon(&pool, user.id)?.ok_or_else(|| anyhow!("The started marathon is missing")),
+                    false => Err(anyhow!("There are no tests for a marathon")),
+                },
+            })
+            .await
+            .map_err(|err| {
+                error!("{}:{} Loading the marathon failed - {:?}", file!(), line!(), err);
+                HttpResponse::InternalServerError().finish()
+            })?;
+            // Every test is answered, the marathon stays finished until it's started again
+            let test_id = match progress.test_id {
+                Some(test_id) => test_id,
+                None => {
+                    return Ok(HttpResponse::Ok()
+                        .content_type("application/json")
+                        .json(json!({ "marathon": progress })))
+                }
+            };
+            extra.insert("marathon".to_owned(), json!(progress));
+            bank.test(test_id)
+        }
+        (TestMode::Random, Some(user)) => {
+            let collection_id = query.collection.unwrap_or_default();
+            let tests = block(move || db::collection_tests(&pool, user.id, collection_id))
+                .await
+                .map_err(|err| {
+                    error!("{}:{} Loading a collection failed - {:?}", file!(), line!(), err);
+                    HttpResponse::InternalServerError().finish()
+                })?;
+            let test_id = match tests.as_ref().and_then(|tests| tests.choose(&mut rand::thread_rng())) {
+                Some(test_id) => *test_id,
+                // The user has no such collection or it's empty
+                None => return Ok(HttpResponse::NotFound().finish()),
+            };
+            bank.test(test_id)
+        }
+        _ => bank.random_test(),
+    };
+    let test = test.ok_or_else(|| {
+        error!("{}:{} There are no tests to serve", file!(), line!());
+        HttpResponse::InternalServerError().finish()
+    })?;
+
+    let mut test_json = test_json(&request, &test, query.image);
+    if let Value::Object(test_json) = &mut test_json {
+        test_json.extend(extra);
+    }
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(test_json))
+}
+
+#[get("/cache/stats")]
+pub async fn cache_stats(cache: Data<QuestionCache>) -> HttpResponse {
+    HttpResponse::Ok().content_type("application/json").json(cache.stats())
+}
+
+#[get("/check_answer")]
+pub async fn check_answer(query_data: Query<AnswerForm>, pool: Data<DbPool>) -> Result<HttpResponse> {
+    let AnswerForm { test_id, answer_id } = query_data.into_inner();
+
+    let check_result = block(move || db::check_test_answer(test_id, answer_id, &pool))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Checking a test answer failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish();
+        })?;
+
+    let json_data = match check_result {
+        true => json!({
+            "description": "The answer is correct",
+            "scores": SCORES_FOR_RIGHT_ANSWER,
+        }),
+        false => json!({
+            "description": "The answer is incorrect",
+            "scores": 0
+            ,
+        }),
+    };
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(json_data))
+}
+
+/// Finds the user of the form when the password is right, otherwise the response to send instead:
+/// 400 for an unknown user and 403 for a wrong password
+pub(crate) async fn authenticate(
+    user: UserForm,
+    pool: Data<DbPool>,
+) -> Result<std::result::Result<User, HttpResponse>> {
+    let user_clone = user.clone();
+    let pool_clone = pool.clone();
+    let check_passed = block(move || db::check_if_user_exists(user_clone, pool_clone))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Checking if a user exists error - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    if !check_passed {
+        return Ok(Err(HttpResponse::BadRequest().finish()));
+    }
+
+    let user_clone = user.clone();
+    let pool_clone = pool.clone();
+    let verify_passed = block(move || db::verify_password(user_clone, pool_clone))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Verifying an user password failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    if !verify_passed {
+        return Ok(Err(HttpResponse::Forbidden().finish()));
+    }
+
+    let user = block(move || db::get_user(&user, &pool)).await.map_err(|err| {
+        error!("{}:{} Failed to find the user - {:?}", file!(), line!(), err);
+        HttpResponse::InternalServerError().finish()
+    })?;
+
+    Ok(Ok(user))
+}
+
+#[post("/check_test")]
+pub async fn check_answer_with_user(
+    user_data: Json<AnswerWithUserForm>,
+    pool: Data<DbPool>,
+    xapi: Option<Data<Xapi>>,
+) -> Result<HttpResponse> {
+    let AnswerWithUserForm {
+        user,
+        answer,
+        response_time_ms,
+    } = user_data.into_inner();
+    let AnswerForm { test_id, answer_id } = answer;
+
+    let user = match authenticate(user, pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let pool_clone = pool.clone();
+    let check_result = block(move || db::check_test_answer(test_id, answer_id, &pool_clone))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Checking a test answer failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish();
+        })?;
+
+    let scores = match check_result {
+        true => SCORES_FOR_RIGHT_ANSWER,
+        false => 0,
+    };
+    let answer = NewUserAnswer {
+        response_ms: response_time_ms.map(|response_ms| response_ms.min(i32::MAX as u32) as i32),
+        ..NewUserAnswer::new(user.id, test_id as i32, answer_id as i32, check_result, scores as i32)
+    };
+    block(move || db::record_answer(&answer, &pool)).await.map_err(|err| {
+        error!("{}:{} Failed to add new scores - {:?}", file!(), line!(), err);
+        HttpResponse::InternalServerError().finish();
+    })?;
+
+    // The statement is only sent for an answer the DB has
+    if let Some(xapi) = xapi {
+        let actor = Actor {
+            user_id: user.id,
+            name: format!("{} {}", user.name, user.second_name),
+        };
+        xapi.record(vec![xapi.answered(&actor, test_id as i32, answer_id, check_result)]);
+    }
+
+    let json_data = match check_result {
+        true => json!({
+            "description": "The answer is correct",
+            "scores": SCORES_FOR_RIGHT_ANSWER,
+        }),
+        false => json!({
+            "description": "The answer is incorrect",
+            "scores": 0,
+        }),
+    };
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(json_data))
+}
+
+#[get("/healthy")]
+pub async fn healthy() -> HttpResponse {
+    let response = "Drive-tests is working and healthy".to_string();
+    HttpResponse::Ok().content_type("application/json").json(response)
+}
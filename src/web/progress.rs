@@ -0,0 +1,41 @@
+use actix_web::{
+    get,
+    web::{block, Data, Json, Query},
+    HttpResponse, Result,
+};
+use chrono::Utc;
+use log::error;
+use serde::Deserialize;
+
+use super::authenticate;
+use crate::{
+    db::{self, DbPool, TrendPeriod},
+    model::UserForm,
+};
+
+#[derive(Deserialize)]
+pub struct ProgressQuery {
+    pub period: Option<TrendPeriod>,
+}
+
+#[get("/user/progress")]
+pub async fn get_progress(
+    query: Query<ProgressQuery>,
+    user: Json<UserForm>,
+    pool: Data<DbPool>,
+) -> Result<HttpResponse> {
+    let user = match authenticate(user.into_inner(), pool.clone()).await? {
+        Ok(user) => user,
+        Err(response) => return Ok(response),
+    };
+
+    let period = query.period.unwrap_or_default();
+    let progress = block(move || db::progress(&pool, user.id, period, Utc::now()))
+        .await
+        .map_err(|err| {
+            error!("{}:{} Computing the user progress failed - {:?}", file!(), line!(), err);
+            HttpResponse::InternalServerError().finish()
+        })?;
+
+    Ok(HttpResponse::Ok().content_type("application/json").json(progress))
+}
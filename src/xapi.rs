@@ -0,0 +1,364 @@
+//! xAPI statements about the answers and the exam results, sent to a learning record store.
+//! Statements are buffered in memory and sent in the background, so a slow or down LRS never holds up an answer
+use actix_web::{
+    client::Client,
+    rt::{self, time::delay_for},
+};
+use anyhow::Context;
+use log::{error, warn};
+use openssl::base64;
+use serde_json::{json, Value};
+use std::{
+    collections::VecDeque,
+    env,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+const XAPI_VERSION: &str = "1.0.3";
+const DEFAULT_HOMEPAGE: &str = "https://drive-tests";
+const DEFAULT_BUFFER_SIZE: usize = 10_000;
+const DEFAULT_RETRY_SECS: u64 = 30;
+/// Statements sent in one request
+const BATCH_SIZE: usize = 50;
+
+/// The LRS the statements go to, read from the `XAPI_*` environment variables
+#[derive(Clone)]
+pub struct XapiConfig {
+    /// The xAPI endpoint of the LRS, statements are posted to its `statements` resource
+    pub endpoint: String,
+    /// Basic auth credentials, the LRS is called without them when there is no username
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// IRI the activities and the user accounts of the statements are under
+    pub homepage: String,
+    /// Statements kept while the LRS is down, the oldest ones are dropped beyond it
+    pub buffer_size: usize,
+    pub retry_interval: Duration,
+}
+
+impl XapiConfig {
+    /// `None` when `XAPI_ENDPOINT` isn't set, no statements are sent then
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let endpoint = match env::var("XAPI_ENDPOINT") {
+            Ok(endpoint) => endpoint,
+            Err(_) => return Ok(None),
+        };
+        let number = |name: &str, default: u64| match env::var(name) {
+            Ok(value) => value
+                .parse::<u64>()
+                .with_context(|| format!("{} isn't a number: {}", name, value)),
+            Err(_) => Ok(default),
+        };
+
+        Ok(Some(XapiConfig {
+            endpoint,
+            username: env::var("XAPI_USERNAME").ok(),
+            password: env::var("XAPI_PASSWORD").ok(),
+            homepage: env::var("XAPI_HOMEPAGE").unwrap_or_else(|_| DEFAULT_HOMEPAGE.to_owned()),
+            buffer_size: number("XAPI_BUFFER_SIZE", DEFAULT_BUFFER_SIZE as u64)? as usize,
+            retry_interval: Duration::from_secs(number("XAPI_RETRY_SECS", DEFAULT_RETRY_SECS)?),
+        }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Verb {
+    Answered,
+    Passed,
+    Failed,
+    Completed,
+}
+
+impl Verb {
+    fn id(self) -> &'static str {
+        match self {
+            Verb::Answered => "http://adlnet.gov/expapi/verbs/answered",
+            Verb::Passed => "http://adlnet.gov/expapi/verbs/passed",
+            Verb::Failed => "http://adlnet.gov/expapi/verbs/failed",
+            Verb::Completed => "http://adlnet.gov/expapi/verbs/completed",
+        }
+    }
+
+    fn display(self) -> &'static str {
+        match self {
+            Verb::Answered => "answered",
+            Verb::Passed => "passed",
+            Verb::Failed => "failed",
+            Verb::Completed => "completed",
+        }
+    }
+}
+
+/// The user a statement is about, identified by their account on the server
+pub struct Actor {
+    pub user_id: i32,
+    pub name: String,
+}
+
+enum Delivery {
+    Sent,
+    /// The LRS refused the statements, sending them again won't help
+    Rejected(String),
+    Failed(String),
+}
+
+pub struct Xapi {
+    config: XapiConfig,
+    buffer: Mutex<VecDeque<Value>>,
+    flushing: AtomicBool,
+}
+
+/// Statements have their own UUID, so an LRS can tell a retried statement from a new one
+fn statement_id() -> String {
+    let mut bytes = rand::random::<[u8; 16]>();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let id = hex::encode(bytes);
+
+    format!(
+        "{}-{}-{}-{}-{}",
+        &id[..8],
+        &id[8..12],
+        &id[12..16],
+        &id[16..20],
+        &id[20..]
+    )
+}
+
+impl Xapi {
+    pub fn new(config: XapiConfig) -> Self {
+        Xapi {
+            config,
+            buffer: Mutex::new(VecDeque::new()),
+            flushing: AtomicBool::new(false),
+        }
+    }
+
+    fn statement(&self, actor: &Actor, verb: Verb, object: Value, result: Value) -> Value {
+        json!({
+            "id": statement_id(),
+            "actor": {
+                "objectType": "Agent",
+                "name": actor.name,
+                "account": { "homePage": self.config.homepage, "name": actor.user_id.to_string() },
+            },
+            "verb": { "id": verb.id(), "display": { "en-US": verb.display() } },
+            "object": object,
+            "result": result,
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+        })
+    }
+
+    /// The answer of a single test, the answer is 1-based like in the API
+    pub fn answered(&self, actor: &Actor, test_id: i32, answer_id: u32, correct: bool) -> Value {
+        let object = json!({
+            "objectType": "Activity",
+            "id": format!("{}/tests/{}", self.config.homepage, test_id),
+            "definition": {
+                "type": "http://adlnet.gov/expapi/activities/cmi.interaction",
+                "interactionType": "choice",
+            },
+        });
+
+        self.statement(
+            actor,
+            Verb::Answered,
+            object,
+            json!({ "success": correct, "response": answer_id.to_string() }),
+        )
+    }
+
+    /// `completed` for a finished session of tests and `passed` or `failed` too when it was an exam
+    pub fn exam_results(
+        &self,
+        actor: &Actor,
+        exam: &str,
+        right_answers: usize,
+        total: usize,
+        passed: Option<bool>,
+    ) -> Vec<Value> {
+        let object = json!({
+            "objectType": "Activity",
+            "id": format!("{}/exams/{}", self.config.homepage, exam),
+            "definition": { "type": "http://adlnet.gov/expapi/activities/assessment" },
+        });
+        let score = json!({
+            "raw": right_answers,
+            "min": 0,
+            "max": total,
+            "scaled": right_answers as f64 / total.max(1) as f64,
+        });
+
+        let mut statements = vec![self.statement(
+            actor,
+            Verb::Completed,
+            object.clone(),
+            json!({ "score": score, "completion": true }),
+        )];
+        if let Some(passed) = passed {
+            let verb = match passed {
+                true => Verb::Passed,
+                false => Verb::Failed,
+            };
+            statements.push(self.statement(
+                actor,
+                verb,
+                object,
+                json!({ "score": score, "success": passed, "completion": true }),
+            ));
+        }
+
+        statements
+    }
+
+    /// Buffers the statements and sends them in the background
+    pub fn record(self: &Arc<Self>, statements: Vec<Value>) {
+        {
+            let mut buffer = self.buffer.lock().unwrap();
+            buffer.extend(statements);
+
+            let overflow = buffer.len().saturating_sub(self.config.buffer_size);
+            if overflow > 0 {
+                warn!("The xAPI buffer is full, {} oldest statements are dropped", overflow);
+                buffer.drain(..overflow);
+            }
+        }
+
+        self.clone().spawn_flush(None);
+    }
+
+    pub fn buffered(&self) -> usize {
+        self.buffer.lock().unwrap().len()
+    }
+
+    /// Only one flush runs at a time, it sends the statements buffered while it runs as well
+    fn spawn_flush(self: Arc<Self>, delay: Option<Duration>) {
+        if self.flushing.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        rt::spawn(async move {
+            if let Some(delay) = delay {
+                delay_for(delay).await;
+            }
+
+            let sent = self.flush().await;
+            self.flushing.store(false, Ordering::SeqCst);
+
+            match sent {
+                false => {
+                    let retry_interval = self.config.retry_interval;
+                    self.spawn_flush(Some(retry_interval));
+                }
+                // Statements recorded right before the flush had finished
+                true if self.buffered() > 0 => self.spawn_flush(None),
+                true => {}
+            }
+        });
+    }
+
+    /// Sends the buffered statements in batches, `false` when the LRS is unreachable and they have to be retried
+    async fn flush(&self) -> bool {
+        loop {
+            let batch = self
+                .buffer
+                .lock()
+                .unwrap()
+                .iter()
+                .take(BATCH_SIZE)
+                .cloned()
+                .collect::<Vec<_>>();
+            if batch.is_empty() {
+                return true;
+            }
+
+            match self.send(&batch).await {
+                Delivery::Sent => {}
+                Delivery::Rejected(reason) => {
+                    error!("The LRS rejected {} xAPI statements - {}", batch.len(), reason);
+                }
+                Delivery::Failed(reason) => {
+                    warn!(
+                        "Failed to send {} xAPI statements, they will be retried - {}",
+                        batch.len(),
+                        reason
+                    );
+                    return false;
+                }
+            }
+
+            // The buffer could have dropped statements of the batch when it overflowed meanwhile
+            let ids = batch.iter().map(|statement| &statement["id"]).collect::<Vec<_>>();
+            let mut buffer = self.buffer.lock().unwrap();
+            while buffer.front().is_some_and(|statement| ids.contains(&&statement["id"])) {
+                buffer.pop_front();
+            }
+        }
+    }
+
+    async fn send(&self, statements: &[Value]) -> Delivery {
+        let mut request = Client::default()
+            .post(format!("{}/statements", self.config.endpoint.trim_end_matches('/')))
+            .header("X-Experience-API-Version", XAPI_VERSION);
+        if let Some(username) = self.config.username.as_ref() {
+            let credentials = format!("{}:{}", username, self.config.password.as_deref().unwrap_or_default());
+            request = request.header(
+                "Authorization",
+                format!("Basic {}", base64::encode_block(credentials.as_bytes())),
+            );
+        }
+
+        match request.send_json(&statements).await {
+            // 409 means the LRS already has statements with these ids, a retry of a request it had taken
+            Ok(response) if response.status().is_success() || response.status().as_u16() == 409 => Delivery::Sent,
+            Ok(response)
+                if response.status().is_client_error() && ![408, 429].contains(&response.status().as_u16()) =>
+            {
+                Delivery::Rejected(response.status().to_string())
+            }
+            Ok(response) => Delivery::Failed(response.status().to_string()),
+            Err(err) => Delivery::Failed(err.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exams_are_completed_and_passed_or_failed() {
+        let xapi = Xapi::new(XapiConfig {
+            endpoint: "http://lrs.example/xapi".to_owned(),
+            username: None,
+            password: None,
+            homepage: DEFAULT_HOMEPAGE.to_owned(),
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            retry_interval: Duration::from_secs(DEFAULT_RETRY_SECS),
+        });
+        let actor = Actor {
+            user_id: 7,
+            name: "Ann Lee".to_owned(),
+        };
+
+        let answered = xapi.answered(&actor, 3, 2, false);
+        assert_eq!(answered["verb"]["id"], Verb::Answered.id());
+        assert_eq!(answered["actor"]["account"]["name"], "7");
+        assert_eq!(answered["object"]["id"], "https://drive-tests/tests/3");
+        assert_eq!(answered["result"], json!({ "success": false, "response": "2" }));
+
+        let statements = xapi.exam_results(&actor, "lti/1", 4, 5, Some(true));
+        let verbs = statements
+            .iter()
+            .map(|statement| statement["verb"]["display"]["en-US"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(verbs, vec![json!("completed"), json!("passed")]);
+        assert_eq!(statements[1]["result"]["score"]["scaled"], 0.8);
+        assert_ne!(statements[0]["id"], statements[1]["id"]);
+        assert_eq!(xapi.exam_results(&actor, "lti/1", 4, 5, None).len(), 1);
+    }
+}
@@ -1,8 +1,12 @@
 use actix_web::{
     body::Body,
-    http::{header::ContentType, StatusCode},
-    test::{call_service, init_service, TestRequest},
-    web, App, HttpResponse,
+    dev::ServiceResponse,
+    http::{
+        header::{self, ContentType},
+        StatusCode,
+    },
+    test::{self, call_service, init_service, TestRequest},
+    web, App, HttpRequest, HttpResponse,
 };
 use lazy_static::lazy_static;
 
@@ -11,15 +15,30 @@ use image::EncodableLayout;
 use serde::Deserialize;
 use uuid::Uuid;
 
-use openssl::base64;
-use std::ops::Deref;
+use openssl::{
+    base64,
+    pkey::{PKey, Private, Public},
+    rsa::Rsa,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    future,
+    ops::Deref,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use lib::{
+    cache::QuestionCache,
     db::{
         model::{self, UserForm},
         remove_user_from_db,
     },
-    web::{AnswerForm, AnswerWithUserForm},
+    irt::IrtConfig,
+    lti::{jwt, LtiConfig},
+    web::{AdminConfig, AnswerForm, AnswerWithUserForm},
+    xapi::XapiConfig,
     *,
 };
 
@@ -69,6 +88,7 @@ struct TestForm {
     #[allow(dead_code)]
     description: String,
     answers: Vec<String>,
+    image_url: Option<String>,
     image: Option<String>,
 }
 
@@ -80,8 +100,7 @@ impl TestForm {
             Body::Bytes(data) => {
                 let json_test = serde_json::from_slice::<TestForm>(data).unwrap();
 
-                if json_test.image.is_some() {
-                    let image_base64 = json_test.image.as_ref().unwrap();
+                if let Some(image_base64) = json_test.image.as_ref() {
                     let image_data = base64::decode_block(image_base64).unwrap();
                     let _ = image::load_from_memory(image_data.as_bytes()).unwrap();
                 }
@@ -105,6 +124,38 @@ fn get_correct_answer_id_from_test_id(test_id: i32) -> u32 {
     selected_test.right_answer_id as u32
 }
 
+fn get_test_id_with_image() -> i32 {
+    use lib::db::schema::tests::dsl::*;
+
+    let db = DB.get().unwrap();
+    tests
+        .select(id)
+        .filter(image_hash.is_not_null())
+        .order(id)
+        .first::<i32>(db.deref())
+        .unwrap()
+}
+
+/// A served test that isn't among the questions of the daily challenge
+fn get_test_id_outside_daily(daily: &Value) -> u32 {
+    use lib::db::schema::tests::dsl::*;
+
+    let questions = daily["questions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|question| question["id"].as_i64().unwrap() as i32)
+        .collect::<Vec<_>>();
+
+    let db = DB.get().unwrap();
+    tests
+        .select(id)
+        .filter(retired.eq(false).and(id.ne_all(questions)))
+        .order(id)
+        .first::<i32>(db.deref())
+        .unwrap() as u32
+}
+
 fn get_user_scores(user: &UserForm) -> u32 {
     use lib::db::schema::users::dsl::*;
 
@@ -140,6 +191,42 @@ async fn test_request() {
     assert!(response.status().is_success());
 }
 
+#[actix_rt::test]
+async fn build_app_serves_all_the_services() {
+    let mut app = init_service(build_app(AppConfig::new(DB.clone()))).await;
+
+    let request = TestRequest::get().uri("/healthy").to_request();
+    let response = call_service(&mut app, request).await;
+    assert!(response.status().is_success());
+
+    let request = TestRequest::get().uri("/test").to_request();
+    let response = call_service(&mut app, request).await;
+    assert!(response.status().is_success());
+}
+
+#[actix_rt::test]
+async fn app_config_mounts_the_services_under_a_prefix() {
+    let config = AppConfig::new(DB.clone()).with_prefix("/drive-tests/");
+    let mut app = init_service(
+        App::new()
+            .route("/", web::get().to(HttpResponse::Ok))
+            .configure(|cfg| config.configure(cfg)),
+    )
+    .await;
+
+    let request = TestRequest::get().uri("/drive-tests/healthy").to_request();
+    let response = call_service(&mut app, request).await;
+    assert!(response.status().is_success());
+
+    let request = TestRequest::get().uri("/healthy").to_request();
+    let response = call_service(&mut app, request).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let request = TestRequest::get().uri("/").to_request();
+    let response = call_service(&mut app, request).await;
+    assert!(response.status().is_success());
+}
+
 #[actix_rt::test]
 async fn create_user() {
     let mut app = init_service(App::new().data(DB.clone()).service(sing_up)).await;
@@ -247,7 +334,7 @@ async fn log_in_for_bad_password() {
 
 #[actix_rt::test]
 async fn get_test_returns_correct_test() {
-    let mut app = init_service(App::new().data(DB.clone()).service(get_test)).await;
+    let mut app = init_service(App::new().data(DB.clone()).data(QuestionCache::new()).service(get_test)).await;
 
     let request = TestRequest::get().uri("/test").to_request();
 
@@ -257,9 +344,112 @@ async fn get_test_returns_correct_test() {
     let _ = TestForm::from_http_response(response.into());
 }
 
+#[actix_rt::test]
+async fn get_test_serves_tests_from_the_cache() {
+    let config = AppConfig::new(DB.clone());
+    let mut app = init_service(build_app(config.clone())).await;
+
+    for _ in 0..3 {
+        let request = TestRequest::get().uri("/test").to_request();
+        let response = call_service(&mut app, request).await;
+        assert!(response.status().is_success());
+    }
+
+    let stats = config.cache.stats();
+    assert_eq!(stats.misses, 1);
+    assert_eq!(stats.hits, 2);
+    assert_ne!(stats.tests, 0);
+
+    config.cache.invalidate();
+
+    let request = TestRequest::get().uri("/test").to_request();
+    let response = call_service(&mut app, request).await;
+    assert!(response.status().is_success());
+    assert_eq!(config.cache.stats().misses, 2);
+}
+
+#[actix_rt::test]
+async fn get_test_returns_image_url_and_base64_only_on_demand() {
+    let mut app = init_service(build_app(AppConfig::new(DB.clone()))).await;
+
+    for _ in 0..20 {
+        let request = TestRequest::get().uri("/test").to_request();
+        let test_form: TestForm = test::read_body_json(call_service(&mut app, request).await).await;
+        assert!(test_form.image.is_none());
+        if let Some(image_url) = test_form.image_url {
+            assert_eq!(image_url, format!("/images/{}", test_form.id));
+        }
+
+        let request = TestRequest::get().uri("/test?image=base64").to_request();
+        let test_form: TestForm = test::read_body_json(call_service(&mut app, request).await).await;
+        assert_eq!(test_form.image.is_some(), test_form.image_url.is_some());
+    }
+}
+
+#[actix_rt::test]
+async fn get_image_supports_conditional_and_range_requests() {
+    let mut app = init_service(build_app(AppConfig::new(DB.clone()))).await;
+    let url = format!("/images/{}", get_test_id_with_image());
+
+    let request = TestRequest::get().uri(&url).to_request();
+    let response = call_service(&mut app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("image/"));
+    assert!(response.headers().contains_key(header::CACHE_CONTROL));
+    assert!(!response.headers().contains_key(header::LAST_MODIFIED));
+    let etag = response.headers().get(header::ETAG).unwrap().clone();
+    let image_data = test::read_body(response).await;
+    let _ = image::load_from_memory(&image_data).unwrap();
+
+    // The image stays cached on the client after a restart of the server
+    let mut restarted_app = init_service(build_app(AppConfig::new(DB.clone()))).await;
+    let request = TestRequest::get()
+        .uri(&url)
+        .header(header::IF_NONE_MATCH, etag)
+        .to_request();
+    let response = call_service(&mut restarted_app, request).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    let request = TestRequest::get()
+        .uri(&url)
+        .header(header::RANGE, "bytes=0-9")
+        .to_request();
+    let response = call_service(&mut app, request).await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        response.headers().get(header::CONTENT_RANGE).unwrap().to_str().unwrap(),
+        format!("bytes 0-9/{}", image_data.len())
+    );
+    assert_eq!(test::read_body(response).await, image_data[..10]);
+
+    let request = TestRequest::get()
+        .uri(&url)
+        .header(header::RANGE, format!("bytes={}-", image_data.len()))
+        .to_request();
+    let response = call_service(&mut app, request).await;
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+
+    let request = TestRequest::get().uri("/images/0").to_request();
+    let response = call_service(&mut app, request).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
 #[actix_rt::test]
 async fn check_answer_returns_right_scores_if_a_test_passed() {
-    let mut app = init_service(App::new().data(DB.clone()).service(get_test).service(check_answer)).await;
+    let mut app = init_service(
+        App::new()
+            .data(DB.clone())
+            .data(QuestionCache::new())
+            .service(get_test)
+            .service(check_answer),
+    )
+    .await;
 
     let request = TestRequest::get().uri("/test").to_request();
     let response = call_service(&mut app, request).await;
@@ -282,7 +472,14 @@ async fn check_answer_returns_right_scores_if_a_test_passed() {
 
 #[actix_rt::test]
 async fn check_answer_return_zero_for_a_failed_test() {
-    let mut app = init_service(App::new().data(DB.clone()).service(get_test).service(check_answer)).await;
+    let mut app = init_service(
+        App::new()
+            .data(DB.clone())
+            .data(QuestionCache::new())
+            .service(get_test)
+            .service(check_answer),
+    )
+    .await;
 
     let request = TestRequest::get().uri("/test").to_request();
     let response = call_service(&mut app, request).await;
@@ -314,6 +511,7 @@ async fn check_answer_with_a_user_save_new_scores_correctly_for_a_passed_test()
     let mut app = init_service(
         App::new()
             .data(DB.clone())
+            .data(QuestionCache::new())
             .service(sing_in)
             .service(sing_up)
             .service(get_test)
@@ -348,11 +546,12 @@ async fn check_answer_with_a_user_save_new_scores_correctly_for_a_passed_test()
             answer_id: correct_answer_id,
         },
         user: user.clone(),
+        response_time_ms: None,
     };
 
     let request = TestRequest::post()
         .set_json(&answer_with_user)
-        .uri(&"/check_test")
+        .uri("/check_test")
         .to_request();
 
     let response = call_service(&mut app, request).await;
@@ -369,6 +568,7 @@ async fn check_answer_with_a_user_for_a_failed_test_doesnt_change_scores() {
     let mut app = init_service(
         App::new()
             .data(DB.clone())
+            .data(QuestionCache::new())
             .service(sing_in)
             .service(sing_up)
             .service(get_test)
@@ -404,6 +604,7 @@ async fn check_answer_with_a_user_for_a_failed_test_doesnt_change_scores() {
 
     let answer_with_user = AnswerWithUserForm {
         user: user.clone(),
+        response_time_ms: None,
         answer: AnswerForm {
             test_id: test_from.id as u32,
             answer_id: bad_answer_id,
@@ -412,7 +613,7 @@ async fn check_answer_with_a_user_for_a_failed_test_doesnt_change_scores() {
 
     let request = TestRequest::post()
         .set_json(&answer_with_user)
-        .uri(&"/check_test")
+        .uri("/check_test")
         .to_request();
 
     let response = call_service(&mut app, request).await;
@@ -429,6 +630,7 @@ async fn passing_many_tests_in_a_row_correctly_processing_scores() {
     let mut app = init_service(
         App::new()
             .data(DB.clone())
+            .data(QuestionCache::new())
             .service(sing_in)
             .service(sing_up)
             .service(get_test)
@@ -459,6 +661,7 @@ async fn passing_many_tests_in_a_row_correctly_processing_scores() {
 
         let answer_with_user = AnswerWithUserForm {
             user: user.clone(),
+            response_time_ms: None,
             answer: AnswerForm {
                 test_id: test_from.id as u32,
                 answer_id: rand_answer,
@@ -467,7 +670,7 @@ async fn passing_many_tests_in_a_row_correctly_processing_scores() {
 
         let request = TestRequest::post()
             .set_json(&answer_with_user)
-            .uri(&"/check_test")
+            .uri("/check_test")
             .to_request();
 
         let response = call_service(&mut app, request).await;
@@ -497,3 +700,861 @@ async fn passing_many_tests_in_a_row_correctly_processing_scores() {
         _ => panic!("Got an expected body from get test request"),
     }
 }
+
+/// A stand-in LMS platform: its key set, token endpoint and the line item scores it received
+fn start_mock_platform(
+    platform_key: &PKey<Private>,
+    tool_key: PKey<Public>,
+) -> (test::TestServer, Arc<Mutex<Vec<Value>>>) {
+    let key_set = jwt::JwkSet {
+        keys: vec![jwt::Jwk::from_key(platform_key, &jwt::key_id(platform_key).unwrap()).unwrap()],
+    };
+    let scores = Arc::new(Mutex::new(Vec::new()));
+    let scores_clone = scores.clone();
+
+    let server = test::start(move || {
+        let key_set = key_set.clone();
+        let tool_key = tool_key.clone();
+        let scores = scores_clone.clone();
+
+        App::new()
+            .route(
+                "/jwks",
+                web::get().to(move || {
+                    let key_set = key_set.clone();
+                    future::ready(HttpResponse::Ok().json(key_set))
+                }),
+            )
+            .route(
+                "/token",
+                web::post().to(move |form: web::Form<HashMap<String, String>>| {
+                    let verified = jwt::verify(&form["client_assertion"], &tool_key).is_ok();
+                    future::ready(match verified {
+                        true => HttpResponse::Ok().json(json!({ "access_token": "platform-token" })),
+                        false => HttpResponse::Unauthorized().finish(),
+                    })
+                }),
+            )
+            .route(
+                "/lineitems/1/scores",
+                web::post().to(move |request: HttpRequest, body: web::Bytes| {
+                    let authorized = request
+                        .headers()
+                        .get(header::AUTHORIZATION)
+                        .is_some_and(|value| value == "Bearer platform-token");
+                    if authorized {
+                        scores
+                            .lock()
+                            .unwrap()
+                            .push(serde_json::from_slice::<Value>(&body).unwrap());
+                    }
+                    future::ready(match authorized {
+                        true => HttpResponse::Ok().finish(),
+                        false => HttpResponse::Unauthorized().finish(),
+                    })
+                }),
+            )
+    });
+
+    (server, scores)
+}
+
+async fn body_json(response: ServiceResponse) -> Value {
+    serde_json::from_slice(&test::read_body(response).await).unwrap()
+}
+
+#[actix_rt::test]
+async fn lti_launches_map_the_user_and_send_the_exam_score_to_the_platform() {
+    let platform_key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let tool_key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let tool_public_key = PKey::public_key_from_der(&tool_key.public_key_to_der().unwrap()).unwrap();
+    let (platform, scores) = start_mock_platform(&platform_key, tool_public_key);
+
+    let config = LtiConfig {
+        issuer: platform.url(""),
+        client_id: "drive-tests".to_owned(),
+        deployment_id: Some("1".to_owned()),
+        auth_url: platform.url("/auth"),
+        launch_url: "https://tool.example/lti/launch".to_owned(),
+        jwks_url: platform.url("/jwks"),
+        token_url: platform.url("/token"),
+        key: tool_key,
+        questions: 20,
+    };
+    let mut app = init_service(build_app(AppConfig::new(DB.clone()).with_lti(config))).await;
+
+    let user = create_rand_user();
+    let subject = Uuid::new_v4().to_string();
+    let mut user_ids = Vec::new();
+    for _ in 0..2 {
+        let login = format!(
+            "/lti/login?iss={}&login_hint={}&target_link_uri=https://tool.example/lti/launch",
+            platform.url(""),
+            subject
+        );
+        let response = call_service(&mut app, TestRequest::get().uri(&login).to_request()).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        let location = response.headers().get(header::LOCATION).unwrap().to_str().unwrap();
+        let auth = serde_urlencoded::from_str::<HashMap<String, String>>(location.split_once('?').unwrap().1).unwrap();
+        assert_eq!(auth["redirect_uri"], "https://tool.example/lti/launch");
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let claims = json!({
+            "iss": platform.url(""),
+            "aud": "drive-tests",
+            "exp": now + 300,
+            "iat": now,
+            "nonce": auth["nonce"],
+            "sub": subject,
+            "given_name": user.name,
+            "family_name": user.second_name,
+            "https://purl.imsglobal.org/spec/lti/claim/message_type": "LtiResourceLinkRequest",
+            "https://purl.imsglobal.org/spec/lti/claim/version": "1.3.0",
+            "https://purl.imsglobal.org/spec/lti/claim/deployment_id": "1",
+            "https://purl.imsglobal.org/spec/lti/claim/custom": { "mode": "exam", "questions": "3" },
+            "https://purl.imsglobal.org/spec/lti-ags/claim/endpoint": {
+                "scope": ["https://purl.imsglobal.org/spec/lti-ags/scope/score"],
+                "lineitem": platform.url("/lineitems/1"),
+            },
+        });
+        let id_token = jwt::sign(&claims, &platform_key, &jwt::key_id(&platform_key).unwrap()).unwrap();
+
+        let request = TestRequest::post()
+            .uri("/lti/launch")
+            .set_form(&[("id_token", id_token.as_str()), ("state", auth["state"].as_str())])
+            .to_request();
+        let response = call_service(&mut app, request).await;
+        assert!(response.status().is_success());
+        let page = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+        let token = page.split("name=\"lti-session\" content=\"").nth(1).unwrap();
+        let token = &token[..token.find('"').unwrap()];
+
+        loop {
+            let request = TestRequest::get().uri(&format!("/lti/sessions/{}", token)).to_request();
+            let session = body_json(call_service(&mut app, request).await).await;
+            if session["finished"] == true {
+                assert_eq!(session["right_answers"], 3);
+                break;
+            }
+
+            let test_id = session["test"]["id"].as_i64().unwrap() as i32;
+            let answer = AnswerForm {
+                test_id: test_id as u32,
+                answer_id: get_correct_answer_id_from_test_id(test_id),
+            };
+            let request = TestRequest::post()
+                .uri(&format!("/lti/sessions/{}/answers", token))
+                .set_json(&answer)
+                .to_request();
+            let result = body_json(call_service(&mut app, request).await).await;
+            // Exams don't reveal the answers
+            assert!(result.get("right_answer_id").is_none());
+            assert_eq!(result["score_sent"], result["finished"]);
+        }
+
+        let db = DB.get().unwrap();
+        user_ids.push(
+            lib::db::schema::users::table
+                .select(lib::db::schema::users::id)
+                .filter(lib::db::schema::users::name.eq(&user.name))
+                .load::<i32>(db.deref())
+                .unwrap(),
+        );
+    }
+
+    let scores_received = scores.lock().unwrap().clone();
+    let user_scores = get_user_scores(&user);
+    remove_user_from_db(user, &web::Data::new(DB.clone()));
+
+    assert_eq!(user_ids[0].len(), 1);
+    assert_eq!(user_ids[0], user_ids[1]);
+    assert_eq!(user_scores, 6 * 5);
+    assert_eq!(scores_received.len(), 2);
+    assert_eq!(scores_received[0]["userId"], json!(subject));
+    assert_eq!(scores_received[0]["scoreGiven"], 3);
+    assert_eq!(scores_received[0]["scoreMaximum"], 3);
+    assert_eq!(scores_received[0]["gradingProgress"], "FullyGraded");
+}
+
+/// A stand-in LRS which is down for its first `outages` requests and records the statements of the others
+fn start_mock_lrs(outages: usize) -> (test::TestServer, Arc<Mutex<Vec<Value>>>) {
+    let statements = Arc::new(Mutex::new(Vec::new()));
+    let statements_clone = statements.clone();
+    let requests = Arc::new(Mutex::new(0));
+
+    let server = test::start(move || {
+        let statements = statements_clone.clone();
+        let requests = requests.clone();
+
+        App::new().route(
+            "/xapi/statements",
+            web::post().to(move |request: HttpRequest, body: web::Json<Vec<Value>>| {
+                let mut requests = requests.lock().unwrap();
+                *requests += 1;
+                let versioned = request.headers().contains_key("X-Experience-API-Version");
+                let response = match (*requests > outages, versioned) {
+                    (true, true) => {
+                        statements.lock().unwrap().extend(body.into_inner());
+                        HttpResponse::Ok().finish()
+                    }
+                    (true, false) => HttpResponse::BadRequest().finish(),
+                    (false, _) => HttpResponse::ServiceUnavailable().finish(),
+                };
+                future::ready(response)
+            }),
+        )
+    });
+
+    (server, statements)
+}
+
+#[actix_rt::test]
+async fn answers_are_sent_to_the_lrs_as_xapi_statements_once_it_is_up() {
+    let (lrs, statements) = start_mock_lrs(2);
+    let config = AppConfig::new(DB.clone()).with_xapi(XapiConfig {
+        endpoint: lrs.url("/xapi"),
+        username: Some("drive-tests".to_owned()),
+        password: Some("secret".to_owned()),
+        homepage: "https://drive-tests.example".to_owned(),
+        buffer_size: 100,
+        retry_interval: Duration::from_millis(50),
+    });
+    let mut app = init_service(build_app(config)).await;
+
+    let user = create_rand_user();
+    let request = TestRequest::post().set_json(&user).uri("/user").to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    let test_id = get_test_id_with_image();
+    let right_answer_id = get_correct_answer_id_from_test_id(test_id);
+    for answer_id in [right_answer_id, right_answer_id % 2 + 1] {
+        let answer_with_user = AnswerWithUserForm {
+            answer: AnswerForm {
+                test_id: test_id as u32,
+                answer_id,
+            },
+            user: user.clone(),
+            response_time_ms: None,
+        };
+        let request = TestRequest::post()
+            .set_json(&answer_with_user)
+            .uri("/check_test")
+            .to_request();
+        assert!(call_service(&mut app, request).await.status().is_success());
+    }
+
+    for _ in 0..100 {
+        if statements.lock().unwrap().len() >= 2 {
+            break;
+        }
+        actix_web::rt::time::delay_for(Duration::from_millis(50)).await;
+    }
+    let statements = statements.lock().unwrap().clone();
+    remove_user_from_db(user.clone(), &web::Data::new(DB.clone()));
+
+    assert_eq!(statements.len(), 2);
+    for (statement, success) in statements.iter().zip([true, false]) {
+        assert_eq!(statement["verb"]["id"], "http://adlnet.gov/expapi/verbs/answered");
+        assert_eq!(
+            statement["actor"]["name"],
+            format!("{} {}", user.name, user.second_name)
+        );
+        assert_eq!(
+            statement["object"]["id"],
+            format!("https://drive-tests.example/tests/{}", test_id)
+        );
+        assert_eq!(statement["result"]["success"], success);
+    }
+}
+
+#[actix_rt::test]
+async fn leaderboard_shows_the_callers_rank_under_their_display_name() {
+    let mut app = init_service(build_app(AppConfig::new(DB.clone()))).await;
+
+    let user = create_rand_user();
+    let request = TestRequest::post().set_json(&user).uri("/user").to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    let test_id = get_test_id_with_image();
+    let answer_with_user = AnswerWithUserForm {
+        answer: AnswerForm {
+            test_id: test_id as u32,
+            answer_id: get_correct_answer_id_from_test_id(test_id),
+        },
+        user: user.clone(),
+        response_time_ms: None,
+    };
+    let request = TestRequest::post()
+        .set_json(&answer_with_user)
+        .uri("/check_test")
+        .to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    let display_name = Uuid::new_v4().to_string();
+    let request = TestRequest::put()
+        .set_json(&json!({ "user": user, "display_name": display_name }))
+        .uri("/user/privacy")
+        .to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    let request = TestRequest::get()
+        .set_json(&user)
+        .uri("/leaderboard?window=week&per_page=100")
+        .to_request();
+    let leaderboard = body_json(call_service(&mut app, request).await).await;
+
+    let mut wrong_password = user.clone();
+    wrong_password.password = Uuid::new_v4().to_string();
+    let request = TestRequest::get()
+        .set_json(&wrong_password)
+        .uri("/leaderboard")
+        .to_request();
+    let forbidden = call_service(&mut app, request).await.status();
+
+    let request = TestRequest::get().uri("/leaderboard?page=1000").to_request();
+    let anonymous = body_json(call_service(&mut app, request).await).await;
+
+    remove_user_from_db(user, &web::Data::new(DB.clone()));
+
+    let rank = leaderboard["me"]["rank"].as_u64().unwrap();
+    assert_eq!(leaderboard["window"], "week");
+    assert!(leaderboard["me"]["scores"].as_i64().unwrap() >= 5);
+    assert_eq!(leaderboard["me"]["hidden"], false);
+    assert!(leaderboard["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|entry| entry["name"] == json!(display_name) && entry["rank"] == rank));
+    assert_eq!(forbidden, StatusCode::FORBIDDEN);
+    assert_eq!(anonymous["me"], Value::Null);
+    assert_eq!(anonymous["entries"], json!([]));
+}
+
+#[actix_rt::test]
+async fn progress_counts_the_answers_of_the_user() {
+    let mut app = init_service(build_app(AppConfig::new(DB.clone()))).await;
+
+    let user = create_rand_user();
+    let request = TestRequest::post().set_json(&user).uri("/user").to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    let test_id = get_test_id_with_image();
+    let right_answer_id = get_correct_answer_id_from_test_id(test_id);
+    for answer_id in [right_answer_id, right_answer_id % 2 + 1] {
+        let answer_with_user = AnswerWithUserForm {
+            answer: AnswerForm {
+                test_id: test_id as u32,
+                answer_id,
+            },
+            user: user.clone(),
+            response_time_ms: None,
+        };
+        let request = TestRequest::post()
+            .set_json(&answer_with_user)
+            .uri("/check_test")
+            .to_request();
+        assert!(call_service(&mut app, request).await.status().is_success());
+    }
+
+    let request = TestRequest::get()
+        .set_json(&user)
+        .uri("/user/progress?period=day")
+        .to_request();
+    let progress = body_json(call_service(&mut app, request).await).await;
+
+    let request = TestRequest::get().uri("/user/progress").to_request();
+    let without_user = call_service(&mut app, request).await.status();
+
+    remove_user_from_db(user, &web::Data::new(DB.clone()));
+
+    assert_eq!(progress["seen"], 1);
+    assert_eq!(progress["answers"], 2);
+    assert_eq!(progress["accuracy"], 0.5);
+    assert!(progress["total"].as_u64().unwrap() >= 1);
+    assert_eq!(progress["period"], "day");
+    assert_eq!(progress["trend"].as_array().unwrap().last().unwrap()["answers"], 2);
+    assert_eq!(without_user, StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn review_mode_serves_unseen_tests_before_the_scheduled_ones() {
+    let mut app = init_service(build_app(AppConfig::new(DB.clone()))).await;
+
+    let user = create_rand_user();
+    let request = TestRequest::post().set_json(&user).uri("/user").to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    let request = TestRequest::get().set_json(&user).uri("/test?mode=review").to_request();
+    let first = body_json(call_service(&mut app, request).await).await;
+    let test_id = first["id"].as_u64().unwrap() as u32;
+    let answer_with_user = AnswerWithUserForm {
+        answer: AnswerForm {
+            test_id,
+            answer_id: get_correct_answer_id_from_test_id(test_id as i32),
+        },
+        user: user.clone(),
+        response_time_ms: None,
+    };
+    let request = TestRequest::post()
+        .set_json(&answer_with_user)
+        .uri("/check_test")
+        .to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    let request = TestRequest::get().set_json(&user).uri("/test?mode=review").to_request();
+    let second = body_json(call_service(&mut app, request).await).await;
+
+    let request = TestRequest::get().uri("/test?mode=review").to_request();
+    let without_user = call_service(&mut app, request).await.status();
+
+    remove_user_from_db(user, &web::Data::new(DB.clone()));
+
+    assert_eq!(first["due_at"], Value::Null);
+    assert_eq!(second["due_at"], Value::Null);
+    assert_ne!(second["id"], first["id"]);
+    assert_eq!(without_user, StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn mistakes_mode_serves_the_tests_answered_wrong_until_they_are_cleared() {
+    let mut app = init_service(build_app(AppConfig::new(DB.clone()))).await;
+
+    let user = create_rand_user();
+    let request = TestRequest::post().set_json(&user).uri("/user").to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    let request = TestRequest::get()
+        .set_json(&user)
+        .uri("/test?mode=mistakes")
+        .to_request();
+    let before_mistakes = call_service(&mut app, request).await.status();
+
+    let test_id = get_test_id_with_image();
+    let right_answer_id = get_correct_answer_id_from_test_id(test_id);
+    let mut served = Vec::new();
+    for answer_id in [right_answer_id % 2 + 1, right_answer_id, right_answer_id] {
+        let answer_with_user = AnswerWithUserForm {
+            answer: AnswerForm {
+                test_id: test_id as u32,
+                answer_id,
+            },
+            user: user.clone(),
+            response_time_ms: None,
+        };
+        let request = TestRequest::post()
+            .set_json(&answer_with_user)
+            .uri("/check_test")
+            .to_request();
+        assert!(call_service(&mut app, request).await.status().is_success());
+
+        let request = TestRequest::get()
+            .set_json(&user)
+            .uri("/test?mode=mistakes&clear_after=2")
+            .to_request();
+        let response = call_service(&mut app, request).await;
+        served.push((response.status(), body_json(response).await["id"].clone()));
+    }
+
+    remove_user_from_db(user, &web::Data::new(DB.clone()));
+
+    assert_eq!(before_mistakes, StatusCode::NOT_FOUND);
+    assert_eq!(served[0], (StatusCode::OK, json!(test_id)));
+    assert_eq!(served[1], (StatusCode::OK, json!(test_id)));
+    assert_eq!(served[2], (StatusCode::NOT_FOUND, Value::Null));
+}
+
+#[actix_rt::test]
+async fn adaptive_mode_serves_tests_until_the_ability_is_precise_enough() {
+    let mut app = init_service(build_app(AppConfig::new(DB.clone()))).await;
+    let precise_enough = IrtConfig {
+        target_se: 2.0,
+        ..IrtConfig::default()
+    };
+    let mut finishing_app = init_service(build_app(AppConfig::new(DB.clone()).with_irt(precise_enough))).await;
+
+    let user = create_rand_user();
+    let request = TestRequest::post().set_json(&user).uri("/user").to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    let request = TestRequest::get()
+        .set_json(&user)
+        .uri("/test?mode=adaptive")
+        .to_request();
+    let served = body_json(call_service(&mut app, request).await).await;
+    let request = TestRequest::get()
+        .set_json(&user)
+        .uri("/test?mode=adaptive")
+        .to_request();
+    let finished = body_json(call_service(&mut finishing_app, request).await).await;
+    let request = TestRequest::get().uri("/test?mode=adaptive").to_request();
+    let without_user = call_service(&mut app, request).await.status();
+
+    remove_user_from_db(user, &web::Data::new(DB.clone()));
+
+    assert!(served["id"].is_number());
+    assert_eq!(served["adaptive"]["answered"], 0);
+    assert_eq!(served["adaptive"]["finished"], false);
+    assert!(served["adaptive"]["standard_error"].as_f64().unwrap() > 0.3);
+    assert_eq!(finished["id"], Value::Null);
+    assert_eq!(finished["adaptive"]["finished"], true);
+    assert_eq!(without_user, StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn question_quality_report_needs_the_admin_token() {
+    let admin = AdminConfig {
+        token: "secret-token".to_owned(),
+    };
+    let mut app = init_service(build_app(AppConfig::new(DB.clone()).with_admin(admin))).await;
+    let mut app_without_admin = init_service(build_app(AppConfig::new(DB.clone()))).await;
+
+    let request = |token: Option<&str>| {
+        let request = TestRequest::get().uri("/admin/questions/quality?flagged=false");
+        match token {
+            Some(token) => request.header(header::AUTHORIZATION, format!("Bearer {}", token)),
+            None => request,
+        }
+        .to_request()
+    };
+    let without_token = call_service(&mut app, request(None)).await.status();
+    let wrong_token = call_service(&mut app, request(Some("secret-tokem"))).await.status();
+    let report = body_json(call_service(&mut app, request(Some("secret-token"))).await).await;
+    let not_mounted = call_service(&mut app_without_admin, request(Some("secret-token")))
+        .await
+        .status();
+
+    assert_eq!(without_token, StatusCode::UNAUTHORIZED);
+    assert_eq!(wrong_token, StatusCode::UNAUTHORIZED);
+    assert_eq!(not_mounted, StatusCode::NOT_FOUND);
+    let report = report.as_array().unwrap();
+    assert!(!report.is_empty());
+    let test_id = get_test_id_with_image();
+    let quality = report.iter().find(|quality| quality["test_id"] == test_id).unwrap();
+    assert!(quality["frequencies"].as_array().unwrap().len() >= 2);
+    assert!(quality["flags"].is_array());
+}
+
+#[actix_rt::test]
+async fn marathon_mode_resumes_where_the_user_stopped_until_it_is_reset() {
+    let mut app = init_service(build_app(AppConfig::new(DB.clone()))).await;
+
+    let user = create_rand_user();
+    let request = TestRequest::post().set_json(&user).uri("/user").to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    let request = TestRequest::get()
+        .set_json(&user)
+        .uri("/test?mode=marathon")
+        .to_request();
+    let first = body_json(call_service(&mut app, request).await).await;
+    let test_id = first["id"].as_u64().unwrap() as u32;
+    let answer_with_user = AnswerWithUserForm {
+        answer: AnswerForm {
+            test_id,
+            answer_id: get_correct_answer_id_from_test_id(test_id as i32),
+        },
+        user: user.clone(),
+        response_time_ms: None,
+    };
+    let request = TestRequest::post()
+        .set_json(&answer_with_user)
+        .uri("/check_test")
+        .to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    let request = TestRequest::get()
+        .set_json(&user)
+        .uri("/test?mode=marathon")
+        .to_request();
+    let second = body_json(call_service(&mut app, request).await).await;
+    let request = TestRequest::get().set_json(&user).uri("/marathon").to_request();
+    let progress = body_json(call_service(&mut app, request).await).await;
+    let request = TestRequest::post()
+        .set_json(&json!({ "user": user, "category": "No such category" }))
+        .uri("/marathon")
+        .to_request();
+    let unknown_category = call_service(&mut app, request).await.status();
+    let request = TestRequest::delete().set_json(&user).uri("/marathon").to_request();
+    let reset = call_service(&mut app, request).await.status();
+    let request = TestRequest::get().set_json(&user).uri("/marathon").to_request();
+    let after_reset = call_service(&mut app, request).await.status();
+
+    remove_user_from_db(user, &web::Data::new(DB.clone()));
+
+    assert_eq!(first["marathon"]["position"], 0);
+    assert!(second["id"].as_u64().unwrap() > test_id as u64);
+    assert_eq!(second["marathon"]["position"], 1);
+    assert_eq!(progress["position"], 1);
+    assert_eq!(progress["accuracy"], 1.0);
+    assert_eq!(progress["category"], Value::Null);
+    assert_eq!(unknown_category, StatusCode::BAD_REQUEST);
+    assert_eq!(reset, StatusCode::OK);
+    assert_eq!(after_reset, StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn practice_serves_only_the_tests_of_a_collection() {
+    let mut app = init_service(build_app(AppConfig::new(DB.clone()))).await;
+
+    let user = create_rand_user();
+    let request = TestRequest::post().set_json(&user).uri("/user").to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    let request = TestRequest::post()
+        .set_json(&json!({ "user": user, "name": "Tricky" }))
+        .uri("/collections")
+        .to_request();
+    let response = call_service(&mut app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let collection_id = body_json(response).await["id"].as_i64().unwrap();
+    let test_id = get_test_id_with_image();
+    let request = TestRequest::put()
+        .set_json(&user)
+        .uri(&format!("/collections/{}/tests/{}", collection_id, test_id))
+        .to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    let mut served = Vec::new();
+    for _ in 0..5 {
+        let request = TestRequest::get()
+            .set_json(&user)
+            .uri(&format!("/test?collection={}", collection_id))
+            .to_request();
+        served.push(body_json(call_service(&mut app, request).await).await["id"].clone());
+    }
+    let request = TestRequest::get().set_json(&user).uri("/bookmarks").to_request();
+    let bookmarks = body_json(call_service(&mut app, request).await).await;
+    let request = TestRequest::get()
+        .set_json(&user)
+        .uri(&format!("/test?mode=review&collection={}", collection_id))
+        .to_request();
+    let with_another_mode = call_service(&mut app, request).await.status();
+    let request = TestRequest::delete()
+        .set_json(&user)
+        .uri(&format!("/bookmarks/{}", test_id))
+        .to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+    let request = TestRequest::get()
+        .set_json(&user)
+        .uri(&format!("/test?collection={}", collection_id))
+        .to_request();
+    let emptied = call_service(&mut app, request).await.status();
+
+    remove_user_from_db(user, &web::Data::new(DB.clone()));
+
+    assert!(served.iter().all(|id| *id == json!(test_id)));
+    assert_eq!(bookmarks[0]["test_id"], test_id);
+    assert_eq!(bookmarks[0]["collections"], json!([collection_id]));
+    assert_eq!(with_another_mode, StatusCode::BAD_REQUEST);
+    assert_eq!(emptied, StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn daily_challenge_is_answered_once_per_question() {
+    let mut app = init_service(build_app(AppConfig::new(DB.clone()))).await;
+
+    let user = create_rand_user();
+    let request = TestRequest::post().set_json(&user).uri("/user").to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    let request = TestRequest::put()
+        .set_json(&json!({ "user": user, "time_zone": "Mars/Olympus" }))
+        .uri("/user/time_zone")
+        .to_request();
+    let unknown_time_zone = call_service(&mut app, request).await.status();
+    let request = TestRequest::put()
+        .set_json(&json!({ "user": user, "time_zone": "Europe/Kyiv" }))
+        .uri("/user/time_zone")
+        .to_request();
+    let time_zone = body_json(call_service(&mut app, request).await).await;
+
+    let request = TestRequest::get().set_json(&user).uri("/daily").to_request();
+    let daily = body_json(call_service(&mut app, request).await).await;
+    let test_id = daily["questions"][0]["id"].as_u64().unwrap() as u32;
+    let answer = |test_id: u32| AnswerWithUserForm {
+        answer: AnswerForm {
+            test_id,
+            answer_id: get_correct_answer_id_from_test_id(test_id as i32),
+        },
+        user: user.clone(),
+        response_time_ms: None,
+    };
+    let request = TestRequest::post()
+        .set_json(&answer(test_id))
+        .uri("/daily/answer")
+        .to_request();
+    let answered = body_json(call_service(&mut app, request).await).await;
+    let request = TestRequest::post()
+        .set_json(&answer(test_id))
+        .uri("/daily/answer")
+        .to_request();
+    let answered_twice = call_service(&mut app, request).await.status();
+    let other_test_id = get_test_id_outside_daily(&daily);
+    let request = TestRequest::post()
+        .set_json(&answer(other_test_id))
+        .uri("/daily/answer")
+        .to_request();
+    let not_today = call_service(&mut app, request).await.status();
+    let request = TestRequest::get().set_json(&user).uri("/daily").to_request();
+    let after_answer = body_json(call_service(&mut app, request).await).await;
+    let request = TestRequest::get()
+        .set_json(&user)
+        .uri("/daily/leaderboard?per_page=100")
+        .to_request();
+    let leaderboard = body_json(call_service(&mut app, request).await).await;
+    let request = TestRequest::get().uri("/daily/leaderboard?day=yesterday").to_request();
+    let wrong_day = call_service(&mut app, request).await.status();
+
+    remove_user_from_db(user.clone(), &web::Data::new(DB.clone()));
+
+    assert_eq!(unknown_time_zone, StatusCode::BAD_REQUEST);
+    assert_eq!(time_zone["time_zone"], "Europe/Kyiv");
+    assert_eq!(daily["day"], time_zone["day"]);
+    assert_eq!(daily["answers"], json!([]));
+    assert_eq!(daily["finished"], false);
+    assert_eq!(answered["scores"], 5);
+    assert_eq!(answered_twice, StatusCode::CONFLICT);
+    assert_eq!(not_today, StatusCode::BAD_REQUEST);
+    assert_eq!(after_answer["correct"], 1);
+    assert_eq!(after_answer["answers"][0]["test_id"], test_id);
+    assert_eq!(leaderboard["day"], daily["day"]);
+    let name = format!("{} {}", user.name, user.second_name);
+    let entry = leaderboard["entries"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["name"] == name.as_str())
+        .unwrap();
+    assert_eq!(entry["correct"], 1);
+    assert_eq!(wrong_day, StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn daily_challenge_of_a_day_overlapping_another_one_is_rejected() {
+    let mut app = init_service(build_app(AppConfig::new(DB.clone()))).await;
+
+    let user = create_rand_user();
+    let request = TestRequest::post().set_json(&user).uri("/user").to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    // The days of UTC-12 and of UTC+14 always overlap, and their dates always differ
+    let mut outcomes = Vec::new();
+    for time_zone in &["Etc/GMT+12", "Pacific/Kiritimati"] {
+        let request = TestRequest::put()
+            .set_json(&json!({ "user": user, "time_zone": time_zone }))
+            .uri("/user/time_zone")
+            .to_request();
+        assert!(call_service(&mut app, request).await.status().is_success());
+        let request = TestRequest::get().set_json(&user).uri("/daily").to_request();
+        let daily = body_json(call_service(&mut app, request).await).await;
+        let test_id = daily["questions"][0]["id"].as_u64().unwrap() as u32;
+        let request = TestRequest::post()
+            .set_json(&AnswerWithUserForm {
+                answer: AnswerForm {
+                    test_id,
+                    answer_id: get_correct_answer_id_from_test_id(test_id as i32),
+                },
+                user: user.clone(),
+                response_time_ms: None,
+            })
+            .uri("/daily/answer")
+            .to_request();
+        let response = call_service(&mut app, request).await;
+        outcomes.push((response.status(), body_json(response).await));
+    }
+
+    remove_user_from_db(user.clone(), &web::Data::new(DB.clone()));
+
+    assert_eq!(outcomes[0].0, StatusCode::OK);
+    assert_eq!(outcomes[1].0, StatusCode::CONFLICT);
+    assert!(outcomes[1].1["description"]
+        .as_str()
+        .unwrap()
+        .contains("another time zone"));
+}
+
+#[actix_rt::test]
+async fn perfect_daily_challenge_earns_a_badge_with_a_notification() {
+    let mut app = init_service(build_app(AppConfig::new(DB.clone()))).await;
+
+    let user = create_rand_user();
+    let request = TestRequest::post().set_json(&user).uri("/user").to_request();
+    assert!(call_service(&mut app, request).await.status().is_success());
+
+    let request = TestRequest::get()
+        .set_json(&user)
+        .uri("/user/achievements")
+        .to_request();
+    let before = body_json(call_service(&mut app, request).await).await;
+    let request = TestRequest::get().set_json(&user).uri("/daily").to_request();
+    let daily = body_json(call_service(&mut app, request).await).await;
+    for question in daily["questions"].as_array().unwrap() {
+        let test_id = question["id"].as_u64().unwrap() as u32;
+        let answer = AnswerWithUserForm {
+            answer: AnswerForm {
+                test_id,
+                answer_id: get_correct_answer_id_from_test_id(test_id as i32),
+            },
+            user: user.clone(),
+            response_time_ms: None,
+        };
+        let request = TestRequest::post().set_json(&answer).uri("/daily/answer").to_request();
+        assert!(call_service(&mut app, request).await.status().is_success());
+    }
+    let request = TestRequest::get()
+        .set_json(&user)
+        .uri("/user/achievements")
+        .to_request();
+    let after = body_json(call_service(&mut app, request).await).await;
+    let request = TestRequest::get()
+        .set_json(&user)
+        .uri("/user/notifications")
+        .to_request();
+    let notifications = body_json(call_service(&mut app, request).await).await;
+    let shown = notifications
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|earned| earned["id"].clone())
+        .collect::<Vec<_>>();
+    let request = TestRequest::delete()
+        .set_json(&json!({ "user": user, "achievements": shown }))
+        .uri("/user/notifications")
+        .to_request();
+    let dismissed = call_service(&mut app, request).await.status();
+    let request = TestRequest::get()
+        .set_json(&user)
+        .uri("/user/notifications")
+        .to_request();
+    let after_dismissed = body_json(call_service(&mut app, request).await).await;
+    let wrong_password = UserForm {
+        password: "wrong password".to_owned(),
+        ..user.clone()
+    };
+    let request = TestRequest::get()
+        .set_json(&wrong_password)
+        .uri("/user/achievements")
+        .to_request();
+    let forbidden = call_service(&mut app, request).await.status();
+
+    remove_user_from_db(user, &web::Data::new(DB.clone()));
+
+    let perfect_exam = |achievements: &Value| {
+        achievements
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|achievement| achievement["id"] == "first_perfect_exam")
+            .cloned()
+            .unwrap()
+    };
+    assert_eq!(perfect_exam(&before)["earned_at"], Value::Null);
+    assert_eq!(perfect_exam(&before)["progress"], json!({"current": 0, "target": 1}));
+    assert!(perfect_exam(&after)["earned_at"].is_i64());
+    assert_eq!(notifications.as_array().unwrap().len(), 1);
+    assert_eq!(notifications[0]["id"], "first_perfect_exam");
+    assert_eq!(notifications[0]["title"], "First perfect exam");
+    assert_eq!(dismissed, StatusCode::OK);
+    assert_eq!(after_dismissed, json!([]));
+    assert_eq!(forbidden, StatusCode::FORBIDDEN);
+}